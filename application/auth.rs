@@ -1,7 +1,13 @@
+mod jwt;
 mod middleware;
+mod paseto;
 mod token;
 mod user;
 
+pub use jwt::JwtVerifier;
 pub use middleware::token_authenticator;
+pub use paseto::{
+    public_key_id, verify_signed_token, Mutation, SignedMutation, SignedToken, PASETO_PREFIX,
+};
 pub use token::{generate_new_token, hash};
 pub use user::AuthenticatedUser;
@@ -0,0 +1,178 @@
+//! Verifies the JWTs the GraphQL API is called with.
+//!
+//! Tokens are checked against the keys the identity provider publishes in its JWKS,
+//! which are cached and only reloaded when a token is signed with a key they don't
+//! contain, or once they're older than `KEYS_MAX_AGE`.
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use anyhow::Context;
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{decode, decode_header, Algorithm, DecodingKey, Validation};
+use serde::Deserialize;
+use tokio::sync::{Mutex, RwLock};
+use tracing::{error, info, warn};
+
+use crate::auth::AuthenticatedUser;
+use crate::config::{JwksSource, JwtConfig};
+use crate::error::{AppError, AppResult};
+
+/// Keys are reloaded after this long even if they're still used, so revoked keys are dropped.
+const KEYS_MAX_AGE: Duration = Duration::from_secs(60 * 60);
+/// Unknown keys don't reload the keys more often than this, so bogus tokens can't
+/// have every request fetch the JWKS.
+const MIN_RELOAD_INTERVAL: Duration = Duration::from_secs(60);
+/// Requests that need the keys wait for the JWKS at most this long.
+const FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Deserialize)]
+struct Claims {
+    /// The user ID set by the pre token generation trigger.
+    autogen_id: String,
+}
+
+struct CachedKeys {
+    keys: JwkSet,
+    loaded_at: Instant,
+}
+
+pub struct JwtVerifier {
+    source: JwksSource,
+    validation: Validation,
+    client: reqwest::Client,
+    cache: RwLock<Option<CachedKeys>>,
+    /// Held while the keys are reloaded, so only one request fetches them at a time while
+    /// the others keep using the cached keys.
+    reloading: Mutex<()>,
+}
+
+impl JwtVerifier {
+    /// Nothing is loaded until the first token is verified.
+    pub fn new(config: &JwtConfig) -> Self {
+        // Cognito signs with RS256, never trust the algorithm named in the token itself
+        let mut validation = Validation::new(Algorithm::RS256);
+        validation.set_issuer(&[&config.issuer]);
+        validation.set_audience(&[&config.audience]);
+        validation.set_required_spec_claims(&["exp", "iss", "aud"]);
+
+        Self {
+            source: config.jwks.clone(),
+            validation,
+            client: build_client(FETCH_TIMEOUT),
+            cache: RwLock::new(None),
+            reloading: Mutex::new(()),
+        }
+    }
+
+    /// Sets how long fetching the JWKS can take before it's given up on.
+    pub fn with_fetch_timeout(mut self, timeout: Duration) -> Self {
+        self.client = build_client(timeout);
+        self
+    }
+
+    /// The user the token was issued to, if it's correctly signed and still valid.
+    pub async fn verify(&self, token: &str) -> AppResult<AuthenticatedUser> {
+        let header = decode_header(token).map_err(invalid_token)?;
+        let key_id = header
+            .kid
+            .ok_or_else(|| invalid_token("the token does not name its key"))?;
+        let key = self.find_key(&key_id).await?;
+
+        let claims = decode::<Claims>(token, &key, &self.validation)
+            .map_err(invalid_token)?
+            .claims;
+        let id = u32::from_str(&claims.autogen_id).map_err(invalid_token)?;
+
+        Ok(AuthenticatedUser { id })
+    }
+
+    async fn find_key(&self, key_id: &str) -> AppResult<DecodingKey> {
+        let needs_reload = match self.cache.read().await.as_ref() {
+            None => true,
+            Some(cached) => {
+                let age = cached.loaded_at.elapsed();
+                let is_unknown = cached.keys.find(key_id).is_none();
+                age >= KEYS_MAX_AGE || (is_unknown && age >= MIN_RELOAD_INTERVAL)
+            }
+        };
+        if needs_reload {
+            self.reload_keys().await?;
+        }
+
+        let cache = self.cache.read().await;
+        let jwk = cache
+            .as_ref()
+            .and_then(|cached| cached.keys.find(key_id))
+            .ok_or_else(|| invalid_token(format!("unknown key {}", key_id)))?;
+        DecodingKey::from_jwk(jwk).map_err(invalid_token)
+    }
+
+    async fn reload_keys(&self) -> AppResult<()> {
+        let _reloading = self.reloading.lock().await;
+        // another request may have reloaded the keys while this one waited for the lock
+        let (is_reloaded, has_keys) = match self.cache.read().await.as_ref() {
+            Some(cached) => (cached.loaded_at.elapsed() < MIN_RELOAD_INTERVAL, true),
+            None => (false, false),
+        };
+        if is_reloaded {
+            return Ok(());
+        }
+
+        // the cache isn't locked while the keys are fetched, so requests using the cached
+        // keys don't wait for the identity provider
+        match self.load_keys().await {
+            Ok(keys) => {
+                info!(keys = keys.keys.len(), "loaded the JWT signing keys");
+                *self.cache.write().await = Some(CachedKeys {
+                    keys,
+                    loaded_at: Instant::now(),
+                });
+                Ok(())
+            }
+            // the keys we have are still better than rejecting every token
+            Err(err) if has_keys => {
+                let error_message = err.to_string();
+                error!(error_message, "failed to reload the JWT signing keys");
+                Ok(())
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    async fn load_keys(&self) -> AppResult<JwkSet> {
+        let keys = match &self.source {
+            JwksSource::Url(url) => self
+                .client
+                .get(url.clone())
+                .send()
+                .await
+                .and_then(|response| response.error_for_status())
+                .with_context(|| format!("failed to fetch the JWKS from {}", url))?
+                .json()
+                .await
+                .with_context(|| format!("invalid JWKS at {}", url))?,
+            JwksSource::File(path) => {
+                let contents = tokio::fs::read(path)
+                    .await
+                    .with_context(|| format!("failed to read the JWKS from {}", path.display()))?;
+                serde_json::from_slice(&contents)
+                    .with_context(|| format!("invalid JWKS in {}", path.display()))?
+            }
+        };
+
+        Ok(keys)
+    }
+}
+
+fn build_client(timeout: Duration) -> reqwest::Client {
+    reqwest::Client::builder()
+        .timeout(timeout)
+        .build()
+        .expect("the HTTP client to build")
+}
+
+fn invalid_token(reason: impl ToString) -> AppError {
+    let reason = reason.to_string();
+    warn!(reason, "rejected a JWT");
+    AppError::Unauthorized(format!("invalid token: {}", reason))
+}
@@ -4,29 +4,66 @@ use axum::middleware::Next;
 use axum::response::IntoResponse;
 use tracing::{error, warn};
 
-use crate::auth::AuthenticatedUser;
-use crate::repository::DynRepository;
+use crate::auth::{verify_signed_token, AuthenticatedUser, PASETO_PREFIX};
+use crate::error::AppError;
+use crate::models::token::now;
+use crate::router::AppState;
 
 pub async fn token_authenticator<B>(
-    State(repository): State<DynRepository>,
+    State((repository, _, config)): State<AppState>,
     mut request: Request<B>,
     next: Next<B>,
 ) -> impl IntoResponse {
     if let Some(auth_header) = request.headers().get("Authorization") {
         let token = auth_header.as_bytes();
-        match repository.get_auth_token(token).await {
-            Ok(Some(t)) => {
-                let user = AuthenticatedUser { id: t.user_id };
-                request.extensions_mut().insert(user);
-                return next.run(request).await;
+        let used_at = now();
+        if token.starts_with(PASETO_PREFIX.as_bytes()) {
+            let token = String::from_utf8_lossy(token);
+            let verified =
+                verify_signed_token(&repository, &config.domain_name, &token, used_at).await;
+            match verified {
+                Ok(signed) => {
+                    request.extensions_mut().insert(signed.user);
+                    request.extensions_mut().insert(signed.scopes);
+                    if let Some(mutation) = signed.mutation {
+                        request.extensions_mut().insert(mutation);
+                    }
+                    return next.run(request).await;
+                }
+                // the reason has already been logged
+                Err(AppError::Unauthorized(_)) => {}
+                Err(err) => {
+                    error!(
+                        err = err.to_string(),
+                        "error in trying to verify signed token"
+                    );
+                }
             }
-            Err(err) => {
-                error!(
-                    err = err.to_string(),
-                    "error in trying to get token for user"
-                );
+        } else {
+            match repository.get_auth_token(token).await {
+                Ok(Some(t)) if t.is_expired(used_at) => {
+                    warn!(token_id = t.token_id, "attempt to use an expired token");
+                }
+                Ok(Some(t)) => {
+                    if t.needs_use_recorded(used_at) {
+                        if let Err(err) = repository.record_token_use(token, used_at).await {
+                            // the request shouldn't fail because of the bookkeeping
+                            warn!(err = err.to_string(), "failed to record the use of a token");
+                        }
+                    }
+                    let user = AuthenticatedUser { id: t.user_id };
+                    request.extensions_mut().insert(user);
+                    request.extensions_mut().insert(t.scopes);
+                    return next.run(request).await;
+                }
+                Err(err) => {
+                    error!(
+                        err = err.to_string(),
+                        "error in trying to get token for user"
+                    );
+                }
+                _ => {}
             }
-            _ => {}
         }
     }
 
@@ -0,0 +1,303 @@
+//! Verifies the PASETO tokens cargo's `cargo:paseto` credential provider signs requests with.
+//!
+//! The provider signs a v3 public token with the user's private key for every request
+//! (RFC 3231), so the registry only ever stores public keys. Tokens are short-lived, and
+//! the ones that change the registry carry claims tying them to a single request, which
+//! can't be replayed.
+//!
+//! The registry doesn't issue challenges, so a token that only reads the registry can be
+//! replayed until it's older than `MAX_TOKEN_AGE`.
+use std::fmt;
+
+use hex::ToHex;
+use pasetors::keys::AsymmetricPublicKey;
+use pasetors::paserk::{FormatAsPaserk, Id};
+use pasetors::token::UntrustedToken;
+use pasetors::version3::{PublicToken, V3};
+use pasetors::Public;
+use semver::Version;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use time::{Duration, OffsetDateTime};
+use tracing::warn;
+
+use crate::auth::AuthenticatedUser;
+use crate::error::{internal_error, AppError, AppResult};
+use crate::models::token::{parse_timestamp, EndpointScope, TokenScopes};
+use crate::repository::DynRepository;
+
+/// Signed tokens start with this, where plain tokens never have a `.` in them.
+pub const PASETO_PREFIX: &str = PublicToken::HEADER;
+/// Tokens issued longer ago than this are rejected, cargo signs a new one for every request.
+const MAX_TOKEN_AGE: Duration = Duration::minutes(15);
+/// How far ahead of the registry's clock the client's clock can be.
+const MAX_CLOCK_SKEW: Duration = Duration::minutes(1);
+/// The only version of the claims there is.
+const CLAIMS_VERSION: u8 = 1;
+
+#[derive(Debug, Deserialize)]
+struct Footer {
+    /// The URL of the registry's index, `sparse+https://<domain>/` for this registry.
+    url: String,
+    /// The PASERK ID of the key the token was signed with.
+    kip: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Claims {
+    /// When the token was issued, as an RFC 3339 timestamp.
+    iat: String,
+    mutation: Option<Mutation>,
+    name: Option<String>,
+    vers: Option<Version>,
+    cksum: Option<String>,
+    challenge: Option<String>,
+    v: Option<u8>,
+}
+
+/// The change to the registry a token was signed for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Mutation {
+    Publish,
+    Yank,
+    Unyank,
+    Owners,
+}
+
+impl fmt::Display for Mutation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Mutation::Publish => "publish",
+            Mutation::Yank => "yank",
+            Mutation::Unyank => "unyank",
+            Mutation::Owners => "owners",
+        };
+        f.write_str(name)
+    }
+}
+
+/// The request a token that changes the registry was signed for, which the endpoints
+/// check against the request they get.
+#[derive(Clone, Debug)]
+pub struct SignedMutation {
+    pub mutation: Mutation,
+    pub name: String,
+    /// Set for everything but changing owners.
+    pub vers: Option<Version>,
+    /// The SHA-256 checksum of the crate file, only set for publishing.
+    pub cksum: Option<String>,
+}
+
+impl SignedMutation {
+    pub fn check(&self, mutation: Mutation, name: &str, vers: Option<&Version>) -> AppResult<()> {
+        if self.mutation != mutation {
+            return Err(claim_mismatch(format!(
+                "it was signed for {}, not {}",
+                self.mutation, mutation
+            )));
+        }
+        if self.name != name {
+            return Err(claim_mismatch(format!(
+                "it was signed for the crate {}, not {}",
+                self.name, name
+            )));
+        }
+        if self.vers.as_ref() != vers {
+            let signed = self.vers.as_ref().map(ToString::to_string);
+            return Err(claim_mismatch(format!(
+                "it was signed for version {}, not {}",
+                signed.as_deref().unwrap_or("none"),
+                vers.map(ToString::to_string).as_deref().unwrap_or("none")
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// The checksum is only known once the crate file has been read.
+    pub fn check_checksum(&self, cksum: &str) -> AppResult<()> {
+        match &self.cksum {
+            Some(signed) if signed == cksum => Ok(()),
+            _ => Err(claim_mismatch(
+                "the checksum of the crate file doesn't match".to_string(),
+            )),
+        }
+    }
+}
+
+/// What a verified token can be used for, and by whom.
+#[derive(Clone, Debug)]
+pub struct SignedToken {
+    pub user: AuthenticatedUser,
+    pub scopes: TokenScopes,
+    pub mutation: Option<SignedMutation>,
+}
+
+/// Parses a public key in the PASERK format (`k3.public.…`), returning its PASERK ID.
+pub fn public_key_id(public_key: &str) -> Result<String, String> {
+    let key = AsymmetricPublicKey::<V3>::try_from(public_key)
+        .map_err(|_| "the public key must be a PASERK of a P-384 key (k3.public.…)".to_string())?;
+    let mut key_id = String::new();
+    Id::from(&key)
+        .fmt(&mut key_id)
+        .map_err(|err| err.to_string())?;
+
+    Ok(key_id)
+}
+
+/// Checks the token is signed with a registered key for this registry and still valid,
+/// and records the use of tokens that change the registry.
+pub async fn verify_signed_token(
+    repository: &DynRepository,
+    domain_name: &str,
+    token: &str,
+    now: OffsetDateTime,
+) -> AppResult<SignedToken> {
+    let untrusted = UntrustedToken::<Public, V3>::try_from(token).map_err(invalid_token)?;
+    let footer: Footer =
+        serde_json::from_slice(untrusted.untrusted_footer()).map_err(invalid_token)?;
+    let url = footer.url.strip_prefix("sparse+").unwrap_or(&footer.url);
+    if url.trim_end_matches('/') != format!("https://{}", domain_name) {
+        return Err(invalid_token(format!(
+            "the token was signed for {}",
+            footer.url
+        )));
+    }
+
+    let public_key = repository
+        .get_public_key(&footer.kip)
+        .await?
+        .ok_or_else(|| invalid_token(format!("unknown key {}", footer.kip)))?;
+    // keys are only stored once they've been parsed
+    let key = AsymmetricPublicKey::<V3>::try_from(public_key.public_key.as_str())
+        .map_err(|_| internal_error())?;
+    let trusted = PublicToken::verify(&key, &untrusted, None, None).map_err(invalid_token)?;
+    let claims: Claims = serde_json::from_str(trusted.payload()).map_err(invalid_token)?;
+
+    if claims.v.is_some_and(|v| v != CLAIMS_VERSION) {
+        return Err(invalid_token("unsupported version of the claims"));
+    }
+    let issued_at = parse_timestamp(&claims.iat).map_err(invalid_token)?;
+    if issued_at > now + MAX_CLOCK_SKEW || now - issued_at > MAX_TOKEN_AGE {
+        return Err(invalid_token("the token was issued too long ago"));
+    }
+    let mutation = signed_mutation(&claims)?;
+
+    // cargo only sends a challenge the registry gave it, so this one came from elsewhere
+    if claims.challenge.is_some() {
+        return Err(invalid_token("the registry doesn't issue challenges"));
+    }
+
+    let expires_at = issued_at + MAX_TOKEN_AGE;
+    if mutation.is_some() {
+        let hash: String = Sha256::digest(token.as_bytes()).encode_hex();
+        let nonce = format!("token:{}", hash);
+        if !repository.use_nonce(&nonce, expires_at).await? {
+            return Err(invalid_token("the token was already used"));
+        }
+    }
+
+    let endpoints = match mutation.as_ref().map(|m| m.mutation) {
+        None => vec![],
+        Some(Mutation::Publish) => vec![EndpointScope::PublishNew, EndpointScope::PublishUpdate],
+        Some(Mutation::Yank | Mutation::Unyank) => vec![EndpointScope::Yank],
+        Some(Mutation::Owners) => vec![EndpointScope::ChangeOwners],
+    };
+
+    Ok(SignedToken {
+        user: AuthenticatedUser {
+            id: public_key.user_id,
+        },
+        scopes: TokenScopes {
+            endpoints: Some(endpoints),
+            crates: None,
+        },
+        mutation,
+    })
+}
+
+/// Tokens without a mutation can only read the registry.
+fn signed_mutation(claims: &Claims) -> AppResult<Option<SignedMutation>> {
+    let Some(mutation) = claims.mutation else {
+        return Ok(None);
+    };
+    let name = claims
+        .name
+        .clone()
+        .ok_or_else(|| invalid_token(format!("{} tokens need a name", mutation)))?;
+    let needs_vers = mutation != Mutation::Owners;
+    if needs_vers && claims.vers.is_none() {
+        return Err(invalid_token(format!("{} tokens need a version", mutation)));
+    }
+    if mutation == Mutation::Publish && claims.cksum.is_none() {
+        return Err(invalid_token("publish tokens need a checksum"));
+    }
+
+    Ok(Some(SignedMutation {
+        mutation,
+        name,
+        vers: claims.vers.clone(),
+        cksum: claims.cksum.clone(),
+    }))
+}
+
+fn claim_mismatch(reason: String) -> AppError {
+    warn!(reason, "rejected a signed token");
+    AppError::TokenClaimMismatch(reason)
+}
+
+fn invalid_token(reason: impl ToString) -> AppError {
+    let reason = reason.to_string();
+    warn!(reason, "rejected a signed token");
+    AppError::Unauthorized(format!("invalid token: {}", reason))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mutation(vers: Option<&str>, cksum: Option<&str>) -> SignedMutation {
+        SignedMutation {
+            mutation: Mutation::Publish,
+            name: "raktar".to_string(),
+            vers: vers.map(|v| Version::parse(v).unwrap()),
+            cksum: cksum.map(ToString::to_string),
+        }
+    }
+
+    #[test]
+    fn test_mutations_are_checked_against_the_request() {
+        let signed = mutation(Some("0.1.0"), Some("abc"));
+        let vers = Version::parse("0.1.0").unwrap();
+
+        assert!(signed
+            .check(Mutation::Publish, "raktar", Some(&vers))
+            .is_ok());
+        assert!(signed.check(Mutation::Yank, "raktar", Some(&vers)).is_err());
+        assert!(signed
+            .check(Mutation::Publish, "serde", Some(&vers))
+            .is_err());
+        let other = Version::parse("0.2.0").unwrap();
+        assert!(signed
+            .check(Mutation::Publish, "raktar", Some(&other))
+            .is_err());
+
+        assert!(signed.check_checksum("abc").is_ok());
+        assert!(signed.check_checksum("def").is_err());
+        assert!(mutation(Some("0.1.0"), None).check_checksum("abc").is_err());
+    }
+
+    #[test]
+    fn test_public_key_ids() {
+        // a compressed P-384 point
+        let public_key =
+            "k3.public.AgL-9brv6ACk7klXRDVYPRg7Ds2r1XJrk-SsxK1WqCmSO0cvVs8JXuHP9UPMFXJwWg";
+        let key_id = public_key_id(public_key).unwrap();
+
+        assert!(key_id.starts_with("k3.pid."));
+        assert_eq!(public_key_id(public_key).unwrap(), key_id);
+        assert!(public_key_id("k4.public.AAAA").is_err());
+        assert!(public_key_id("not a key").is_err());
+    }
+}
@@ -1,8 +1,9 @@
-use axum::http::StatusCode;
+use axum::extract::State;
 use axum::Json;
 use serde::Serialize;
-use serde_json::{json, Value};
-use tracing::error;
+
+use crate::config::{AuthMode, RaktarConfig};
+use crate::router::AppState;
 
 #[derive(Serialize)]
 pub struct Config {
@@ -12,32 +13,16 @@ pub struct Config {
     auth_required: bool,
 }
 
-#[derive(Serialize)]
-#[serde(untagged)]
-pub enum Response {
-    Config(Config),
-    Error(Value),
+impl From<&RaktarConfig> for Config {
+    fn from(config: &RaktarConfig) -> Self {
+        Self {
+            dl: format!("https://{}/api/v1/crates", config.domain_name),
+            api: format!("https://{}", config.domain_name),
+            auth_required: config.auth.mode == AuthMode::Required,
+        }
+    }
 }
 
-pub async fn get_config_json() -> (StatusCode, Json<Response>) {
-    if let Ok(domain_name) = std::env::var("DOMAIN_NAME") {
-        let dl = format!("https://{}/api/v1/crates", domain_name);
-        let api = format!("https://{}", domain_name);
-        let config = Config {
-            dl,
-            api,
-            auth_required: true,
-        };
-
-        (StatusCode::OK, Json(Response::Config(config)))
-    } else {
-        error!("DOMAIN_NAME is not set in environment");
-        let error_response = json!({
-            "reason": "misconfigured application"
-        });
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(Response::Error(error_response)),
-        )
-    }
+pub async fn get_config_json(State((_, _, config)): State<AppState>) -> Json<Config> {
+    Json(config.as_ref().into())
 }
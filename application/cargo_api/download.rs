@@ -1,15 +1,88 @@
 use std::str::FromStr;
 
+use axum::body::StreamBody;
 use axum::extract::{Path, State};
+use axum::http::header::{CONTENT_LENGTH, LOCATION};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
 use semver::Version;
+use tracing::warn;
 
-use crate::error::AppResult;
+use crate::config::DownloadMode;
+use crate::error::{AppError, AppResult};
+use crate::integrity::read_verified_crate;
+use crate::models::downloads::today;
 use crate::router::AppState;
+use crate::storage::CrateData;
 
 pub async fn download_crate(
     Path((crate_name, version)): Path<(String, String)>,
-    State((_, storage)): State<AppState>,
-) -> AppResult<Vec<u8>> {
-    let vers = Version::from_str(&version).expect("version to be valid");
-    storage.get_crate(&crate_name, vers).await
+    State((repository, storage, config)): State<AppState>,
+) -> AppResult<Response> {
+    let vers = Version::from_str(&version).map_err(|_| AppError::InvalidVersion(version))?;
+    // crates are stored under the name they were published with
+    let crate_name = repository
+        .resolve_crate_name(&crate_name)
+        .await?
+        .ok_or_else(|| AppError::NonExistentCrateVersion {
+            crate_name,
+            version: vers.clone(),
+        })?;
+    // only versions that exist are served and counted, whatever the download mode
+    if repository
+        .get_crate_metadata(&crate_name, &vers)
+        .await?
+        .is_none()
+    {
+        return Err(AppError::NonExistentCrateVersion {
+            crate_name,
+            version: vers,
+        });
+    }
+
+    let response = if config.verify_downloads {
+        let package_info = repository
+            .list_package_infos(&crate_name)
+            .await?
+            .into_iter()
+            .find(|info| info.vers == vers)
+            .ok_or_else(|| AppError::NonExistentCrateVersion {
+                crate_name: crate_name.clone(),
+                version: vers.clone(),
+            })?;
+        let data = read_verified_crate(&storage, &package_info).await?;
+        crate_response(data)
+    } else {
+        let download_url = match config.downloads {
+            DownloadMode::Redirect => storage.get_download_url(&crate_name, &vers).await?,
+            DownloadMode::Proxy => None,
+        };
+        match download_url {
+            Some(url) => (StatusCode::FOUND, [(LOCATION, url)]).into_response(),
+            None => crate_response(storage.get_crate(&crate_name, vers.clone()).await?),
+        }
+    };
+
+    // a download shouldn't fail because it couldn't be counted
+    if let Err(err) = repository
+        .record_download(&crate_name, &vers, today())
+        .await
+    {
+        warn!(
+            crate_name,
+            version = vers.to_string(),
+            error = err.to_string(),
+            "failed to record download"
+        );
+    }
+
+    Ok(response)
+}
+
+fn crate_response(data: CrateData) -> Response {
+    (
+        [(CONTENT_LENGTH, data.content_length.to_string())],
+        StreamBody::new(data.stream),
+    )
+        .into_response()
 }
@@ -1,32 +1,237 @@
 use axum::extract::{Path, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use std::time::{Duration, UNIX_EPOCH};
 
-use crate::error::AppResult;
+use crate::error::{AppError, AppResult};
+use crate::models::index::IndexState;
+use crate::repository::DynRepository;
 use crate::router::AppState;
 
-pub async fn get_info_for_short_name_crate(
+pub async fn get_info_for_one_letter_crate(
     Path(crate_name): Path<String>,
-    State((repository, _)): State<AppState>,
-) -> AppResult<String> {
-    assert_eq!(1, crate_name.len());
+    State((repository, _, _)): State<AppState>,
+    headers: HeaderMap,
+) -> AppResult<Response> {
+    check_index_directory("1", &crate_name)?;
 
-    repository.get_package_info(&crate_name).await
+    get_index_file(&repository, &crate_name, &headers).await
+}
+
+pub async fn get_info_for_two_letter_crate(
+    Path(crate_name): Path<String>,
+    State((repository, _, _)): State<AppState>,
+    headers: HeaderMap,
+) -> AppResult<Response> {
+    check_index_directory("2", &crate_name)?;
+
+    get_index_file(&repository, &crate_name, &headers).await
 }
 
 pub async fn get_info_for_three_letter_crate(
     Path((first_letter, crate_name)): Path<(String, String)>,
-    State((repository, _)): State<AppState>,
-) -> AppResult<String> {
-    assert_eq!(Some(first_letter.as_ref()), crate_name.get(0..1));
+    State((repository, _, _)): State<AppState>,
+    headers: HeaderMap,
+) -> AppResult<Response> {
+    check_index_directory(&format!("3/{}", first_letter), &crate_name)?;
 
-    repository.get_package_info(&crate_name).await
+    get_index_file(&repository, &crate_name, &headers).await
 }
 
 pub async fn get_info_for_long_name_crate(
     Path((first_two, second_two, crate_name)): Path<(String, String, String)>,
-    State((repository, _)): State<AppState>,
-) -> AppResult<String> {
-    assert_eq!(Some(first_two.as_ref()), crate_name.get(0..2));
-    assert_eq!(Some(second_two.as_ref()), crate_name.get(2..4));
+    State((repository, _, _)): State<AppState>,
+    headers: HeaderMap,
+) -> AppResult<Response> {
+    check_index_directory(&format!("{}/{}", first_two, second_two), &crate_name)?;
+
+    get_index_file(&repository, &crate_name, &headers).await
+}
+
+/// The directory of the sparse index the crate's file lives in.
+fn index_directory(crate_name: &str) -> Option<String> {
+    if !crate_name.is_ascii() {
+        return None;
+    }
+
+    match crate_name.len() {
+        0 => None,
+        1 => Some("1".to_string()),
+        2 => Some("2".to_string()),
+        3 => Some(format!("3/{}", &crate_name[0..1])),
+        _ => Some(format!("{}/{}", &crate_name[0..2], &crate_name[2..4])),
+    }
+}
+
+/// Requests for a crate outside of its directory are treated as missing files.
+fn check_index_directory(directory: &str, crate_name: &str) -> AppResult<()> {
+    match index_directory(crate_name) {
+        // cargo lowercases the whole path
+        Some(expected) if expected.eq_ignore_ascii_case(directory) => Ok(()),
+        _ => Err(AppError::NonExistentPackageInfo(crate_name.to_string())),
+    }
+}
+
+/// Cargo lowercases index paths, so the crate is looked up by its canonical name.
+///
+/// Cargo sends the `ETag` and `Last-Modified` of its cached copy back on every fetch,
+/// which can be answered without reading the index file itself.
+async fn get_index_file(
+    repository: &DynRepository,
+    crate_name: &str,
+    headers: &HeaderMap,
+) -> AppResult<Response> {
+    let crate_name = repository
+        .resolve_crate_name(crate_name)
+        .await?
+        .ok_or_else(|| AppError::NonExistentPackageInfo(crate_name.to_string()))?;
+
+    let state = repository.get_index_state(&crate_name).await?;
+    if let Some(state) = &state {
+        if is_not_modified(headers, state) {
+            return Ok((StatusCode::NOT_MODIFIED, cache_headers(state)).into_response());
+        }
+    }
+
+    let index_file = repository.get_package_info(&crate_name).await?;
+    let response = match state {
+        Some(state) => (cache_headers(&state), index_file).into_response(),
+        // crates that haven't changed since index states were introduced
+        None => index_file.into_response(),
+    };
+
+    Ok(response)
+}
+
+fn cache_headers(state: &IndexState) -> [(header::HeaderName, String); 2] {
+    let last_modified = UNIX_EPOCH + Duration::from_secs(state.last_modified);
+    [
+        (header::ETAG, format!("\"{}\"", state.etag)),
+        (
+            header::LAST_MODIFIED,
+            httpdate::fmt_http_date(last_modified),
+        ),
+    ]
+}
+
+fn is_not_modified(headers: &HeaderMap, state: &IndexState) -> bool {
+    // when both are sent, `If-None-Match` takes precedence
+    if let Some(value) = headers.get(header::IF_NONE_MATCH) {
+        return match value.to_str() {
+            Ok(value) => value.split(',').map(str::trim).any(|tag| {
+                tag == "*" || tag.trim_start_matches("W/").trim_matches('"') == state.etag
+            }),
+            Err(_) => false,
+        };
+    }
+
+    headers
+        .get(header::IF_MODIFIED_SINCE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| httpdate::parse_http_date(value).ok())
+        .and_then(|since| since.duration_since(UNIX_EPOCH).ok())
+        .is_some_and(|since| state.last_modified <= since.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(pairs: &[(header::HeaderName, &str)]) -> HeaderMap {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.clone(), value.parse().unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn test_not_modified() {
+        let state = IndexState {
+            etag: "abc".to_string(),
+            last_modified: 1_686_000_000,
+        };
+        let [(_, etag), (_, last_modified)] = cache_headers(&state);
+        assert_eq!(etag, "\"abc\"");
+        assert_eq!(last_modified, "Mon, 05 Jun 2023 21:20:00 GMT");
+
+        for matching in [
+            headers(&[(header::IF_NONE_MATCH, "\"abc\"")]),
+            headers(&[(header::IF_NONE_MATCH, "W/\"abc\"")]),
+            headers(&[(header::IF_NONE_MATCH, "\"old\", \"abc\"")]),
+            headers(&[(header::IF_NONE_MATCH, "*")]),
+            headers(&[(header::IF_MODIFIED_SINCE, &last_modified)]),
+            headers(&[(header::IF_MODIFIED_SINCE, "Tue, 06 Jun 2023 00:00:00 GMT")]),
+        ] {
+            assert!(is_not_modified(&matching, &state), "{:?}", matching);
+        }
+
+        for changed in [
+            HeaderMap::new(),
+            headers(&[(header::IF_NONE_MATCH, "\"old\"")]),
+            headers(&[(header::IF_MODIFIED_SINCE, "Mon, 05 Jun 2023 00:00:00 GMT")]),
+            headers(&[(header::IF_MODIFIED_SINCE, "yesterday")]),
+            // the ETag wins over the date
+            headers(&[
+                (header::IF_NONE_MATCH, "\"old\""),
+                (header::IF_MODIFIED_SINCE, &last_modified),
+            ]),
+        ] {
+            assert!(!is_not_modified(&changed, &state), "{:?}", changed);
+        }
+    }
+
+    #[test]
+    fn test_index_directory() {
+        assert_eq!(index_directory("a").as_deref(), Some("1"));
+        assert_eq!(index_directory("ab").as_deref(), Some("2"));
+        assert_eq!(index_directory("abc").as_deref(), Some("3/a"));
+        assert_eq!(index_directory("Serde").as_deref(), Some("Se/rd"));
+        assert_eq!(index_directory(""), None);
+        assert_eq!(index_directory("caf\u{e9}"), None);
+    }
+
+    #[test]
+    fn test_matching_directories() {
+        for (directory, crate_name) in [
+            ("1", "a"),
+            ("2", "ab"),
+            ("3/a", "abc"),
+            ("3/a", "Abc"),
+            ("se/rd", "serde"),
+            ("se/rd", "Serde_Json"),
+        ] {
+            assert!(
+                check_index_directory(directory, crate_name).is_ok(),
+                "{}/{} should be found",
+                directory,
+                crate_name
+            );
+        }
+    }
 
-    repository.get_package_info(&crate_name).await
+    #[test]
+    fn test_mismatched_directories() {
+        for (directory, crate_name) in [
+            // the wrong length bucket
+            ("1", "ab"),
+            ("2", "abc"),
+            ("3/a", "abcd"),
+            ("ab/c", "abc"),
+            // the wrong prefix
+            ("3/b", "abc"),
+            ("se/rx", "serde"),
+            ("xe/rd", "serde"),
+            ("se/rd", "s\u{e9}rde"),
+        ] {
+            assert!(
+                matches!(
+                    check_index_directory(directory, crate_name),
+                    Err(AppError::NonExistentPackageInfo(_))
+                ),
+                "{}/{} should not be found",
+                directory,
+                crate_name
+            );
+        }
+    }
 }
@@ -1,9 +1,12 @@
 use axum::extract::{Path, State};
-use axum::Json;
+use axum::{Extension, Json};
 use serde::{Deserialize, Serialize};
 
-use crate::error::AppResult;
+use crate::auth::{Mutation, SignedMutation};
+use crate::error::{AppError, AppResult};
+use crate::models::token::{EndpointScope, TokenScopes};
 use crate::models::user::User;
+use crate::repository::DynRepository;
 use crate::router::AppState;
 
 #[derive(Debug, Serialize)]
@@ -12,9 +15,10 @@ pub struct ListOwnersResponse {
 }
 
 pub async fn list_owners(
-    Path(crate_name): Path<String>,
-    State((repository, _)): State<AppState>,
+    Path(requested_name): Path<String>,
+    State((repository, _, _)): State<AppState>,
 ) -> AppResult<Json<ListOwnersResponse>> {
+    let crate_name = resolve_crate_name(&repository, requested_name).await?;
     let users = repository.list_owners(&crate_name).await?;
     let response = ListOwnersResponse { users };
 
@@ -33,10 +37,18 @@ pub struct AddOwnersResponse {
 }
 
 pub async fn add_owners(
-    Path(crate_name): Path<String>,
-    State((repository, _)): State<AppState>,
+    Path(requested_name): Path<String>,
+    Extension(scopes): Extension<TokenScopes>,
+    signed: Option<Extension<SignedMutation>>,
+    State((repository, _, _)): State<AppState>,
     Json(new_owners): Json<AddOwnersBody>,
 ) -> AppResult<Json<AddOwnersResponse>> {
+    // cargo signs the name as it was typed, which is the one in the path
+    if let Some(Extension(signed)) = signed {
+        signed.check(Mutation::Owners, &requested_name, None)?;
+    }
+    let crate_name = resolve_crate_name(&repository, requested_name).await?;
+    scopes.check(EndpointScope::ChangeOwners, &crate_name)?;
     repository.add_owners(&crate_name, new_owners.users).await?;
 
     let response = AddOwnersResponse {
@@ -45,3 +57,14 @@ pub async fn add_owners(
     };
     Ok(response.into())
 }
+
+/// Scopes and the repository work with the name the crate was published with.
+async fn resolve_crate_name(
+    repository: &DynRepository,
+    requested_name: String,
+) -> AppResult<String> {
+    repository
+        .resolve_crate_name(&requested_name)
+        .await?
+        .ok_or(AppError::NonExistentPackageInfo(requested_name))
+}
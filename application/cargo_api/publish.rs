@@ -1,20 +1,34 @@
-use axum::body::Bytes;
-use axum::extract::State;
+use axum::extract::{BodyStream, State};
 use axum::{Extension, Json};
-use byteorder::{LittleEndian, ReadBytesExt};
+use bytes::Bytes;
+use futures::channel::mpsc;
+use futures::{SinkExt, StreamExt, TryStreamExt};
 use hex::ToHex;
+use semver::Version;
 use serde::Serialize;
 use sha2::{Digest, Sha256};
-use std::io::{Cursor, Read};
-use tracing::info;
+use std::io::Read;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio_util::io::StreamReader;
+use tracing::{error, info};
 
-use crate::auth::AuthenticatedUser;
-use crate::error::AppResult;
+use crate::auth::{AuthenticatedUser, Mutation, SignedMutation};
+use crate::config::PublishLimits;
+use crate::error::{AppError, AppResult};
+use crate::models::crate_name::validate_crate_name;
 use crate::models::index::PackageInfo;
 use crate::models::metadata::Metadata;
+use crate::models::token::{EndpointScope, TokenScopes};
 use crate::repository::DynRepository;
 use crate::router::AppState;
-use crate::storage::DynCrateStorage;
+use crate::storage::{CrateData, DynCrateStorage};
+
+mod tarball;
+
+/// The crate file is passed on to storage in chunks of at most this many bytes.
+const CHUNK_SIZE: u64 = 64 * 1024;
+/// How many chunks can be waiting for storage or validation before reading the body pauses.
+const CHUNK_BUFFER: usize = 8;
 
 #[derive(Serialize)]
 pub struct PublishResponse {
@@ -25,10 +39,22 @@ pub struct PublishResponse {
 
 pub async fn publish_crate_handler(
     Extension(authenticated_user): Extension<AuthenticatedUser>,
-    State((repository, storage)): State<AppState>,
-    body: Bytes,
+    Extension(scopes): Extension<TokenScopes>,
+    signed: Option<Extension<SignedMutation>>,
+    State((repository, storage, config)): State<AppState>,
+    body: BodyStream,
 ) -> AppResult<Json<PublishResponse>> {
-    publish_crate(authenticated_user, storage, repository, body).await?;
+    let body = StreamReader::new(body.map_err(std::io::Error::other));
+    publish_crate(
+        authenticated_user,
+        &scopes,
+        signed.as_ref().map(|Extension(signed)| signed),
+        storage,
+        repository,
+        &config.limits,
+        body,
+    )
+    .await?;
 
     Ok(Json(PublishResponse {
         invalid_categories: vec![],
@@ -37,19 +63,78 @@ pub async fn publish_crate_handler(
     }))
 }
 
+/// Publishes the crate in the body of a `cargo publish` request.
+///
+/// The body is the metadata and the crate file, both prefixed by their length as a little
+/// endian `u32`. Only the metadata is held in memory, the crate file is streamed into
+/// storage while it's hashed and validated.
+///
+/// Requests signed with a public key also have to match the crate the key signed for.
 pub async fn publish_crate(
     authenticated_user: AuthenticatedUser,
+    scopes: &TokenScopes,
+    signed: Option<&SignedMutation>,
     storage: DynCrateStorage,
     repository: DynRepository,
-    data: Bytes,
+    limits: &PublishLimits,
+    mut body: impl AsyncRead + Send + Unpin,
 ) -> AppResult<()> {
-    let (metadata_bytes, crate_bytes) = read_body(data);
-    let metadata = serde_json::from_slice::<Metadata>(&metadata_bytes).unwrap();
+    let metadata_bytes = read_section(&mut body, "metadata", limits.max_metadata_size).await?;
+    let metadata = parse_metadata(&metadata_bytes)?;
+    validate_crate_name(&metadata.name).map_err(|reason| AppError::InvalidCrateName {
+        crate_name: metadata.name.clone(),
+        reason,
+    })?;
+    let is_update = repository
+        .resolve_crate_name(&metadata.name)
+        .await?
+        .is_some();
+    let scope = if is_update {
+        EndpointScope::PublishUpdate
+    } else {
+        EndpointScope::PublishNew
+    };
+    scopes.check(scope, &metadata.name)?;
+    if let Some(signed) = signed {
+        signed.check(Mutation::Publish, &metadata.name, Some(&metadata.vers))?;
+    }
+    // the crate's own limits are only known once we have the name
+    let limits = repository
+        .get_crate_limits(&metadata.name)
+        .await?
+        .apply_to(limits);
+    let crate_length = read_length(&mut body, "crate file", limits.max_crate_size).await?;
+    let upload_size = 8 + metadata_bytes.len() as u64 + crate_length;
+    if upload_size > limits.max_upload_size {
+        return Err(AppError::PublishLimitExceeded(format!(
+            "the upload is {} bytes, which is more than the limit of {} bytes",
+            upload_size, limits.max_upload_size
+        )));
+    }
+    check_metadata_limits(&metadata, &limits)?;
 
     info!("metadata: {}", serde_json::to_string(&metadata).unwrap());
     let vers = metadata.vers.clone();
     let crate_name = metadata.name.clone();
-    let checksum: String = Sha256::digest(&crate_bytes).encode_hex();
+    // storage won't overwrite an existing version's file either, but this saves reading it
+    if repository
+        .get_crate_metadata(&crate_name, &vers)
+        .await?
+        .is_some()
+    {
+        return Err(AppError::DuplicateCrateVersion {
+            crate_name,
+            version: vers,
+        });
+    }
+
+    let checksum = store_crate_file(&mut body, crate_length, &metadata, &storage).await?;
+    if let Some(signed) = signed {
+        if let Err(err) = signed.check_checksum(&checksum) {
+            discard_crate_file(&storage, &crate_name, &vers).await;
+            return Err(err);
+        }
+    }
     let package_info = PackageInfo::from_metadata(metadata.clone(), &checksum);
 
     info!(
@@ -61,7 +146,7 @@ pub async fn publish_crate(
         vers
     );
 
-    repository
+    let stored = repository
         .store_package_info(
             &crate_name,
             &vers,
@@ -69,24 +154,369 @@ pub async fn publish_crate(
             metadata,
             &authenticated_user,
         )
-        .await?;
-    storage.store_crate(&crate_name, vers, crate_bytes).await?;
+        .await;
+    if let Err(err) = stored {
+        // storage only stores new files, so this publish is the one that created it
+        discard_crate_file(&storage, &crate_name, &vers).await;
+        return Err(err);
+    }
 
     Ok(())
 }
 
-fn read_body(body: Bytes) -> (Vec<u8>, Vec<u8>) {
-    let mut cursor = Cursor::new(body);
+/// Removes the crate file of a publish that failed after it was stored.
+///
+/// A failure to remove it is only logged, so the error of the publish itself is reported.
+/// The version can't be published again until the file is removed by hand.
+async fn discard_crate_file(storage: &DynCrateStorage, crate_name: &str, version: &Version) {
+    if let Err(err) = storage.delete_crate(crate_name, version.clone()).await {
+        error!(
+            crate_name,
+            version = version.to_string(),
+            error = err.to_string(),
+            "failed to remove the crate file of a failed publish"
+        );
+    }
+}
+
+/// Streams the crate file from the body into storage, returning its checksum.
+///
+/// The tarball is validated on a blocking thread as it passes through, and removed
+/// from storage again when it turns out to be invalid. Storage refuses to replace the
+/// file of a concurrent publish of the same version, which then fails here.
+async fn store_crate_file(
+    body: &mut (impl AsyncRead + Send + Unpin),
+    crate_length: u64,
+    metadata: &Metadata,
+    storage: &DynCrateStorage,
+) -> AppResult<String> {
+    let (storage_sender, storage_receiver) = mpsc::channel(CHUNK_BUFFER);
+    let (tarball_sender, tarball_receiver) = tokio::sync::mpsc::channel(CHUNK_BUFFER);
+
+    let validation_metadata = metadata.clone();
+    let validation = tokio::task::spawn_blocking(move || {
+        tarball::validate_crate_tarball(ChannelReader::new(tarball_receiver), &validation_metadata)
+    });
+    let data = CrateData::new(crate_length, storage_receiver.boxed());
+    let (checksum, stored) = tokio::join!(
+        read_crate_file(body, crate_length, storage_sender, tarball_sender),
+        storage.store_crate(&metadata.name, metadata.vers.clone(), data),
+    );
+    // a truncated body fails the storage write too, but the body is the actual problem
+    let checksum = checksum?;
+    stored?;
+
+    let validation = validation
+        .await
+        .map_err(|_| anyhow::anyhow!("crate file validation did not complete"))?;
+    if let Err(err) = validation {
+        discard_crate_file(storage, &metadata.name, &metadata.vers).await;
+        return Err(err);
+    }
+
+    Ok(checksum)
+}
+
+/// Reads the crate file from the body, passing every chunk on to storage and validation.
+async fn read_crate_file(
+    body: &mut (impl AsyncRead + Send + Unpin),
+    crate_length: u64,
+    mut storage_sender: mpsc::Sender<std::io::Result<Bytes>>,
+    tarball_sender: tokio::sync::mpsc::Sender<Bytes>,
+) -> AppResult<String> {
+    let mut hasher = Sha256::new();
+    let mut remaining = crate_length;
+    while remaining > 0 {
+        let mut chunk = vec![0u8; remaining.min(CHUNK_SIZE) as usize];
+        if let Err(err) = body.read_exact(&mut chunk).await {
+            // make sure storage doesn't keep what it got so far
+            let _ = storage_sender.send(Err(err)).await;
+            return Err(AppError::InvalidPublishBody(
+                "truncated crate file in the body".to_string(),
+            ));
+        }
+        remaining -= chunk.len() as u64;
+
+        let chunk = Bytes::from(chunk);
+        hasher.update(&chunk);
+        // validation stops reading once it has seen a problem, that's reported afterwards
+        let _ = tarball_sender.send(chunk.clone()).await;
+        if storage_sender.send(Ok(chunk)).await.is_err() {
+            // storage gave up, its error is reported instead
+            break;
+        }
+    }
+
+    Ok(hasher.finalize().encode_hex())
+}
+
+/// Lets blocking code read the chunks sent over a channel.
+struct ChannelReader {
+    receiver: tokio::sync::mpsc::Receiver<Bytes>,
+    chunk: Bytes,
+}
+
+impl ChannelReader {
+    fn new(receiver: tokio::sync::mpsc::Receiver<Bytes>) -> Self {
+        Self {
+            receiver,
+            chunk: Bytes::new(),
+        }
+    }
+}
+
+impl Read for ChannelReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.chunk.is_empty() {
+            match self.receiver.blocking_recv() {
+                Some(chunk) => self.chunk = chunk,
+                None => return Ok(0),
+            }
+        }
+
+        let length = buf.len().min(self.chunk.len());
+        buf[..length].copy_from_slice(&self.chunk.split_to(length));
+        Ok(length)
+    }
+}
+
+/// Reads the length prefix of the next section of the publish body.
+async fn read_length(
+    body: &mut (impl AsyncRead + Unpin),
+    section: &str,
+    max_length: u64,
+) -> AppResult<u64> {
+    let length = body.read_u32_le().await.map_err(|_| {
+        AppError::InvalidPublishBody(format!("truncated length prefix for the {}", section))
+    })?;
+
+    if u64::from(length) > max_length {
+        return Err(AppError::PublishLimitExceeded(format!(
+            "the {} is {} bytes, which is more than the limit of {} bytes",
+            section, length, max_length
+        )));
+    }
+
+    Ok(u64::from(length))
+}
+
+/// Reads the next length prefixed section of the publish body.
+async fn read_section(
+    body: &mut (impl AsyncRead + Unpin),
+    section: &str,
+    max_length: u64,
+) -> AppResult<Vec<u8>> {
+    let length = read_length(body, section, max_length).await?;
+    let mut bytes = vec![0u8; length as usize];
+    body.read_exact(&mut bytes)
+        .await
+        .map_err(|_| AppError::InvalidPublishBody(format!("truncated {} in the body", section)))?;
+
+    Ok(bytes)
+}
+
+fn check_metadata_limits(metadata: &Metadata, limits: &PublishLimits) -> AppResult<()> {
+    if metadata.deps.len() > limits.max_dependencies {
+        return Err(AppError::PublishLimitExceeded(format!(
+            "the crate has {} dependencies, which is more than the limit of {}",
+            metadata.deps.len(),
+            limits.max_dependencies
+        )));
+    }
+    if metadata.features.len() > limits.max_features {
+        return Err(AppError::PublishLimitExceeded(format!(
+            "the crate has {} features, which is more than the limit of {}",
+            metadata.features.len(),
+            limits.max_features
+        )));
+    }
+    let readme_length = metadata.readme.as_ref().map_or(0, String::len);
+    if readme_length > limits.max_readme_length {
+        return Err(AppError::PublishLimitExceeded(format!(
+            "the README is {} bytes, which is more than the limit of {} bytes",
+            readme_length, limits.max_readme_length
+        )));
+    }
+
+    Ok(())
+}
+
+fn parse_metadata(metadata_bytes: &[u8]) -> AppResult<Metadata> {
+    let deserializer = &mut serde_json::Deserializer::from_slice(metadata_bytes);
+    serde_path_to_error::deserialize(deserializer).map_err(|err| {
+        AppError::InvalidPublishBody(format!(
+            "invalid metadata JSON at `{}`: {}",
+            err.path(),
+            err.inner()
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn body(sections: &[&[u8]]) -> Vec<u8> {
+        let mut body = vec![];
+        for section in sections {
+            body.extend_from_slice(&(section.len() as u32).to_le_bytes());
+            body.extend_from_slice(section);
+        }
+        body
+    }
+
+    async fn read_body(data: &[u8]) -> AppResult<(Vec<u8>, Vec<u8>)> {
+        let mut body = data;
+        let metadata = read_section(&mut body, "metadata", u64::MAX).await?;
+        let krate = read_section(&mut body, "crate file", u64::MAX).await?;
+        Ok((metadata, krate))
+    }
+
+    fn invalid_body_message(result: AppResult<impl std::fmt::Debug>) -> String {
+        match result {
+            Err(AppError::InvalidPublishBody(message)) => message,
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_read_body() {
+        let (metadata, krate) = read_body(&body(&[b"{}", b"crate"])).await.unwrap();
+        assert_eq!(metadata, b"{}");
+        assert_eq!(krate, b"crate");
+    }
+
+    #[tokio::test]
+    async fn test_truncated_length_prefix() {
+        let message = invalid_body_message(read_body(&[1, 0]).await);
+        assert_eq!(message, "truncated length prefix for the metadata");
+
+        let mut data = body(&[b"{}"]);
+        data.push(0);
+        let message = invalid_body_message(read_body(&data).await);
+        assert_eq!(message, "truncated length prefix for the crate file");
+    }
+
+    #[tokio::test]
+    async fn test_length_exceeding_body() {
+        let mut data = body(&[b"{}"]);
+        data.extend_from_slice(&100u32.to_le_bytes());
+        data.extend_from_slice(b"short");
+
+        let message = invalid_body_message(read_body(&data).await);
+        assert_eq!(message, "truncated crate file in the body");
+    }
+
+    #[tokio::test]
+    async fn test_section_over_the_limit_is_not_read() {
+        let data = body(&[b"{}", &[0; 100]]);
+        let mut body = data.as_slice();
+        read_section(&mut body, "metadata", 2).await.unwrap();
+
+        let result = read_section(&mut body, "crate file", 99).await;
+        assert!(matches!(
+            result,
+            Err(AppError::PublishLimitExceeded(message))
+                if message == "the crate file is 100 bytes, which is more than the limit of 99 bytes"
+        ));
+        assert_eq!(body.len(), 100, "the crate file should not have been read");
+    }
+
+    #[test]
+    fn test_channel_reader() {
+        let (sender, receiver) = tokio::sync::mpsc::channel(4);
+        sender.try_send(Bytes::from_static(b"cr")).unwrap();
+        sender.try_send(Bytes::new()).unwrap();
+        sender.try_send(Bytes::from_static(b"ate")).unwrap();
+        drop(sender);
+
+        let mut contents = String::new();
+        ChannelReader::new(receiver)
+            .read_to_string(&mut contents)
+            .unwrap();
+        assert_eq!(contents, "crate");
+    }
+
+    #[test]
+    fn test_metadata_limits() {
+        let metadata: Metadata = serde_json::from_value(serde_json::json!({
+            "name": "testcrate",
+            "vers": "0.1.0",
+            "deps": [],
+            "features": { "default": [], "extra": [] },
+            "authors": [],
+            "description": null,
+            "documentation": null,
+            "homepage": null,
+            "readme": "# Test crate",
+            "readme_file": null,
+            "keywords": [],
+            "categories": [],
+            "license": null,
+            "license_file": null,
+            "repository": null,
+            "badges": {},
+            "links": null,
+        }))
+        .unwrap();
+        let limits = PublishLimits::default();
+        assert!(check_metadata_limits(&metadata, &limits).is_ok());
+
+        let result = check_metadata_limits(
+            &metadata,
+            &PublishLimits {
+                max_features: 1,
+                ..limits.clone()
+            },
+        );
+        assert!(matches!(
+            result,
+            Err(AppError::PublishLimitExceeded(message))
+                if message == "the crate has 2 features, which is more than the limit of 1"
+        ));
+
+        let result = check_metadata_limits(
+            &metadata,
+            &PublishLimits {
+                max_readme_length: 5,
+                ..limits
+            },
+        );
+        assert!(matches!(
+            result,
+            Err(AppError::PublishLimitExceeded(message))
+                if message == "the README is 12 bytes, which is more than the limit of 5 bytes"
+        ));
+    }
+
+    #[test]
+    fn test_invalid_metadata_json() {
+        let message = invalid_body_message(parse_metadata(
+            br#"{"name":"testcrate","vers":"not-a-version"}"#,
+        ));
+        assert!(
+            message.starts_with("invalid metadata JSON at `vers`: "),
+            "{}",
+            message
+        );
+    }
 
-    // read metadata bytes
-    let metadata_length = cursor.read_u32::<LittleEndian>().unwrap();
-    let mut metadata_bytes = vec![0u8; metadata_length as usize];
-    cursor.read_exact(&mut metadata_bytes).unwrap();
+    #[tokio::test]
+    async fn test_errors_render_for_cargo() {
+        use axum::body::HttpBody;
+        use axum::response::IntoResponse;
 
-    // read crate bytes
-    let crate_length = cursor.read_u32::<LittleEndian>().unwrap();
-    let mut crate_bytes = vec![0u8; crate_length as usize];
-    cursor.read_exact(&mut crate_bytes).unwrap();
+        let error = AppError::InvalidPublishBody("truncated crate file in the body".to_string());
+        let response = error.into_response();
+        assert_eq!(response.status(), axum::http::StatusCode::BAD_REQUEST);
 
-    (metadata_bytes, crate_bytes)
+        let body = response.into_body().data().await.unwrap().unwrap();
+        let payload: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            payload,
+            serde_json::json!({
+                "errors": [{ "detail": "invalid publish request: truncated crate file in the body" }]
+            })
+        );
+    }
 }
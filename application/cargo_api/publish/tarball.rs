@@ -0,0 +1,293 @@
+//! Checks on the `.crate` tarball uploaded by `cargo publish`.
+//!
+//! The tarball is expected to contain everything under a single
+//! `<name>-<version>/` directory, including the normalised `Cargo.toml`
+//! that has to agree with the metadata sent alongside it.
+use std::io::Read;
+use std::path::{Component, Path};
+
+use flate2::read::GzDecoder;
+use semver::Version;
+use serde::Deserialize;
+use tar::Archive;
+
+use crate::error::{AppError, AppResult};
+use crate::models::metadata::Metadata;
+
+/// Cargo.toml files bigger than this are not read.
+const MAX_MANIFEST_SIZE: u64 = 1024 * 1024;
+
+#[derive(Debug, Deserialize)]
+struct Manifest {
+    package: Option<ManifestPackage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ManifestPackage {
+    name: String,
+    version: String,
+}
+
+pub fn validate_crate_tarball(crate_file: impl Read, metadata: &Metadata) -> AppResult<()> {
+    let problems = find_problems(crate_file, metadata);
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(AppError::InvalidCrateFile(problems))
+    }
+}
+
+fn find_problems(crate_file: impl Read, metadata: &Metadata) -> Vec<String> {
+    let root = format!("{}-{}", metadata.name, metadata.vers);
+    let mut problems = vec![];
+    let mut manifest = None;
+
+    let mut archive = Archive::new(GzDecoder::new(crate_file));
+    let entries = match archive.entries() {
+        Ok(entries) => entries,
+        Err(err) => return vec![format!("failed to read the crate file: {}", err)],
+    };
+
+    for entry in entries {
+        let mut entry = match entry {
+            Ok(entry) => entry,
+            Err(err) => {
+                problems.push(format!("failed to read the crate file: {}", err));
+                break;
+            }
+        };
+        let path = match entry.path() {
+            Ok(path) => path.into_owned(),
+            Err(_) => {
+                problems.push("the crate file contains an entry with an invalid path".to_string());
+                continue;
+            }
+        };
+
+        if !is_contained(&path) {
+            problems.push(format!("entry {} escapes the crate root", path.display()));
+            continue;
+        }
+        if let Ok(Some(link_name)) = entry.link_name() {
+            if !is_contained(&link_name) {
+                problems.push(format!(
+                    "entry {} links to {} outside of the crate root",
+                    path.display(),
+                    link_name.display()
+                ));
+                continue;
+            }
+        }
+
+        let mut components = path.components();
+        match components.next() {
+            Some(Component::Normal(first)) if first == root.as_str() => {}
+            _ => {
+                problems.push(format!(
+                    "entry {} is not inside the {}/ directory",
+                    path.display(),
+                    root
+                ));
+                continue;
+            }
+        }
+
+        if components.as_path() == Path::new("Cargo.toml") {
+            let mut contents = String::new();
+            match (&mut entry)
+                .take(MAX_MANIFEST_SIZE)
+                .read_to_string(&mut contents)
+            {
+                Ok(_) => manifest = Some(contents),
+                Err(err) => problems.push(format!("failed to read Cargo.toml: {}", err)),
+            }
+        }
+    }
+
+    match manifest {
+        None => problems.push(format!(
+            "the crate file does not contain {}/Cargo.toml",
+            root
+        )),
+        Some(contents) => problems.extend(check_manifest(&contents, metadata)),
+    }
+
+    problems
+}
+
+fn check_manifest(contents: &str, metadata: &Metadata) -> Vec<String> {
+    let package = match toml::from_str::<Manifest>(contents) {
+        Ok(Manifest {
+            package: Some(package),
+        }) => package,
+        Ok(Manifest { package: None }) => {
+            return vec!["Cargo.toml does not have a [package] section".to_string()]
+        }
+        Err(err) => return vec![format!("Cargo.toml could not be parsed: {}", err)],
+    };
+
+    let mut problems = vec![];
+    if package.name != metadata.name {
+        problems.push(format!(
+            "the package name in Cargo.toml ({}) does not match the published name ({})",
+            package.name, metadata.name
+        ));
+    }
+    match Version::parse(&package.version) {
+        Ok(version) if version == metadata.vers => {}
+        _ => problems.push(format!(
+            "the package version in Cargo.toml ({}) does not match the published version ({})",
+            package.version, metadata.vers
+        )),
+    }
+
+    problems
+}
+
+/// Whether the path stays inside the directory it's unpacked into.
+fn is_contained(path: &Path) -> bool {
+    path.components()
+        .all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::collections::HashMap;
+    use tar::{Builder, EntryType, Header};
+
+    fn build_metadata(name: &str, version: &str) -> Metadata {
+        Metadata {
+            name: name.to_string(),
+            vers: version.parse().unwrap(),
+            deps: vec![],
+            features: HashMap::new(),
+            authors: vec![],
+            description: None,
+            documentation: None,
+            homepage: None,
+            readme: None,
+            readme_file: None,
+            keywords: vec![],
+            categories: vec![],
+            license: None,
+            license_file: None,
+            repository: None,
+            badges: HashMap::new(),
+            links: None,
+            rust_version: None,
+            yanked: false,
+        }
+    }
+
+    fn build_tarball(files: &[(&str, &str)]) -> Vec<u8> {
+        let mut builder = Builder::new(GzEncoder::new(vec![], Compression::default()));
+        for (path, contents) in files {
+            let mut header = Header::new_gnu();
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o644);
+            // bypass the builder's own path checks, we want to produce malicious tarballs too
+            header.as_gnu_mut().unwrap().name[..path.len()].copy_from_slice(path.as_bytes());
+            header.set_cksum();
+            builder.append(&header, contents.as_bytes()).unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap()
+    }
+
+    fn manifest(name: &str, version: &str) -> String {
+        format!(
+            "[package]\nname = \"{}\"\nversion = \"{}\"\n",
+            name, version
+        )
+    }
+
+    #[test]
+    fn test_valid_tarball() {
+        let cargo_toml = manifest("testcrate", "0.1.0");
+        let tarball = build_tarball(&[
+            ("testcrate-0.1.0/Cargo.toml", &cargo_toml),
+            ("testcrate-0.1.0/src/lib.rs", "pub fn f() {}"),
+        ]);
+
+        let result =
+            validate_crate_tarball(tarball.as_slice(), &build_metadata("testcrate", "0.1.0"));
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_mismatched_manifest() {
+        let cargo_toml = manifest("othercrate", "0.2.0");
+        let tarball = build_tarball(&[("testcrate-0.1.0/Cargo.toml", &cargo_toml)]);
+
+        let problems = find_problems(tarball.as_slice(), &build_metadata("testcrate", "0.1.0"));
+        assert_eq!(problems.len(), 2);
+        assert!(problems[0].contains("package name"));
+        assert!(problems[1].contains("package version"));
+    }
+
+    #[test]
+    fn test_missing_manifest_and_wrong_root() {
+        let tarball = build_tarball(&[("other-0.1.0/Cargo.toml", "")]);
+
+        let problems = find_problems(tarball.as_slice(), &build_metadata("testcrate", "0.1.0"));
+        assert_eq!(
+            problems,
+            vec![
+                "entry other-0.1.0/Cargo.toml is not inside the testcrate-0.1.0/ directory",
+                "the crate file does not contain testcrate-0.1.0/Cargo.toml",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_path_traversal_is_rejected() {
+        let cargo_toml = manifest("testcrate", "0.1.0");
+        let tarball = build_tarball(&[
+            ("testcrate-0.1.0/Cargo.toml", &cargo_toml),
+            ("testcrate-0.1.0/../../etc/passwd", "root"),
+        ]);
+
+        let problems = find_problems(tarball.as_slice(), &build_metadata("testcrate", "0.1.0"));
+        assert_eq!(
+            problems,
+            vec!["entry testcrate-0.1.0/../../etc/passwd escapes the crate root"]
+        );
+    }
+
+    #[test]
+    fn test_links_outside_the_root_are_rejected() {
+        let cargo_toml = manifest("testcrate", "0.1.0");
+        let mut builder = Builder::new(GzEncoder::new(vec![], Compression::default()));
+        let mut header = Header::new_gnu();
+        header.set_size(cargo_toml.len() as u64);
+        header.set_cksum();
+        builder
+            .append_data(
+                &mut header,
+                "testcrate-0.1.0/Cargo.toml",
+                cargo_toml.as_bytes(),
+            )
+            .unwrap();
+        let mut header = Header::new_gnu();
+        header.set_entry_type(EntryType::Symlink);
+        header.set_size(0);
+        builder
+            .append_link(&mut header, "testcrate-0.1.0/secrets", "/etc/shadow")
+            .unwrap();
+        let tarball = builder.into_inner().unwrap().finish().unwrap();
+
+        let problems = find_problems(tarball.as_slice(), &build_metadata("testcrate", "0.1.0"));
+        assert_eq!(
+            problems,
+            vec!["entry testcrate-0.1.0/secrets links to /etc/shadow outside of the crate root"]
+        );
+    }
+
+    #[test]
+    fn test_not_a_tarball() {
+        let problems = find_problems(&b"not gzip"[..], &build_metadata("testcrate", "0.1.0"));
+        assert!(problems[0].starts_with("failed to read the crate file"));
+    }
+}
@@ -1,11 +1,13 @@
 use std::str::FromStr;
 
 use axum::extract::{Path, State};
-use axum::Json;
+use axum::{Extension, Json};
 use semver::Version;
 use serde::Serialize;
 
-use crate::error::AppResult;
+use crate::auth::{Mutation, SignedMutation};
+use crate::error::{AppError, AppResult};
+use crate::models::token::{EndpointScope, TokenScopes};
 use crate::router::AppState;
 
 #[derive(Serialize)]
@@ -14,10 +16,25 @@ pub struct Response {
 }
 
 pub async fn yank(
-    Path((crate_name, version)): Path<(String, String)>,
-    State((repository, _)): State<AppState>,
+    Path((requested_name, version)): Path<(String, String)>,
+    Extension(scopes): Extension<TokenScopes>,
+    signed: Option<Extension<SignedMutation>>,
+    State((repository, _, _)): State<AppState>,
 ) -> AppResult<Json<Response>> {
-    let vers = Version::from_str(&version).expect("version to be valid");
+    let vers = Version::from_str(&version).map_err(|_| AppError::InvalidVersion(version))?;
+    // cargo signs the name as it was typed, which is the one in the path
+    if let Some(Extension(signed)) = signed {
+        signed.check(Mutation::Yank, &requested_name, Some(&vers))?;
+    }
+    // scopes and the repository work with the name the crate was published with
+    let crate_name = repository
+        .resolve_crate_name(&requested_name)
+        .await?
+        .ok_or_else(|| AppError::NonExistentCrateVersion {
+            crate_name: requested_name.clone(),
+            version: vers.clone(),
+        })?;
+    scopes.check(EndpointScope::Yank, &crate_name)?;
     repository.set_yanked(&crate_name, &vers, true).await?;
 
     let response = Json(Response { ok: true });
@@ -0,0 +1,880 @@
+//! Typed application configuration.
+//!
+//! The configuration is read from an optional TOML file (pointed to by `RAKTAR_CONFIG`)
+//! and then overridden by environment variables, so the Lambda deployment can keep
+//! configuring everything through its environment. Everything is validated once at
+//! startup, so a misconfigured application fails to start rather than failing requests.
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use serde::Deserialize;
+use thiserror::Error;
+use url::Url;
+
+use crate::models::user::UserId;
+
+pub const CONFIG_PATH_VAR: &str = "RAKTAR_CONFIG";
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("failed to read configuration file {path}: {source}")]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("failed to parse configuration file {path}: {source}")]
+    Parse {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+    #[error("invalid value {value:?} for {key}")]
+    InvalidValue { key: &'static str, value: String },
+    #[error("missing required configuration: {0}")]
+    Missing(&'static str),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RaktarConfig {
+    pub repository: RepositoryConfig,
+    pub storage: StorageConfig,
+    pub downloads: DownloadMode,
+    /// Whether downloads are checked against their checksum before they are served.
+    /// Checked downloads are read into memory and never redirected.
+    pub verify_downloads: bool,
+    pub listen_address: SocketAddr,
+    /// The public domain name the registry is served on, e.g. `raktar.io`.
+    pub domain_name: String,
+    pub auth: AuthConfig,
+    pub limits: PublishLimits,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum RepositoryConfig {
+    DynamoDB {
+        table_name: String,
+    },
+    /// Keeps everything in memory, nothing survives a restart.
+    Memory,
+    /// A relational database, either SQLite or PostgreSQL depending on the URL.
+    Sql {
+        database_url: String,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum StorageConfig {
+    S3 {
+        bucket: String,
+    },
+    Filesystem {
+        path: PathBuf,
+        /// Where `path` is served from, needed to redirect downloads to it.
+        public_url: Option<Url>,
+    },
+}
+
+/// How crate files are handed to cargo.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum DownloadMode {
+    /// Redirect to the storage when it can provide a URL, proxy the bytes otherwise.
+    #[default]
+    Redirect,
+    /// Always proxy the bytes, for storage that isn't reachable from clients.
+    Proxy,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct AuthConfig {
+    pub mode: AuthMode,
+    /// Users allowed to manage the registry itself, e.g. raise the limits of a crate.
+    pub admins: Vec<UserId>,
+    pub jwt: JwtConfig,
+}
+
+/// How the JWTs the GraphQL API is called with are verified.
+#[derive(Clone, Debug, PartialEq)]
+pub struct JwtConfig {
+    pub jwks: JwksSource,
+    /// The `iss` tokens must be issued by, the user pool URL for Cognito.
+    pub issuer: String,
+    /// The `aud` tokens must be issued for, the app client ID for Cognito.
+    pub audience: String,
+}
+
+/// Where the keys tokens are signed with are published.
+#[derive(Clone, Debug, PartialEq)]
+pub enum JwksSource {
+    Url(Url),
+    File(PathBuf),
+}
+
+/// Registry-wide limits on what can be published, all sizes are in bytes.
+///
+/// Everything but the upload and metadata sizes can be raised for a single crate,
+/// see [`crate::models::limits::CrateLimits`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct PublishLimits {
+    /// The size of the whole publish request, enforced as soon as both length prefixes are read.
+    pub max_upload_size: u64,
+    pub max_crate_size: u64,
+    pub max_metadata_size: u64,
+    pub max_dependencies: usize,
+    pub max_features: usize,
+    pub max_readme_length: usize,
+}
+
+impl Default for PublishLimits {
+    fn default() -> Self {
+        let max_crate_size = 10 * 1024 * 1024;
+        let max_metadata_size = 1024 * 1024;
+        Self {
+            max_upload_size: default_upload_size(max_crate_size, max_metadata_size),
+            max_crate_size,
+            max_metadata_size,
+            max_dependencies: 500,
+            max_features: 300,
+            max_readme_length: 512 * 1024,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum AuthMode {
+    /// Every request to the Cargo API must carry a registry token.
+    #[default]
+    Required,
+    /// The index and crate downloads are public, changing the registry still needs a token.
+    AnonymousRead,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum RepositoryBackend {
+    DynamoDB,
+    Memory,
+    Sql,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageBackend {
+    S3,
+    Filesystem,
+}
+
+/// The configuration as it is written in the file, before any validation.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RawConfig {
+    repository: RawRepositoryConfig,
+    storage: RawStorageConfig,
+    listen_address: Option<SocketAddr>,
+    domain_name: Option<String>,
+    auth: RawAuthConfig,
+    limits: RawLimitsConfig,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RawRepositoryConfig {
+    backend: Option<RepositoryBackend>,
+    table_name: Option<String>,
+    database_url: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RawStorageConfig {
+    backend: Option<StorageBackend>,
+    bucket: Option<String>,
+    path: Option<PathBuf>,
+    public_url: Option<Url>,
+    downloads: Option<DownloadMode>,
+    verify_downloads: Option<bool>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RawAuthConfig {
+    mode: Option<AuthMode>,
+    admins: Option<Vec<UserId>>,
+    jwt_issuer: Option<String>,
+    jwt_audience: Option<String>,
+    jwks_url: Option<Url>,
+    jwks_path: Option<PathBuf>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RawLimitsConfig {
+    max_upload_size: Option<u64>,
+    max_crate_size: Option<u64>,
+    max_metadata_size: Option<u64>,
+    max_dependencies: Option<usize>,
+    max_features: Option<usize>,
+    max_readme_length: Option<usize>,
+}
+
+impl RaktarConfig {
+    /// Loads the configuration from the file in `RAKTAR_CONFIG` (if set) and the environment.
+    pub fn load() -> Result<Self, ConfigError> {
+        Self::from_sources(read_config_file()?, |key| std::env::var(key).ok())
+    }
+
+    /// Builds the configuration from the contents of a TOML file and a lookup for
+    /// environment overrides.
+    pub fn from_sources(
+        file: Option<(PathBuf, String)>,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Result<Self, ConfigError> {
+        RawConfig::from_sources(file, env)?.validate()
+    }
+}
+
+impl RepositoryConfig {
+    /// Loads only the repository configuration, for handlers that need nothing else,
+    /// from the same sources as [`RaktarConfig::load`].
+    pub fn load() -> Result<Self, ConfigError> {
+        Self::from_sources(read_config_file()?, |key| std::env::var(key).ok())
+    }
+
+    pub fn from_sources(
+        file: Option<(PathBuf, String)>,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Result<Self, ConfigError> {
+        RawConfig::from_sources(file, env)?.repository.validate()
+    }
+}
+
+/// Reads the file in `RAKTAR_CONFIG`, if it's set.
+fn read_config_file() -> Result<Option<(PathBuf, String)>, ConfigError> {
+    let Ok(path) = std::env::var(CONFIG_PATH_VAR) else {
+        return Ok(None);
+    };
+    let path = PathBuf::from(path);
+    let contents = std::fs::read_to_string(&path).map_err(|source| ConfigError::Io {
+        path: path.clone(),
+        source,
+    })?;
+
+    Ok(Some((path, contents)))
+}
+
+impl RawConfig {
+    fn from_sources(
+        file: Option<(PathBuf, String)>,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Result<Self, ConfigError> {
+        let mut raw = match file {
+            Some((path, contents)) => toml::from_str::<RawConfig>(&contents)
+                .map_err(|source| ConfigError::Parse { path, source })?,
+            None => RawConfig::default(),
+        };
+
+        raw.apply_env_overrides(env)?;
+        Ok(raw)
+    }
+
+    fn apply_env_overrides(
+        &mut self,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Result<(), ConfigError> {
+        if let Some(value) = env("RAKTAR_REPOSITORY_BACKEND") {
+            self.repository.backend = Some(parse_enum("RAKTAR_REPOSITORY_BACKEND", value)?);
+        }
+        if let Some(value) = env("TABLE_NAME") {
+            self.repository.table_name = Some(value);
+        }
+        if let Some(value) = env("DATABASE_URL") {
+            self.repository.database_url = Some(value);
+        }
+        if let Some(value) = env("RAKTAR_STORAGE_BACKEND") {
+            self.storage.backend = Some(parse_enum("RAKTAR_STORAGE_BACKEND", value)?);
+        }
+        if let Some(value) = env("CRATES_BUCKET_NAME") {
+            self.storage.bucket = Some(value);
+        }
+        if let Some(value) = env("CRATES_STORAGE_PATH") {
+            self.storage.path = Some(value.into());
+        }
+        if let Some(value) = env("CRATES_PUBLIC_URL") {
+            self.storage.public_url = Some(parse_value("CRATES_PUBLIC_URL", value)?);
+        }
+        if let Some(value) = env("RAKTAR_DOWNLOADS") {
+            self.storage.downloads = Some(parse_enum("RAKTAR_DOWNLOADS", value)?);
+        }
+        if let Some(value) = env("RAKTAR_VERIFY_DOWNLOADS") {
+            self.storage.verify_downloads = Some(parse_value("RAKTAR_VERIFY_DOWNLOADS", value)?);
+        }
+        if let Some(value) = env("RAKTAR_LISTEN_ADDRESS") {
+            let address = value.parse().map_err(|_| ConfigError::InvalidValue {
+                key: "RAKTAR_LISTEN_ADDRESS",
+                value,
+            })?;
+            self.listen_address = Some(address);
+        }
+        if let Some(value) = env("DOMAIN_NAME") {
+            self.domain_name = Some(value);
+        }
+        if let Some(value) = env("RAKTAR_AUTH_MODE") {
+            self.auth.mode = Some(parse_enum("RAKTAR_AUTH_MODE", value)?);
+        }
+        if let Some(value) = env("RAKTAR_ADMINS") {
+            let admins = value
+                .split(',')
+                .map(str::trim)
+                .filter(|id| !id.is_empty())
+                .map(|id| parse_value("RAKTAR_ADMINS", id.to_string()))
+                .collect::<Result<_, _>>()?;
+            self.auth.admins = Some(admins);
+        }
+        if let Some(value) = env("RAKTAR_JWT_ISSUER") {
+            self.auth.jwt_issuer = Some(value);
+        }
+        if let Some(value) = env("RAKTAR_JWT_AUDIENCE") {
+            self.auth.jwt_audience = Some(value);
+        }
+        if let Some(value) = env("RAKTAR_JWKS_URL") {
+            self.auth.jwks_url = Some(parse_value("RAKTAR_JWKS_URL", value)?);
+        }
+        if let Some(value) = env("RAKTAR_JWKS_PATH") {
+            self.auth.jwks_path = Some(value.into());
+        }
+        if let Some(value) = env("RAKTAR_MAX_UPLOAD_SIZE") {
+            self.limits.max_upload_size = Some(parse_value("RAKTAR_MAX_UPLOAD_SIZE", value)?);
+        }
+        if let Some(value) = env("RAKTAR_MAX_CRATE_SIZE") {
+            self.limits.max_crate_size = Some(parse_value("RAKTAR_MAX_CRATE_SIZE", value)?);
+        }
+        if let Some(value) = env("RAKTAR_MAX_METADATA_SIZE") {
+            self.limits.max_metadata_size = Some(parse_value("RAKTAR_MAX_METADATA_SIZE", value)?);
+        }
+        if let Some(value) = env("RAKTAR_MAX_DEPENDENCIES") {
+            self.limits.max_dependencies = Some(parse_value("RAKTAR_MAX_DEPENDENCIES", value)?);
+        }
+        if let Some(value) = env("RAKTAR_MAX_FEATURES") {
+            self.limits.max_features = Some(parse_value("RAKTAR_MAX_FEATURES", value)?);
+        }
+        if let Some(value) = env("RAKTAR_MAX_README_LENGTH") {
+            self.limits.max_readme_length = Some(parse_value("RAKTAR_MAX_README_LENGTH", value)?);
+        }
+
+        Ok(())
+    }
+
+    fn validate(self) -> Result<RaktarConfig, ConfigError> {
+        let repository = self.repository.validate()?;
+
+        // when the backend isn't given explicitly, a configured path selects the filesystem
+        let storage_backend = match (self.storage.backend, &self.storage.path) {
+            (Some(backend), _) => backend,
+            (None, Some(_)) => StorageBackend::Filesystem,
+            (None, None) => StorageBackend::S3,
+        };
+        let storage = match storage_backend {
+            StorageBackend::S3 => StorageConfig::S3 {
+                bucket: non_empty(self.storage.bucket)
+                    .ok_or(ConfigError::Missing("storage.bucket (CRATES_BUCKET_NAME)"))?,
+            },
+            StorageBackend::Filesystem => StorageConfig::Filesystem {
+                path: self
+                    .storage
+                    .path
+                    .ok_or(ConfigError::Missing("storage.path (CRATES_STORAGE_PATH)"))?,
+                public_url: self.storage.public_url,
+            },
+        };
+
+        let domain_name =
+            non_empty(self.domain_name).ok_or(ConfigError::Missing("domain_name (DOMAIN_NAME)"))?;
+
+        Ok(RaktarConfig {
+            repository,
+            storage,
+            downloads: self.storage.downloads.unwrap_or_default(),
+            verify_downloads: self.storage.verify_downloads.unwrap_or(false),
+            listen_address: self
+                .listen_address
+                .unwrap_or_else(|| SocketAddr::from(([0, 0, 0, 0], 3026))),
+            domain_name,
+            auth: self.auth.validate()?,
+            limits: self.limits.validate(),
+        })
+    }
+}
+
+impl RawRepositoryConfig {
+    fn validate(self) -> Result<RepositoryConfig, ConfigError> {
+        let repository = match self.backend.unwrap_or(RepositoryBackend::DynamoDB) {
+            RepositoryBackend::DynamoDB => RepositoryConfig::DynamoDB {
+                table_name: non_empty(self.table_name)
+                    .ok_or(ConfigError::Missing("repository.table_name (TABLE_NAME)"))?,
+            },
+            RepositoryBackend::Memory => RepositoryConfig::Memory,
+            RepositoryBackend::Sql => {
+                let database_url = non_empty(self.database_url).ok_or(ConfigError::Missing(
+                    "repository.database_url (DATABASE_URL)",
+                ))?;
+                let is_supported = ["sqlite:", "postgres:", "postgresql:"]
+                    .iter()
+                    .any(|scheme| database_url.starts_with(scheme));
+                if !is_supported {
+                    return Err(ConfigError::InvalidValue {
+                        key: "repository.database_url",
+                        value: database_url,
+                    });
+                }
+                RepositoryConfig::Sql { database_url }
+            }
+        };
+
+        Ok(repository)
+    }
+}
+
+impl RawAuthConfig {
+    fn validate(self) -> Result<AuthConfig, ConfigError> {
+        let issuer = non_empty(self.jwt_issuer)
+            .ok_or(ConfigError::Missing("auth.jwt_issuer (RAKTAR_JWT_ISSUER)"))?;
+        let audience = non_empty(self.jwt_audience).ok_or(ConfigError::Missing(
+            "auth.jwt_audience (RAKTAR_JWT_AUDIENCE)",
+        ))?;
+        let jwks = match (self.jwks_url, self.jwks_path) {
+            (Some(url), None) => JwksSource::Url(url),
+            (None, Some(path)) => JwksSource::File(path),
+            (Some(url), Some(_)) => {
+                return Err(ConfigError::InvalidValue {
+                    key: "auth.jwks_url (only one of jwks_url and jwks_path can be set)",
+                    value: url.to_string(),
+                })
+            }
+            // where Cognito, like most OpenID providers, publishes its keys
+            (None, None) => {
+                let url = format!("{}/.well-known/jwks.json", issuer.trim_end_matches('/'));
+                JwksSource::Url(url.parse().map_err(|_| ConfigError::InvalidValue {
+                    key: "auth.jwt_issuer",
+                    value: issuer.clone(),
+                })?)
+            }
+        };
+
+        Ok(AuthConfig {
+            mode: self.mode.unwrap_or_default(),
+            admins: self.admins.unwrap_or_default(),
+            jwt: JwtConfig {
+                jwks,
+                issuer,
+                audience,
+            },
+        })
+    }
+}
+
+impl RawLimitsConfig {
+    fn validate(self) -> PublishLimits {
+        let defaults = PublishLimits::default();
+        let max_crate_size = self.max_crate_size.unwrap_or(defaults.max_crate_size);
+        let max_metadata_size = self.max_metadata_size.unwrap_or(defaults.max_metadata_size);
+        // by default, the upload limit fits the largest crate with the largest metadata
+        let max_upload_size = self
+            .max_upload_size
+            .unwrap_or_else(|| default_upload_size(max_crate_size, max_metadata_size));
+        PublishLimits {
+            max_upload_size,
+            max_crate_size,
+            max_metadata_size,
+            max_dependencies: self.max_dependencies.unwrap_or(defaults.max_dependencies),
+            max_features: self.max_features.unwrap_or(defaults.max_features),
+            max_readme_length: self.max_readme_length.unwrap_or(defaults.max_readme_length),
+        }
+    }
+}
+
+/// The crate and metadata together with their two length prefixes.
+pub(crate) fn default_upload_size(max_crate_size: u64, max_metadata_size: u64) -> u64 {
+    max_crate_size
+        .saturating_add(max_metadata_size)
+        .saturating_add(8)
+}
+
+fn parse_enum<T: for<'de> Deserialize<'de>>(
+    key: &'static str,
+    value: String,
+) -> Result<T, ConfigError> {
+    T::deserialize(serde::de::value::StrDeserializer::<serde::de::value::Error>::new(&value))
+        .map_err(|_| ConfigError::InvalidValue { key, value })
+}
+
+fn parse_value<T: FromStr>(key: &'static str, value: String) -> Result<T, ConfigError> {
+    value
+        .parse()
+        .map_err(|_| ConfigError::InvalidValue { key, value })
+}
+
+fn non_empty(value: Option<String>) -> Option<String> {
+    value.filter(|v| !v.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    const ISSUER: &str = "https://cognito-idp.eu-west-1.amazonaws.com/eu-west-1_pool";
+
+    fn env_from(pairs: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let vars: HashMap<String, String> = pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        move |key| vars.get(key).cloned()
+    }
+
+    #[test]
+    fn test_lambda_environment_is_enough() {
+        let env = env_from(&[
+            ("TABLE_NAME", "raktar"),
+            ("CRATES_BUCKET_NAME", "raktar-crates"),
+            ("DOMAIN_NAME", "raktar.io"),
+            ("RAKTAR_JWT_ISSUER", ISSUER),
+            ("RAKTAR_JWT_AUDIENCE", "raktar-app"),
+        ]);
+
+        let config = RaktarConfig::from_sources(None, env).unwrap();
+
+        assert_eq!(
+            config.repository,
+            RepositoryConfig::DynamoDB {
+                table_name: "raktar".to_string()
+            }
+        );
+        assert_eq!(
+            config.storage,
+            StorageConfig::S3 {
+                bucket: "raktar-crates".to_string()
+            }
+        );
+        assert_eq!(config.domain_name, "raktar.io");
+        assert_eq!(config.auth.mode, AuthMode::Required);
+        assert_eq!(
+            config.auth.jwt,
+            JwtConfig {
+                jwks: JwksSource::Url(format!("{}/.well-known/jwks.json", ISSUER).parse().unwrap()),
+                issuer: ISSUER.to_string(),
+                audience: "raktar-app".to_string(),
+            }
+        );
+        assert_eq!(config.downloads, DownloadMode::Redirect);
+        assert!(!config.verify_downloads);
+    }
+
+    #[test]
+    fn test_repository_config_only_needs_the_repository() {
+        let env = env_from(&[("TABLE_NAME", "raktar")]);
+
+        let repository = RepositoryConfig::from_sources(None, &env).unwrap();
+
+        assert_eq!(
+            repository,
+            RepositoryConfig::DynamoDB {
+                table_name: "raktar".to_string()
+            }
+        );
+        assert!(RaktarConfig::from_sources(None, &env).is_err());
+        assert!(matches!(
+            RepositoryConfig::from_sources(None, env_from(&[])),
+            Err(ConfigError::Missing("repository.table_name (TABLE_NAME)"))
+        ));
+    }
+
+    #[test]
+    fn test_file_with_env_overrides() {
+        let contents = r#"
+            listen_address = "127.0.0.1:8080"
+            domain_name = "registry.internal"
+
+            [repository]
+            table_name = "from-file"
+
+            [storage]
+            backend = "filesystem"
+            path = "/var/lib/raktar"
+        "#;
+        let env = env_from(&[
+            ("TABLE_NAME", "from-env"),
+            ("RAKTAR_JWT_ISSUER", ISSUER),
+            ("RAKTAR_JWT_AUDIENCE", "raktar-app"),
+        ]);
+
+        let config =
+            RaktarConfig::from_sources(Some(("raktar.toml".into(), contents.to_string())), env)
+                .unwrap();
+
+        assert_eq!(
+            config.repository,
+            RepositoryConfig::DynamoDB {
+                table_name: "from-env".to_string()
+            }
+        );
+        assert_eq!(
+            config.storage,
+            StorageConfig::Filesystem {
+                path: "/var/lib/raktar".into(),
+                public_url: None,
+            }
+        );
+        assert_eq!(config.listen_address, "127.0.0.1:8080".parse().unwrap());
+    }
+
+    #[test]
+    fn test_memory_repository_needs_no_table() {
+        let env = env_from(&[
+            ("RAKTAR_REPOSITORY_BACKEND", "memory"),
+            ("CRATES_STORAGE_PATH", "/tmp/raktar"),
+            ("DOMAIN_NAME", "localhost"),
+            ("RAKTAR_JWT_ISSUER", ISSUER),
+            ("RAKTAR_JWT_AUDIENCE", "raktar-app"),
+        ]);
+
+        let config = RaktarConfig::from_sources(None, env).unwrap();
+
+        assert_eq!(config.repository, RepositoryConfig::Memory);
+    }
+
+    #[test]
+    fn test_sql_repository_needs_a_supported_database() {
+        let contents = r#"
+            domain_name = "raktar.io"
+
+            [repository]
+            backend = "sql"
+            database_url = "mysql://localhost/raktar"
+
+            [storage]
+            path = "/var/lib/raktar"
+        "#;
+
+        let result =
+            RaktarConfig::from_sources(Some(("raktar.toml".into(), contents.to_string())), |_| {
+                None
+            });
+
+        assert!(matches!(
+            result,
+            Err(ConfigError::InvalidValue {
+                key: "repository.database_url",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_missing_values_are_reported() {
+        let env = env_from(&[("TABLE_NAME", "raktar"), ("DOMAIN_NAME", "raktar.io")]);
+
+        let result = RaktarConfig::from_sources(None, env);
+
+        assert!(matches!(
+            result,
+            Err(ConfigError::Missing("storage.bucket (CRATES_BUCKET_NAME)"))
+        ));
+    }
+
+    #[test]
+    fn test_invalid_backend_is_reported() {
+        let env = env_from(&[
+            ("TABLE_NAME", "raktar"),
+            ("DOMAIN_NAME", "raktar.io"),
+            ("RAKTAR_STORAGE_BACKEND", "ftp"),
+        ]);
+
+        let result = RaktarConfig::from_sources(None, env);
+
+        assert!(matches!(
+            result,
+            Err(ConfigError::InvalidValue {
+                key: "RAKTAR_STORAGE_BACKEND",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_unknown_keys_are_rejected() {
+        let contents = r#"
+            domain_nme = "raktar.io"
+        "#;
+
+        let result =
+            RaktarConfig::from_sources(Some(("raktar.toml".into(), contents.to_string())), |_| {
+                None
+            });
+
+        assert!(matches!(result, Err(ConfigError::Parse { .. })));
+    }
+
+    #[test]
+    fn test_limits_and_admins() {
+        let contents = r#"
+            domain_name = "raktar.io"
+
+            [auth]
+            admins = [1, 2]
+
+            [storage]
+            path = "/var/lib/raktar"
+
+            [limits]
+            max_crate_size = 1000
+            max_metadata_size = 100
+            max_dependencies = 10
+        "#;
+        let env = env_from(&[
+            ("TABLE_NAME", "raktar"),
+            ("RAKTAR_ADMINS", "3, 4"),
+            ("RAKTAR_MAX_FEATURES", "5"),
+            ("RAKTAR_JWT_ISSUER", ISSUER),
+            ("RAKTAR_JWT_AUDIENCE", "raktar-app"),
+        ]);
+
+        let config =
+            RaktarConfig::from_sources(Some(("raktar.toml".into(), contents.to_string())), env)
+                .unwrap();
+
+        assert_eq!(config.auth.admins, vec![3, 4]);
+        assert_eq!(
+            config.limits,
+            PublishLimits {
+                max_upload_size: 1108,
+                max_crate_size: 1000,
+                max_metadata_size: 100,
+                max_dependencies: 10,
+                max_features: 5,
+                max_readme_length: 512 * 1024,
+            }
+        );
+    }
+
+    #[test]
+    fn test_invalid_limit_is_reported() {
+        let env = env_from(&[
+            ("TABLE_NAME", "raktar"),
+            ("CRATES_BUCKET_NAME", "raktar-crates"),
+            ("DOMAIN_NAME", "raktar.io"),
+            ("RAKTAR_MAX_CRATE_SIZE", "10MB"),
+        ]);
+
+        let result = RaktarConfig::from_sources(None, env);
+
+        assert!(matches!(
+            result,
+            Err(ConfigError::InvalidValue {
+                key: "RAKTAR_MAX_CRATE_SIZE",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_downloads() {
+        let contents = r#"
+            domain_name = "raktar.io"
+
+            [storage]
+            path = "/var/lib/raktar"
+            public_url = "https://static.raktar.io/"
+        "#;
+        let env = env_from(&[
+            ("TABLE_NAME", "raktar"),
+            ("RAKTAR_DOWNLOADS", "proxy"),
+            ("RAKTAR_VERIFY_DOWNLOADS", "true"),
+            ("RAKTAR_JWT_ISSUER", ISSUER),
+            ("RAKTAR_JWT_AUDIENCE", "raktar-app"),
+        ]);
+
+        let config =
+            RaktarConfig::from_sources(Some(("raktar.toml".into(), contents.to_string())), env)
+                .unwrap();
+
+        assert_eq!(
+            config.storage,
+            StorageConfig::Filesystem {
+                path: "/var/lib/raktar".into(),
+                public_url: Some("https://static.raktar.io/".parse().unwrap()),
+            }
+        );
+        assert_eq!(config.downloads, DownloadMode::Proxy);
+        assert!(config.verify_downloads);
+    }
+
+    #[test]
+    fn test_auth_mode() {
+        let contents = r#"
+            domain_name = "raktar.io"
+
+            [auth]
+            mode = "anonymous-read"
+        "#;
+        let file = Some(("raktar.toml".into(), contents.to_string()));
+        let vars = [
+            ("TABLE_NAME", "raktar"),
+            ("CRATES_BUCKET_NAME", "raktar-crates"),
+            ("RAKTAR_JWT_ISSUER", ISSUER),
+            ("RAKTAR_JWT_AUDIENCE", "raktar-app"),
+        ];
+        let env_with_mode = |mode| env_from(&[&vars[..], &[("RAKTAR_AUTH_MODE", mode)]].concat());
+
+        let config = RaktarConfig::from_sources(file.clone(), env_from(&vars)).unwrap();
+        assert_eq!(config.auth.mode, AuthMode::AnonymousRead);
+
+        let config = RaktarConfig::from_sources(file.clone(), env_with_mode("required")).unwrap();
+        assert_eq!(config.auth.mode, AuthMode::Required);
+
+        assert!(matches!(
+            RaktarConfig::from_sources(file, env_with_mode("public")),
+            Err(ConfigError::InvalidValue {
+                key: "RAKTAR_AUTH_MODE",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_jwt_settings() {
+        let contents = r#"
+            domain_name = "raktar.io"
+
+            [auth]
+            jwt_audience = "raktar-app"
+            jwks_path = "/etc/raktar/jwks.json"
+
+            [storage]
+            path = "/var/lib/raktar"
+        "#;
+        let file = || Some(("raktar.toml".into(), contents.to_string()));
+
+        let result = RaktarConfig::from_sources(file(), env_from(&[("TABLE_NAME", "raktar")]));
+        assert!(matches!(
+            result,
+            Err(ConfigError::Missing("auth.jwt_issuer (RAKTAR_JWT_ISSUER)"))
+        ));
+
+        let env = env_from(&[("TABLE_NAME", "raktar"), ("RAKTAR_JWT_ISSUER", ISSUER)]);
+        let config = RaktarConfig::from_sources(file(), env).unwrap();
+        assert_eq!(
+            config.auth.jwt.jwks,
+            JwksSource::File("/etc/raktar/jwks.json".into())
+        );
+
+        let env = env_from(&[
+            ("TABLE_NAME", "raktar"),
+            ("RAKTAR_JWT_ISSUER", ISSUER),
+            ("RAKTAR_JWKS_URL", "https://keys.raktar.io/jwks.json"),
+        ]);
+        let result = RaktarConfig::from_sources(file(), env);
+        assert!(matches!(result, Err(ConfigError::InvalidValue { .. })));
+    }
+}
@@ -9,6 +9,8 @@ use std::num::ParseIntError;
 use thiserror::Error;
 use tracing::error;
 
+use crate::models::token::EndpointScope;
+
 #[derive(Debug, Error)]
 pub enum AppError {
     #[error("package info for {0} does not exist")]
@@ -20,6 +22,8 @@ pub enum AppError {
         crate_name: String,
         version: Version,
     },
+    #[error("invalid version {0:?}")]
+    InvalidVersion(String),
     #[error("version {version} for {crate_name} already exists")]
     DuplicateCrateVersion {
         crate_name: String,
@@ -27,6 +31,35 @@ pub enum AppError {
     },
     #[error("package info for {0} does not exist")]
     Unauthorized(String),
+    #[error("this token does not have the {scope} scope for {crate_name}")]
+    MissingTokenScope {
+        scope: EndpointScope,
+        crate_name: String,
+    },
+    #[error("this token was not signed for this request: {0}")]
+    TokenClaimMismatch(String),
+    #[error("the public key {0} is already registered")]
+    DuplicatePublicKey(String),
+    #[error("invalid publish request: {0}")]
+    InvalidPublishBody(String),
+    #[error("invalid crate name {crate_name:?}: {reason}")]
+    InvalidCrateName { crate_name: String, reason: String },
+    #[error("crate name {crate_name} is too similar to the existing crate {existing}")]
+    CrateNameCollision {
+        crate_name: String,
+        existing: String,
+    },
+    #[error("{0}")]
+    PublishLimitExceeded(String),
+    #[error("crate {0} was changed by another request at the same time, try again")]
+    ConcurrentWrite(String),
+    #[error("invalid crate file: {}", .0.join("; "))]
+    InvalidCrateFile(Vec<String>),
+    #[error("the stored crate file for {crate_name} {version} does not match its checksum")]
+    CorruptCrateFile {
+        crate_name: String,
+        version: Version,
+    },
     #[error(transparent)]
     Anyhow(#[from] anyhow::Error),
     #[error("unexpected error")]
@@ -40,8 +73,19 @@ impl IntoResponse for AppError {
             AppError::NonExistentPackageInfo(_) => StatusCode::NOT_FOUND,
             AppError::NonExistentCrate(_) => StatusCode::NOT_FOUND,
             AppError::NonExistentCrateVersion { .. } => StatusCode::NOT_FOUND,
+            AppError::InvalidVersion(_) => StatusCode::BAD_REQUEST,
             AppError::DuplicateCrateVersion { .. } => StatusCode::BAD_REQUEST,
             AppError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            AppError::MissingTokenScope { .. } => StatusCode::FORBIDDEN,
+            AppError::TokenClaimMismatch(_) => StatusCode::FORBIDDEN,
+            AppError::DuplicatePublicKey(_) => StatusCode::BAD_REQUEST,
+            AppError::InvalidPublishBody(_) => StatusCode::BAD_REQUEST,
+            AppError::InvalidCrateName { .. } => StatusCode::BAD_REQUEST,
+            AppError::CrateNameCollision { .. } => StatusCode::BAD_REQUEST,
+            AppError::PublishLimitExceeded(_) => StatusCode::BAD_REQUEST,
+            AppError::ConcurrentWrite(_) => StatusCode::CONFLICT,
+            AppError::InvalidCrateFile(_) => StatusCode::BAD_REQUEST,
+            AppError::CorruptCrateFile { .. } => StatusCode::INTERNAL_SERVER_ERROR,
             AppError::Anyhow(_) => StatusCode::INTERNAL_SERVER_ERROR,
             AppError::Other(_) => StatusCode::INTERNAL_SERVER_ERROR,
         };
@@ -89,6 +133,24 @@ impl From<ParseIntError> for AppError {
     }
 }
 
+impl From<sqlx::Error> for AppError {
+    fn from(err: sqlx::Error) -> Self {
+        let error_message = err.to_string();
+        let error_type = "sqlx".to_string();
+        error!(error_message, error_type, "unexpected error");
+        Self::Other(error_message)
+    }
+}
+
+impl From<sqlx::migrate::MigrateError> for AppError {
+    fn from(err: sqlx::migrate::MigrateError) -> Self {
+        let error_message = err.to_string();
+        let error_type = "sqlx_migrate".to_string();
+        error!(error_message, error_type, "unexpected error");
+        Self::Other(error_message)
+    }
+}
+
 pub type AppResult<T> = Result<T, AppError>;
 
 pub fn internal_error() -> AppError {
@@ -1,23 +1,22 @@
-use crate::auth::AuthenticatedUser;
-use anyhow::{anyhow, bail, Result};
+use crate::auth::{AuthenticatedUser, JwtVerifier};
+use anyhow::{anyhow, Result};
 use async_graphql::http::GraphiQLSource;
 use async_graphql_axum::{GraphQLRequest, GraphQLResponse};
 use axum::extract::Extension;
 use axum::response;
 use axum::response::IntoResponse;
-use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
 use http::HeaderMap;
-use serde::Deserialize;
-use std::str::FromStr;
+use std::sync::Arc;
 
 use crate::graphql::schema::RaktarSchema;
 
 pub async fn graphql_handler(
     schema: Extension<RaktarSchema>,
+    verifier: Extension<Arc<JwtVerifier>>,
     headers: HeaderMap,
     req: GraphQLRequest,
 ) -> GraphQLResponse {
-    match extract_user_id(&headers) {
+    match extract_user(&verifier, &headers).await {
         Ok(authenticated_user) => {
             let request = req.into_inner().data(authenticated_user);
             schema.execute(request).await.into()
@@ -44,33 +43,12 @@ pub async fn graphiql() -> impl IntoResponse {
     response::Html(GraphiQLSource::build().endpoint("/gql").finish())
 }
 
-#[derive(Debug, Deserialize)]
-struct Claims {
-    autogen_id: String,
-}
-
-fn extract_user_id(headers: &HeaderMap) -> Result<AuthenticatedUser> {
-    headers
+async fn extract_user(verifier: &JwtVerifier, headers: &HeaderMap) -> Result<AuthenticatedUser> {
+    let header = headers
         .get("Authorization")
-        .and_then(|header| {
-            let token = header.to_str().ok()?;
-            let claims = parse_token(token).ok()?;
-            Some(AuthenticatedUser {
-                id: u32::from_str(&claims.autogen_id).ok()?,
-            })
-        })
-        .ok_or(anyhow!("failed to get authenticated user details"))
-}
+        .ok_or(anyhow!("no token in the request"))?
+        .to_str()?;
+    let token = header.strip_prefix("Bearer ").unwrap_or(header);
 
-fn parse_token(token: &str) -> Result<Claims> {
-    let mut parts = token.rsplitn(3, '.');
-    if let (Some(_), Some(payload), Some(_), None) =
-        (parts.next(), parts.next(), parts.next(), parts.next())
-    {
-        let decoded = URL_SAFE_NO_PAD.decode(payload)?;
-        let claims = serde_json::from_slice::<Claims>(&decoded)?;
-        Ok(claims)
-    } else {
-        bail!("failed to parse token");
-    }
+    Ok(verifier.verify(token).await?)
 }
@@ -2,13 +2,19 @@ use anyhow::anyhow;
 use async_graphql::{Context, EmptySubscription, Object, Result, Schema, ID};
 use semver::Version;
 use std::str::FromStr;
+use std::sync::Arc;
 
-use crate::auth::{generate_new_token, AuthenticatedUser};
+use crate::auth::{generate_new_token, public_key_id, AuthenticatedUser};
+use crate::config::RaktarConfig;
 use crate::error::AppError;
 use crate::graphql::types::{
-    CrateSummary, CrateVersion, DeletedToken, GeneratedToken, Token, User,
+    ChecksumMismatch, CrateLimits, CrateSummary, CrateVersion, DeletedPublicKey, DeletedToken,
+    GeneratedToken, PublicKey, Token, TokenScopes, User,
 };
+use crate::integrity::verify_crates;
+use crate::models::token::{now, parse_timestamp, TokenScopes as TokenScopesModel};
 use crate::repository::DynRepository;
+use crate::storage::DynCrateStorage;
 
 pub struct Query;
 
@@ -77,6 +83,14 @@ impl Query {
         Ok(token_items.into_iter().map(From::from).collect())
     }
 
+    async fn my_public_keys(&self, ctx: &Context<'_>) -> Result<Vec<PublicKey>> {
+        let user = ctx.data::<AuthenticatedUser>()?;
+        let repository = ctx.data::<DynRepository>()?;
+
+        let keys = repository.list_public_keys(user.id).await?;
+        Ok(keys.into_iter().map(From::from).collect())
+    }
+
     async fn user(&self, ctx: &Context<'_>, id: ID) -> Result<Option<User>> {
         let repository = ctx.data::<DynRepository>()?;
         let user = repository.get_user_by_id(id.parse::<u32>()?).await?;
@@ -98,13 +112,33 @@ pub struct Mutation;
 
 #[Object]
 impl Mutation {
-    async fn generate_token(&self, ctx: &Context<'_>, name: String) -> Result<GeneratedToken> {
+    /// Tokens without scopes can be used for every crate the user owns, and tokens without
+    /// an expiry time (an RFC 3339 timestamp) never expire.
+    async fn generate_token(
+        &self,
+        ctx: &Context<'_>,
+        name: String,
+        scopes: Option<TokenScopes>,
+        expires_at: Option<String>,
+    ) -> Result<GeneratedToken> {
         let user = ctx.data::<AuthenticatedUser>()?;
         let repository = ctx.data::<DynRepository>()?;
 
+        let scopes = match scopes {
+            Some(scopes) => scopes.try_into().map_err(|err: String| anyhow!(err))?,
+            None => TokenScopesModel::default(),
+        };
+        let expires_at = expires_at
+            .as_deref()
+            .map(parse_timestamp)
+            .transpose()
+            .map_err(|err| anyhow!(err))?;
+        if expires_at.is_some_and(|expires_at| expires_at <= now()) {
+            return Err(anyhow!("the token can't expire in the past").into());
+        }
         let key = generate_new_token();
         let token_item = repository
-            .store_auth_token(key.as_bytes(), name, user.id)
+            .store_auth_token(key.as_bytes(), name, user.id, scopes, expires_at)
             .await?;
         let token: Token = token_item.into();
         let generated_token = GeneratedToken {
@@ -126,12 +160,94 @@ impl Mutation {
 
         Ok(DeletedToken { id: token_id })
     }
+
+    /// Registers a public key (a `k3.public.…` PASERK) that cargo's `cargo:paseto`
+    /// credential provider can sign requests with instead of sending a token.
+    async fn register_public_key(
+        &self,
+        ctx: &Context<'_>,
+        name: String,
+        public_key: String,
+    ) -> Result<PublicKey> {
+        let user = ctx.data::<AuthenticatedUser>()?;
+        let repository = ctx.data::<DynRepository>()?;
+
+        let key_id = public_key_id(&public_key).map_err(|err| anyhow!(err))?;
+        let key = repository
+            .store_public_key(key_id, public_key, name, user.id)
+            .await?;
+
+        Ok(key.into())
+    }
+
+    async fn delete_public_key(
+        &self,
+        ctx: &Context<'_>,
+        key_id: String,
+    ) -> Result<DeletedPublicKey> {
+        let user = ctx.data::<AuthenticatedUser>()?;
+        let repository = ctx.data::<DynRepository>()?;
+
+        repository
+            .delete_public_key(user.id, key_id.clone())
+            .await?;
+
+        Ok(DeletedPublicKey { id: key_id })
+    }
+
+    async fn set_crate_limits(
+        &self,
+        ctx: &Context<'_>,
+        crate_name: String,
+        limits: CrateLimits,
+    ) -> Result<CrateLimits> {
+        let user = ctx.data::<AuthenticatedUser>()?;
+        let repository = ctx.data::<DynRepository>()?;
+        let config = ctx.data::<Arc<RaktarConfig>>()?;
+
+        if !config.auth.admins.contains(&user.id) {
+            return Err(anyhow!("only admins can change the limits of a crate").into());
+        }
+
+        repository
+            .set_crate_limits(&crate_name, limits.into())
+            .await?;
+        let limits = repository.get_crate_limits(&crate_name).await?;
+
+        Ok(limits.into())
+    }
+
+    /// Checks the stored crate files of every crate matching the prefix against their
+    /// checksums, returning the ones that don't match.
+    async fn verify_crates(
+        &self,
+        ctx: &Context<'_>,
+        filter: Option<String>,
+    ) -> Result<Vec<ChecksumMismatch>> {
+        let user = ctx.data::<AuthenticatedUser>()?;
+        let repository = ctx.data::<DynRepository>()?;
+        let storage = ctx.data::<DynCrateStorage>()?;
+        let config = ctx.data::<Arc<RaktarConfig>>()?;
+
+        if !config.auth.admins.contains(&user.id) {
+            return Err(anyhow!("only admins can verify crates").into());
+        }
+
+        let mismatches = verify_crates(repository, storage, filter).await?;
+        Ok(mismatches.into_iter().map(From::from).collect())
+    }
 }
 
 pub type RaktarSchema = Schema<Query, Mutation, EmptySubscription>;
 
-pub fn build_schema(repository: DynRepository) -> RaktarSchema {
+pub fn build_schema(
+    repository: DynRepository,
+    storage: DynCrateStorage,
+    config: Arc<RaktarConfig>,
+) -> RaktarSchema {
     Schema::build(Query, Mutation, EmptySubscription)
         .data(repository)
+        .data(storage)
+        .data(config)
         .finish()
 }
@@ -1,10 +1,20 @@
 use crate::error::AppError;
-use async_graphql::{ComplexObject, Context, Result, SimpleObject, ID};
+use async_graphql::{ComplexObject, Context, Enum, InputObject, Result, SimpleObject, ID};
 use futures::future::try_join_all;
+use semver::Version;
+use std::str::FromStr;
+use time::Duration;
 
+use crate::integrity::ChecksumMismatch as ChecksumMismatchModel;
 use crate::models::crate_summary::CrateSummary as CrateSummaryModel;
+use crate::models::downloads::{today, DailyDownloads as DailyDownloadsModel};
+use crate::models::limits::CrateLimits as CrateLimitsModel;
 use crate::models::metadata::Metadata;
-use crate::models::token::Token as TokenModel;
+use crate::models::public_key::PublicKey as PublicKeyModel;
+use crate::models::token::{
+    format_timestamp, CratePattern, EndpointScope as EndpointScopeModel, Token as TokenModel,
+    TokenScopes as TokenScopesModel,
+};
 use crate::models::user::User as UserModel;
 use crate::repository::DynRepository;
 
@@ -48,6 +58,23 @@ impl CrateSummary {
 
         Ok(versions)
     }
+
+    /// Downloads of every version combined.
+    async fn downloads(&self, ctx: &Context<'_>) -> Result<u64> {
+        let counts = get_download_counts(ctx, &self.name, None).await?;
+        Ok(counts.iter().map(|count| count.downloads).sum())
+    }
+
+    /// Downloads of every version combined on each of the last `days` days,
+    /// days without any downloads are left out.
+    async fn daily_downloads(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default = 90)] days: u32,
+    ) -> Result<Vec<DailyDownloads>> {
+        let counts = get_download_counts(ctx, &self.name, None).await?;
+        Ok(recent_days(counts, days))
+    }
 }
 
 impl From<CrateSummaryModel> for CrateSummary {
@@ -122,6 +149,58 @@ impl CrateVersion {
             Err(AppError::NonExistentCrate(self.name.clone()).into())
         }
     }
+
+    async fn downloads(&self, ctx: &Context<'_>) -> Result<u64> {
+        let version = Version::from_str(&self.version)?;
+        let counts = get_download_counts(ctx, &self.name, Some(&version)).await?;
+        Ok(counts.iter().map(|count| count.downloads).sum())
+    }
+
+    /// Downloads on each of the last `days` days, days without any downloads are left out.
+    async fn daily_downloads(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default = 90)] days: u32,
+    ) -> Result<Vec<DailyDownloads>> {
+        let version = Version::from_str(&self.version)?;
+        let counts = get_download_counts(ctx, &self.name, Some(&version)).await?;
+        Ok(recent_days(counts, days))
+    }
+}
+
+/// Downloads on a single day, days are in UTC.
+#[derive(SimpleObject)]
+pub struct DailyDownloads {
+    date: String,
+    downloads: u64,
+}
+
+impl From<DailyDownloadsModel> for DailyDownloads {
+    fn from(count: DailyDownloadsModel) -> Self {
+        Self {
+            date: count.date.to_string(),
+            downloads: count.downloads,
+        }
+    }
+}
+
+async fn get_download_counts(
+    ctx: &Context<'_>,
+    crate_name: &str,
+    version: Option<&Version>,
+) -> Result<Vec<DailyDownloadsModel>> {
+    let repository = ctx.data::<DynRepository>()?;
+    Ok(repository.get_download_counts(crate_name, version).await?)
+}
+
+/// Today counts as one of the days.
+fn recent_days(counts: Vec<DailyDownloadsModel>, days: u32) -> Vec<DailyDownloads> {
+    let first_day = today() - Duration::days(i64::from(days) - 1);
+    counts
+        .into_iter()
+        .filter(|count| count.date >= first_day)
+        .map(Into::into)
+        .collect()
 }
 
 #[derive(SimpleObject)]
@@ -129,6 +208,12 @@ pub struct Token {
     pub id: ID,
     user_id: u32,
     name: String,
+    scopes: TokenScopes,
+    /// RFC 3339 timestamps, the creation time is missing on tokens created before it was
+    /// recorded and the last use is only updated once an hour.
+    created_at: Option<String>,
+    expires_at: Option<String>,
+    last_used_at: Option<String>,
 }
 
 impl From<TokenModel> for Token {
@@ -137,10 +222,64 @@ impl From<TokenModel> for Token {
             id: item.token_id.into(),
             user_id: item.user_id,
             name: item.name,
+            scopes: item.scopes.into(),
+            created_at: item.created_at.map(format_timestamp),
+            expires_at: item.expires_at.map(format_timestamp),
+            last_used_at: item.last_used_at.map(format_timestamp),
         }
     }
 }
 
+#[derive(Enum, Copy, Clone, Eq, PartialEq)]
+#[graphql(remote = "EndpointScopeModel")]
+pub enum EndpointScope {
+    PublishNew,
+    PublishUpdate,
+    Yank,
+    ChangeOwners,
+}
+
+/// What a token can be used for, a missing list doesn't restrict the token and an
+/// empty list of endpoints makes it read-only.
+#[derive(InputObject, SimpleObject)]
+#[graphql(input_name = "TokenScopesInput")]
+pub struct TokenScopes {
+    pub endpoints: Option<Vec<EndpointScope>>,
+    /// Crate names, or name prefixes ending with `*`.
+    pub crates: Option<Vec<String>>,
+}
+
+impl From<TokenScopesModel> for TokenScopes {
+    fn from(scopes: TokenScopesModel) -> Self {
+        Self {
+            endpoints: scopes
+                .endpoints
+                .map(|endpoints| endpoints.into_iter().map(Into::into).collect()),
+            crates: scopes
+                .crates
+                .map(|patterns| patterns.into_iter().map(Into::into).collect()),
+        }
+    }
+}
+
+impl TryFrom<TokenScopes> for TokenScopesModel {
+    type Error = String;
+
+    fn try_from(scopes: TokenScopes) -> Result<Self, Self::Error> {
+        let crates = scopes
+            .crates
+            .map(|patterns| patterns.into_iter().map(CratePattern::try_from).collect())
+            .transpose()?;
+
+        Ok(Self {
+            endpoints: scopes
+                .endpoints
+                .map(|endpoints| endpoints.into_iter().map(Into::into).collect()),
+            crates,
+        })
+    }
+}
+
 #[derive(SimpleObject)]
 pub struct GeneratedToken {
     pub id: ID,
@@ -152,3 +291,85 @@ pub struct GeneratedToken {
 pub struct DeletedToken {
     pub id: String,
 }
+
+/// A public key cargo's `cargo:paseto` credential provider signs requests with.
+#[derive(SimpleObject)]
+pub struct PublicKey {
+    /// The PASERK ID of the key (`k3.pid.…`).
+    pub id: ID,
+    user_id: u32,
+    name: String,
+    /// The key as a PASERK (`k3.public.…`).
+    public_key: String,
+    /// An RFC 3339 timestamp.
+    created_at: String,
+}
+
+impl From<PublicKeyModel> for PublicKey {
+    fn from(key: PublicKeyModel) -> Self {
+        Self {
+            id: key.key_id.into(),
+            user_id: key.user_id,
+            name: key.name,
+            public_key: key.public_key,
+            created_at: format_timestamp(key.created_at),
+        }
+    }
+}
+
+#[derive(SimpleObject)]
+pub struct DeletedPublicKey {
+    pub id: String,
+}
+
+/// Publish limits raised for a single crate, unset limits use the registry-wide value.
+#[derive(InputObject, SimpleObject)]
+#[graphql(input_name = "CrateLimitsInput")]
+pub struct CrateLimits {
+    pub max_crate_size: Option<u64>,
+    pub max_dependencies: Option<usize>,
+    pub max_features: Option<usize>,
+    pub max_readme_length: Option<usize>,
+}
+
+impl From<CrateLimitsModel> for CrateLimits {
+    fn from(limits: CrateLimitsModel) -> Self {
+        Self {
+            max_crate_size: limits.max_crate_size,
+            max_dependencies: limits.max_dependencies,
+            max_features: limits.max_features,
+            max_readme_length: limits.max_readme_length,
+        }
+    }
+}
+
+impl From<CrateLimits> for CrateLimitsModel {
+    fn from(limits: CrateLimits) -> Self {
+        Self {
+            max_crate_size: limits.max_crate_size,
+            max_dependencies: limits.max_dependencies,
+            max_features: limits.max_features,
+            max_readme_length: limits.max_readme_length,
+        }
+    }
+}
+
+#[derive(SimpleObject)]
+pub struct ChecksumMismatch {
+    crate_name: String,
+    version: String,
+    expected: String,
+    /// Missing when the crate file isn't in storage at all.
+    actual: Option<String>,
+}
+
+impl From<ChecksumMismatchModel> for ChecksumMismatch {
+    fn from(mismatch: ChecksumMismatchModel) -> Self {
+        Self {
+            crate_name: mismatch.crate_name,
+            version: mismatch.version.to_string(),
+            expected: mismatch.expected,
+            actual: mismatch.actual,
+        }
+    }
+}
@@ -0,0 +1,126 @@
+//! Checks that stored crate files still match the checksums written into the index.
+//!
+//! Mismatches are reported as audit events, so they can be alerted on separately from
+//! the rest of the logs.
+use futures::TryStreamExt;
+use hex::ToHex;
+use semver::Version;
+use sha2::{Digest, Sha256};
+use tracing::error;
+
+use crate::error::{AppError, AppResult};
+use crate::models::index::PackageInfo;
+use crate::repository::DynRepository;
+use crate::storage::{CrateData, DynCrateStorage};
+
+/// The tracing target audit events are logged under.
+pub const AUDIT_TARGET: &str = "audit";
+
+/// A stored crate file that doesn't match the checksum of its index entry.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChecksumMismatch {
+    pub crate_name: String,
+    pub version: Version,
+    pub expected: String,
+    /// `None` when the crate file is missing from storage altogether.
+    pub actual: Option<String>,
+}
+
+/// Reads the crate file into memory and only returns it when it matches its checksum,
+/// corrupted bytes are never handed out.
+pub async fn read_verified_crate(
+    storage: &DynCrateStorage,
+    package_info: &PackageInfo,
+) -> AppResult<CrateData> {
+    let data = storage
+        .get_crate(&package_info.name, package_info.vers.clone())
+        .await?;
+    let bytes = data.into_bytes().await.map_err(anyhow::Error::from)?;
+    let actual: String = Sha256::digest(&bytes).encode_hex();
+    if actual != package_info.cksum {
+        report_mismatch(&ChecksumMismatch {
+            crate_name: package_info.name.clone(),
+            version: package_info.vers.clone(),
+            expected: package_info.cksum.clone(),
+            actual: Some(actual),
+        });
+        return Err(AppError::CorruptCrateFile {
+            crate_name: package_info.name.clone(),
+            version: package_info.vers.clone(),
+        });
+    }
+
+    Ok(CrateData::from_bytes(bytes))
+}
+
+/// Checks every version of the crates matching the prefix, all crates without one.
+pub async fn verify_crates(
+    repository: &DynRepository,
+    storage: &DynCrateStorage,
+    filter: Option<String>,
+) -> AppResult<Vec<ChecksumMismatch>> {
+    // the limit has to fit every backend's own integer type
+    let crates = repository
+        .get_all_crate_details(filter, i32::MAX as usize)
+        .await?;
+
+    let mut mismatches = vec![];
+    for summary in crates {
+        for package_info in repository.list_package_infos(&summary.name).await? {
+            if let Some(mismatch) = check_crate(storage, &package_info).await? {
+                report_mismatch(&mismatch);
+                mismatches.push(mismatch);
+            }
+        }
+    }
+
+    Ok(mismatches)
+}
+
+/// Hashes the stored crate file as it's read, without holding it in memory.
+async fn check_crate(
+    storage: &DynCrateStorage,
+    package_info: &PackageInfo,
+) -> AppResult<Option<ChecksumMismatch>> {
+    let actual = match storage
+        .get_crate(&package_info.name, package_info.vers.clone())
+        .await
+    {
+        Ok(data) => {
+            let hasher = data
+                .stream
+                .try_fold(Sha256::new(), |mut hasher, chunk| async move {
+                    hasher.update(&chunk);
+                    Ok(hasher)
+                })
+                .await
+                .map_err(anyhow::Error::from)?;
+            Some(hasher.finalize().encode_hex::<String>())
+        }
+        Err(AppError::NonExistentCrateVersion { .. }) => None,
+        Err(err) => return Err(err),
+    };
+
+    if actual.as_deref() == Some(package_info.cksum.as_str()) {
+        return Ok(None);
+    }
+
+    Ok(Some(ChecksumMismatch {
+        crate_name: package_info.name.clone(),
+        version: package_info.vers.clone(),
+        expected: package_info.cksum.clone(),
+        actual,
+    }))
+}
+
+fn report_mismatch(mismatch: &ChecksumMismatch) {
+    error!(
+        target: AUDIT_TARGET,
+        event = "checksum_mismatch",
+        crate_name = mismatch.crate_name,
+        version = mismatch.version.to_string(),
+        expected = mismatch.expected,
+        actual = mismatch.actual.as_deref().unwrap_or("missing"),
+        "stored crate file does not match its checksum"
+    );
+}
@@ -1,7 +1,9 @@
 pub mod auth;
 pub mod cargo_api;
+pub mod config;
 pub mod error;
 pub mod graphql;
+pub mod integrity;
 pub mod models;
 pub mod repository;
 pub mod router;
@@ -2,32 +2,91 @@ use std::sync::Arc;
 
 use aws_sdk_dynamodb::Client;
 use axum::Router;
-use raktar::repository::{DynRepository, DynamoDBRepository};
+use raktar::config::{RaktarConfig, RepositoryConfig, StorageConfig};
+use raktar::error::{AppError, AppResult};
+use raktar::repository::{DynRepository, DynamoDBRepository, MemoryRepository, SqlRepository};
 use raktar::router::build_router;
-use raktar::storage::{DynCrateStorage, S3Storage};
+use raktar::storage::{DynCrateStorage, FilesystemStorage, S3Storage};
+use tracing::error;
 
 #[tokio::main]
 async fn main() {
     tracing_subscriber::fmt().json().init();
 
-    let aws_config = aws_config::from_env().load().await;
-    let db_client = Client::new(&aws_config);
-    let repository = Arc::new(DynamoDBRepository::new_from_env(db_client)) as DynRepository;
-    let storage = Arc::new(S3Storage::new().await) as DynCrateStorage;
+    let config = match RaktarConfig::load() {
+        Ok(config) => config,
+        Err(err) => {
+            let error_message = err.to_string();
+            error!(error_message, "invalid configuration");
+            std::process::exit(1);
+        }
+    };
 
-    let app = build_router(repository, storage);
+    let repository = match build_repository(&config.repository).await {
+        Ok(repository) => repository,
+        Err(err) => {
+            // unexpected errors only tell clients that much, the cause is in the message
+            let error_message = match err {
+                AppError::Other(message) => message,
+                err => err.to_string(),
+            };
+            error!(error_message, "failed to set up the repository");
+            std::process::exit(1);
+        }
+    };
+    let storage = build_storage(&config.storage).await;
+    let listen_address = config.listen_address;
 
-    run_app(app).await
+    let app = build_router(repository, storage, Arc::new(config));
+
+    run_app(app, listen_address).await
+}
+
+async fn build_repository(config: &RepositoryConfig) -> AppResult<DynRepository> {
+    let repository = match config {
+        RepositoryConfig::DynamoDB { table_name } => {
+            let aws_config = aws_config::from_env().load().await;
+            let db_client = Client::new(&aws_config);
+            let repository = DynamoDBRepository::new(db_client, table_name.clone());
+            repository.migrate().await?;
+            Arc::new(repository) as DynRepository
+        }
+        RepositoryConfig::Memory => {
+            tracing::warn!("using the in-memory repository, data will be lost on restart");
+            Arc::new(MemoryRepository::new()) as DynRepository
+        }
+        RepositoryConfig::Sql { database_url } => {
+            Arc::new(SqlRepository::connect(database_url).await?) as DynRepository
+        }
+    };
+
+    Ok(repository)
+}
+
+async fn build_storage(config: &StorageConfig) -> DynCrateStorage {
+    match config {
+        StorageConfig::S3 { bucket } => Arc::new(S3Storage::new(bucket.clone()).await),
+        StorageConfig::Filesystem { path, public_url } => {
+            tracing::info!(
+                path = path.display().to_string(),
+                "storing crates on the local filesystem"
+            );
+            let storage = FilesystemStorage::new(path.clone());
+            match public_url {
+                Some(public_url) => Arc::new(storage.with_public_url(public_url.clone())),
+                None => Arc::new(storage),
+            }
+        }
+    }
 }
 
 #[cfg(feature = "local")]
-async fn run_app(app: Router) {
+async fn run_app(app: Router, addr: std::net::SocketAddr) {
     let cors_layer = tower_http::cors::CorsLayer::new()
         .allow_methods([http::Method::GET, http::Method::POST])
         .allow_headers(tower_http::cors::Any)
         .allow_origin(tower_http::cors::Any);
     let app_with_cors = app.layer(cors_layer);
-    let addr = std::net::SocketAddr::from(([0, 0, 0, 0], 3026));
     tracing::info!("listening on http://{}", addr);
     axum::Server::bind(&addr)
         .serve(app_with_cors.into_make_service())
@@ -36,7 +95,7 @@ async fn run_app(app: Router) {
 }
 
 #[cfg(not(feature = "local"))]
-async fn run_app(app: Router) {
+async fn run_app(app: Router, _addr: std::net::SocketAddr) {
     lambda_web::run_hyper_on_lambda(app)
         .await
         .expect("app to run on Lambda successfully")
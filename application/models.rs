@@ -1,5 +1,9 @@
+pub mod crate_name;
 pub mod crate_summary;
+pub mod downloads;
 pub mod index;
+pub mod limits;
 pub mod metadata;
+pub mod public_key;
 pub mod token;
 pub mod user;
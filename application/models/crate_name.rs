@@ -0,0 +1,133 @@
+//! Cargo's rules for crate names.
+//!
+//! Cargo treats `-` and `_` as the same character and crates.io compares names
+//! case-insensitively, so every crate also has a canonical name that two crates
+//! can't share.
+
+pub const MAX_CRATE_NAME_LENGTH: usize = 64;
+
+/// Names of the sysroot crates and directories cargo uses for itself.
+const RESERVED_NAMES: &[&str] = &[
+    "alloc",
+    "build",
+    "core",
+    "deps",
+    "examples",
+    "incremental",
+    "proc_macro",
+    "std",
+    "test",
+];
+
+/// Names that can't be used as file names on Windows.
+const WINDOWS_RESERVED_NAMES: &[&str] = &[
+    "aux", "com1", "com2", "com3", "com4", "com5", "com6", "com7", "com8", "com9", "con", "lpt1",
+    "lpt2", "lpt3", "lpt4", "lpt5", "lpt6", "lpt7", "lpt8", "lpt9", "nul", "prn",
+];
+
+const KEYWORDS: &[&str] = &[
+    "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "crate",
+    "do", "dyn", "else", "enum", "extern", "false", "final", "fn", "for", "if", "impl", "in",
+    "let", "loop", "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref",
+    "return", "self", "static", "struct", "super", "trait", "true", "try", "type", "typeof",
+    "unsafe", "unsized", "use", "virtual", "where", "while", "yield",
+];
+
+/// The name two crates can't share, `Foo-Bar` and `foo_bar` are both `foo_bar`.
+pub fn canonical_crate_name(name: &str) -> String {
+    name.to_ascii_lowercase().replace('-', "_")
+}
+
+/// Checks the name against cargo's rules, returning why it's not allowed.
+pub fn validate_crate_name(name: &str) -> Result<(), String> {
+    let first = match name.chars().next() {
+        None => return Err("the name can't be empty".to_string()),
+        Some(first) => first,
+    };
+    if name.len() > MAX_CRATE_NAME_LENGTH {
+        return Err(format!(
+            "the name can't be longer than {} characters",
+            MAX_CRATE_NAME_LENGTH
+        ));
+    }
+    if !first.is_ascii_alphabetic() {
+        return Err("the name must start with an ASCII letter".to_string());
+    }
+    if let Some(invalid) = name
+        .chars()
+        .find(|c| !(c.is_ascii_alphanumeric() || *c == '-' || *c == '_'))
+    {
+        return Err(format!(
+            "invalid character {:?}, only ASCII letters, digits, `-` and `_` are allowed",
+            invalid
+        ));
+    }
+
+    let canonical_name = canonical_crate_name(name);
+    if RESERVED_NAMES.contains(&canonical_name.as_str()) {
+        return Err("the name is reserved".to_string());
+    }
+    if WINDOWS_RESERVED_NAMES.contains(&canonical_name.as_str()) {
+        return Err("the name is reserved on Windows".to_string());
+    }
+    if KEYWORDS.contains(&name) {
+        return Err("the name is a Rust keyword".to_string());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_canonical_crate_name() {
+        assert_eq!(canonical_crate_name("Foo-Bar"), "foo_bar");
+        assert_eq!(canonical_crate_name("foo_bar"), "foo_bar");
+    }
+
+    #[test]
+    fn test_valid_names() {
+        for name in ["serde", "serde_json", "tokio-util", "Inflector", "a1"] {
+            assert_eq!(validate_crate_name(name), Ok(()), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_invalid_names() {
+        let cases = [
+            ("", "the name can't be empty"),
+            ("1password", "the name must start with an ASCII letter"),
+            ("_private", "the name must start with an ASCII letter"),
+            (
+                "caf\u{e9}",
+                "invalid character '\u{e9}', only ASCII letters, digits, `-` and `_` are allowed",
+            ),
+            (
+                "foo.bar",
+                "invalid character '.', only ASCII letters, digits, `-` and `_` are allowed",
+            ),
+            ("std", "the name is reserved"),
+            ("proc-macro", "the name is reserved"),
+            ("Core", "the name is reserved"),
+            ("nul", "the name is reserved on Windows"),
+            ("COM1", "the name is reserved on Windows"),
+            ("self", "the name is a Rust keyword"),
+        ];
+        for (name, reason) in cases {
+            assert_eq!(
+                validate_crate_name(name),
+                Err(reason.to_string()),
+                "{}",
+                name
+            );
+        }
+
+        let long_name = "a".repeat(MAX_CRATE_NAME_LENGTH + 1);
+        assert_eq!(
+            validate_crate_name(&long_name),
+            Err("the name can't be longer than 64 characters".to_string())
+        );
+    }
+}
@@ -0,0 +1,76 @@
+//! Download counts, kept per version and per day.
+use std::collections::BTreeMap;
+
+use time::format_description::well_known::Iso8601;
+use time::{Date, OffsetDateTime};
+
+use crate::error::{internal_error, AppResult};
+
+/// The downloads of a crate, or one of its versions, on a single day.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DailyDownloads {
+    pub date: Date,
+    pub downloads: u64,
+}
+
+/// Days are counted in UTC.
+pub fn today() -> Date {
+    OffsetDateTime::now_utc().date()
+}
+
+/// Dates are stored as `YYYY-MM-DD`, which sorts the same way the dates do.
+pub fn format_date(date: Date) -> String {
+    date.to_string()
+}
+
+pub fn parse_date(value: &str) -> AppResult<Date> {
+    Date::parse(value, &Iso8601::DATE).map_err(|_| internal_error())
+}
+
+/// Adds up the counts of each day, oldest day first.
+pub fn per_day(counts: impl IntoIterator<Item = (Date, u64)>) -> Vec<DailyDownloads> {
+    let mut days = BTreeMap::new();
+    for (date, downloads) in counts {
+        *days.entry(date).or_insert(0) += downloads;
+    }
+
+    days.into_iter()
+        .map(|(date, downloads)| DailyDownloads { date, downloads })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::Month;
+
+    #[test]
+    fn test_dates_round_trip() {
+        let date = Date::from_calendar_date(2023, Month::July, 4).unwrap();
+
+        assert_eq!(format_date(date), "2023-07-04");
+        assert_eq!(parse_date("2023-07-04").unwrap(), date);
+    }
+
+    #[test]
+    fn test_counts_are_summed_per_day() {
+        let first = Date::from_calendar_date(2023, Month::July, 4).unwrap();
+        let second = first.next_day().unwrap();
+
+        let days = per_day([(second, 1), (first, 2), (second, 3)]);
+
+        assert_eq!(
+            days,
+            vec![
+                DailyDownloads {
+                    date: first,
+                    downloads: 2
+                },
+                DailyDownloads {
+                    date: second,
+                    downloads: 4
+                },
+            ]
+        );
+    }
+}
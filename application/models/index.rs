@@ -1,13 +1,16 @@
 //! Package information in the format information is supposed to be returned
 //! from the index.
+use hex::ToHex;
 use semver::Version;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
 use url::Url;
 
 use crate::models::metadata::{DependencyKind, Metadata, MetadataDependency};
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Dependency {
     pub name: String,
     pub req: semver::VersionReq,
@@ -22,32 +25,58 @@ pub struct Dependency {
 
 /// The package information returned from the index as described in the Cargo reference:
 /// https://doc.rust-lang.org/cargo/reference/registry-index.html
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct PackageInfo {
     pub name: String,
     pub vers: Version,
     pub deps: Vec<Dependency>,
     pub cksum: String,
     pub features: HashMap<String, Vec<String>>,
+    /// Features using the `dep:` or `?` syntax, which cargo versions before 1.60 can't parse.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub features2: Option<HashMap<String, Vec<String>>>,
     pub yanked: bool,
     pub links: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rust_version: Option<String>,
+    /// The schema version of the entry, only set to 2 when `features2` is used.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub v: Option<u32>,
 }
 
 impl PackageInfo {
     pub fn from_metadata(metadata: Metadata, checksum: &str) -> Self {
         let deps = metadata.deps.into_iter().map(Into::into).collect();
+        let (features, features2): (HashMap<_, _>, HashMap<_, _>) = metadata
+            .features
+            .into_iter()
+            .partition(|(_, values)| !values.iter().any(|value| uses_new_feature_syntax(value)));
+        let (features2, v) = if features2.is_empty() {
+            (None, None)
+        } else {
+            (Some(features2), Some(2))
+        };
+
         Self {
             name: metadata.name,
             vers: metadata.vers,
             deps,
             cksum: checksum.to_string(),
-            features: metadata.features,
+            features,
+            features2,
             yanked: metadata.yanked,
             links: metadata.links,
+            rust_version: metadata.rust_version,
+            v,
         }
     }
 }
 
+/// Namespaced (`dep:serde`) and weak (`serde?/std`) dependency features.
+fn uses_new_feature_syntax(value: &str) -> bool {
+    value.starts_with("dep:") || value.contains("?/")
+}
+
 impl From<MetadataDependency> for Dependency {
     fn from(value: MetadataDependency) -> Self {
         let (name, package) = if let Some(local_new_name) = value.explicit_name_in_toml {
@@ -69,3 +98,131 @@ impl From<MetadataDependency> for Dependency {
         }
     }
 }
+
+/// Orders index entries by when they were published, like crates.io does.
+///
+/// Each entry comes with its publish time in milliseconds since the Unix epoch, entries
+/// stored before publish times were recorded have 0 and go first in semver order.
+pub fn in_publish_order(mut entries: Vec<(u64, PackageInfo)>) -> Vec<PackageInfo> {
+    entries
+        .sort_by(|(a_time, a), (b_time, b)| a_time.cmp(b_time).then_with(|| a.vers.cmp(&b.vers)));
+    entries.into_iter().map(|(_, info)| info).collect()
+}
+
+/// The publish time recorded for new versions.
+pub fn publish_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+/// What clients need to know to tell whether their copy of an index file is current.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct IndexState {
+    /// Derived from the versions, their checksums and yank state, so it only
+    /// changes when the index file does.
+    pub etag: String,
+    /// Seconds since the Unix epoch.
+    pub last_modified: u64,
+}
+
+impl IndexState {
+    /// The state of an index file that has just been written.
+    pub fn modified_now(infos: &[PackageInfo]) -> Self {
+        let mut hasher = Sha256::new();
+        for info in infos {
+            hasher.update(format!("{} {} {}\n", info.vers, info.cksum, info.yanked));
+        }
+        let last_modified = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or_default();
+
+        Self {
+            etag: hasher.finalize().encode_hex(),
+            last_modified,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn build_metadata(features: serde_json::Value) -> Metadata {
+        serde_json::from_value(json!({
+            "name": "testcrate",
+            "vers": "0.1.0",
+            "deps": [],
+            "features": features,
+            "authors": [],
+            "description": null,
+            "documentation": null,
+            "homepage": null,
+            "readme": null,
+            "readme_file": null,
+            "keywords": [],
+            "categories": [],
+            "license": null,
+            "license_file": null,
+            "repository": null,
+            "badges": {},
+            "links": null,
+            "rust_version": "1.60",
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_new_feature_syntax_goes_to_features2() {
+        let metadata = build_metadata(json!({
+            "default": ["std"],
+            "std": ["serde?/std"],
+            "derive": ["dep:serde_derive"],
+        }));
+
+        let info = PackageInfo::from_metadata(metadata, "cksum");
+
+        let entry = serde_json::to_value(&info).unwrap();
+        assert_eq!(entry["features"], json!({ "default": ["std"] }));
+        assert_eq!(
+            entry["features2"],
+            json!({ "std": ["serde?/std"], "derive": ["dep:serde_derive"] })
+        );
+        assert_eq!(entry["v"], json!(2));
+        assert_eq!(entry["rust_version"], json!("1.60"));
+    }
+
+    #[test]
+    fn test_old_feature_syntax_stays_v1() {
+        let metadata = build_metadata(json!({ "default": ["std"], "std": [] }));
+
+        let info = PackageInfo::from_metadata(metadata, "cksum");
+
+        let entry = serde_json::to_value(&info).unwrap();
+        assert_eq!(entry["features"], json!({ "default": ["std"], "std": [] }));
+        assert!(entry.get("features2").is_none());
+        assert!(entry.get("v").is_none());
+    }
+
+    #[test]
+    fn test_entries_stored_before_v2_are_readable() {
+        let stored = json!({
+            "name": "testcrate",
+            "vers": "0.1.0",
+            "deps": [],
+            "cksum": "cksum",
+            "features": {},
+            "yanked": false,
+            "links": null,
+        });
+
+        let info: PackageInfo = serde_json::from_value(stored.clone()).unwrap();
+
+        assert_eq!(info.features2, None);
+        assert_eq!(info.rust_version, None);
+        assert_eq!(serde_json::to_value(&info).unwrap(), stored);
+    }
+}
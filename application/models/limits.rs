@@ -0,0 +1,36 @@
+use serde::{Deserialize, Serialize};
+
+use crate::config::{default_upload_size, PublishLimits};
+
+/// Publish limits set by an admin for a single crate.
+///
+/// Anything left unset falls back to the registry-wide limit.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct CrateLimits {
+    pub max_crate_size: Option<u64>,
+    pub max_dependencies: Option<usize>,
+    pub max_features: Option<usize>,
+    pub max_readme_length: Option<usize>,
+}
+
+impl CrateLimits {
+    /// The limits that apply when publishing this crate.
+    ///
+    /// A raised crate size raises the upload size along with it, so the crate file fits
+    /// next to the metadata.
+    pub fn apply_to(&self, limits: &PublishLimits) -> PublishLimits {
+        let max_upload_size = self.max_crate_size.map_or(limits.max_upload_size, |size| {
+            let upload_size = default_upload_size(size, limits.max_metadata_size);
+            limits.max_upload_size.max(upload_size)
+        });
+
+        PublishLimits {
+            max_upload_size,
+            max_crate_size: self.max_crate_size.unwrap_or(limits.max_crate_size),
+            max_dependencies: self.max_dependencies.unwrap_or(limits.max_dependencies),
+            max_features: self.max_features.unwrap_or(limits.max_features),
+            max_readme_length: self.max_readme_length.unwrap_or(limits.max_readme_length),
+            ..limits.clone()
+        }
+    }
+}
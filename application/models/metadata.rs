@@ -45,6 +45,9 @@ pub struct Metadata {
     pub repository: Option<Url>,
     pub badges: HashMap<String, HashMap<String, String>>,
     pub links: Option<String>,
+    /// The minimum supported Rust version, e.g. `1.60`.
+    #[serde(default)]
+    pub rust_version: Option<String>,
     #[serde(default)]
     pub yanked: bool,
 }
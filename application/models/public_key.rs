@@ -0,0 +1,15 @@
+//! Public keys users sign registry requests with instead of sending a stored token.
+use time::OffsetDateTime;
+
+use crate::models::user::UserId;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PublicKey {
+    /// The PASERK ID of the key (`k3.pid.…`), which signed tokens name in their footer.
+    pub key_id: String,
+    /// The key itself as a PASERK (`k3.public.…`).
+    pub public_key: String,
+    pub name: String,
+    pub user_id: UserId,
+    pub created_at: OffsetDateTime,
+}
@@ -1,6 +1,272 @@
+//! Registry API tokens and the scopes that restrict what they can be used for.
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use time::format_description::well_known::Rfc3339;
+use time::{Duration, OffsetDateTime};
+
+use crate::error::{internal_error, AppError, AppResult};
+use crate::models::crate_name::canonical_crate_name;
+
+/// A token's last use is only recorded again once it's this old, so using a token doesn't
+/// cost a write on every request.
+pub const LAST_USED_INTERVAL: Duration = Duration::hours(1);
+
 #[derive(Clone, Debug)]
 pub struct Token {
     pub name: String,
     pub user_id: u32,
     pub token_id: String,
+    pub scopes: TokenScopes,
+    /// Missing on tokens created before it was recorded.
+    pub created_at: Option<OffsetDateTime>,
+    pub expires_at: Option<OffsetDateTime>,
+    pub last_used_at: Option<OffsetDateTime>,
+}
+
+impl Token {
+    pub fn is_expired(&self, now: OffsetDateTime) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+
+    pub fn needs_use_recorded(&self, now: OffsetDateTime) -> bool {
+        self.last_used_at
+            .is_none_or(|last_used_at| now - last_used_at >= LAST_USED_INTERVAL)
+    }
+}
+
+/// Token timestamps are stored as Unix timestamps, so they're kept to the second.
+pub fn now() -> OffsetDateTime {
+    let now = OffsetDateTime::now_utc();
+    now.replace_nanosecond(0).unwrap_or(now)
+}
+
+pub fn from_unix_timestamp(timestamp: i64) -> AppResult<OffsetDateTime> {
+    OffsetDateTime::from_unix_timestamp(timestamp).map_err(|_| internal_error())
+}
+
+pub fn format_timestamp(timestamp: OffsetDateTime) -> String {
+    timestamp
+        .format(&Rfc3339)
+        .expect("Unix timestamps to be valid RFC 3339 timestamps")
+}
+
+pub fn parse_timestamp(value: &str) -> Result<OffsetDateTime, String> {
+    OffsetDateTime::parse(value, &Rfc3339)
+        .map_err(|err| format!("invalid timestamp {:?}: {}", value, err))
+}
+
+/// The endpoints that change the registry, reading it is allowed with any token.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum EndpointScope {
+    PublishNew,
+    PublishUpdate,
+    Yank,
+    ChangeOwners,
+}
+
+impl fmt::Display for EndpointScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            EndpointScope::PublishNew => "publish-new",
+            EndpointScope::PublishUpdate => "publish-update",
+            EndpointScope::Yank => "yank",
+            EndpointScope::ChangeOwners => "change-owners",
+        };
+        f.write_str(name)
+    }
+}
+
+/// A crate name, or a name prefix when it ends with `*`, like crates.io's crate scopes.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct CratePattern(String);
+
+impl CratePattern {
+    /// Names are compared by their canonical names, so `foo-*` also matches `Foo_bar`.
+    pub fn matches(&self, crate_name: &str) -> bool {
+        let crate_name = canonical_crate_name(crate_name);
+        match self.0.strip_suffix('*') {
+            Some(prefix) => crate_name.starts_with(&canonical_crate_name(prefix)),
+            None => crate_name == canonical_crate_name(&self.0),
+        }
+    }
+}
+
+impl TryFrom<String> for CratePattern {
+    type Error = String;
+
+    fn try_from(pattern: String) -> Result<Self, Self::Error> {
+        if pattern.is_empty() {
+            return Err("a crate pattern can't be empty".to_string());
+        }
+        let name = pattern.strip_suffix('*').unwrap_or(&pattern);
+        if let Some(invalid) = name
+            .chars()
+            .find(|c| !(c.is_ascii_alphanumeric() || *c == '-' || *c == '_'))
+        {
+            return Err(format!(
+                "invalid character {:?} in crate pattern {:?}, only a trailing `*` is allowed",
+                invalid, pattern
+            ));
+        }
+
+        Ok(Self(pattern))
+    }
+}
+
+impl From<CratePattern> for String {
+    fn from(pattern: CratePattern) -> Self {
+        pattern.0
+    }
+}
+
+/// What a token can be used for, a missing list doesn't restrict the token so tokens
+/// created before scopes existed keep working.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenScopes {
+    /// The endpoints the token can change the registry with, none makes it read-only.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub endpoints: Option<Vec<EndpointScope>>,
+    /// The crates the token can change.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub crates: Option<Vec<CratePattern>>,
+}
+
+impl TokenScopes {
+    pub fn allows(&self, endpoint: EndpointScope, crate_name: &str) -> bool {
+        let allows_endpoint = self
+            .endpoints
+            .as_ref()
+            .is_none_or(|endpoints| endpoints.contains(&endpoint));
+        let allows_crate = self
+            .crates
+            .as_ref()
+            .is_none_or(|patterns| patterns.iter().any(|p| p.matches(crate_name)));
+
+        allows_endpoint && allows_crate
+    }
+
+    pub fn check(&self, endpoint: EndpointScope, crate_name: &str) -> AppResult<()> {
+        if self.allows(endpoint, crate_name) {
+            Ok(())
+        } else {
+            Err(AppError::MissingTokenScope {
+                scope: endpoint,
+                crate_name: crate_name.to_string(),
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pattern(pattern: &str) -> CratePattern {
+        CratePattern::try_from(pattern.to_string()).unwrap()
+    }
+
+    fn token(expires_at: Option<OffsetDateTime>, last_used_at: Option<OffsetDateTime>) -> Token {
+        Token {
+            name: "ci".to_string(),
+            user_id: 1,
+            token_id: "id".to_string(),
+            scopes: TokenScopes::default(),
+            created_at: None,
+            expires_at,
+            last_used_at,
+        }
+    }
+
+    #[test]
+    fn test_token_expiry() {
+        let now = now();
+
+        assert!(!token(None, None).is_expired(now));
+        assert!(!token(Some(now + Duration::seconds(1)), None).is_expired(now));
+        assert!(token(Some(now), None).is_expired(now));
+    }
+
+    #[test]
+    fn test_token_use_is_recorded_at_most_once_per_interval() {
+        let now = now();
+
+        assert!(token(None, None).needs_use_recorded(now));
+        assert!(!token(None, Some(now - Duration::minutes(5))).needs_use_recorded(now));
+        assert!(token(None, Some(now - LAST_USED_INTERVAL)).needs_use_recorded(now));
+    }
+
+    #[test]
+    fn test_timestamps_round_trip() {
+        let timestamp = from_unix_timestamp(1_688_169_600).unwrap();
+
+        assert_eq!(format_timestamp(timestamp), "2023-07-01T00:00:00Z");
+        assert_eq!(parse_timestamp("2023-07-01T00:00:00Z").unwrap(), timestamp);
+        assert!(parse_timestamp("2023-07-01").is_err());
+    }
+
+    #[test]
+    fn test_crate_patterns() {
+        assert!(pattern("serde").matches("serde"));
+        assert!(!pattern("serde").matches("serde_json"));
+        assert!(pattern("serde*").matches("serde_json"));
+        assert!(pattern("foo-*").matches("Foo_bar"));
+        assert!(pattern("*").matches("anything"));
+        assert!(!pattern("foo-*").matches("foobar"));
+
+        assert!(CratePattern::try_from("".to_string()).is_err());
+        assert!(CratePattern::try_from("foo*bar".to_string()).is_err());
+        assert!(CratePattern::try_from("foo**".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_unrestricted_scopes_allow_everything() {
+        let scopes = TokenScopes::default();
+
+        assert!(scopes.allows(EndpointScope::PublishNew, "foo"));
+        assert!(scopes.allows(EndpointScope::ChangeOwners, "bar"));
+    }
+
+    #[test]
+    fn test_scopes_restrict_endpoints_and_crates() {
+        let scopes = TokenScopes {
+            endpoints: Some(vec![EndpointScope::PublishUpdate]),
+            crates: Some(vec![pattern("raktar-*")]),
+        };
+
+        assert!(scopes
+            .check(EndpointScope::PublishUpdate, "raktar-cli")
+            .is_ok());
+        assert!(scopes
+            .check(EndpointScope::PublishNew, "raktar-cli")
+            .is_err());
+        assert!(scopes.check(EndpointScope::PublishUpdate, "serde").is_err());
+
+        let read_only = TokenScopes {
+            endpoints: Some(vec![]),
+            crates: None,
+        };
+        assert!(!read_only.allows(EndpointScope::Yank, "foo"));
+    }
+
+    #[test]
+    fn test_scopes_serialization() {
+        let scopes = TokenScopes {
+            endpoints: Some(vec![EndpointScope::PublishNew, EndpointScope::ChangeOwners]),
+            crates: Some(vec![pattern("foo*")]),
+        };
+
+        let json = serde_json::to_string(&scopes).unwrap();
+        assert_eq!(
+            json,
+            r#"{"endpoints":["publish-new","change-owners"],"crates":["foo*"]}"#
+        );
+        assert_eq!(serde_json::from_str::<TokenScopes>(&json).unwrap(), scopes);
+        assert_eq!(
+            serde_json::from_str::<TokenScopes>("{}").unwrap(),
+            TokenScopes::default()
+        );
+    }
 }
@@ -1,6 +1,7 @@
 use anyhow::anyhow;
 use aws_sdk_dynamodb::Client;
 use lambda_runtime::{service_fn, Error, LambdaEvent};
+use raktar::config::RepositoryConfig;
 use raktar::models::user::CognitoUserData;
 use serde::{Deserialize, Serialize};
 use serde_json::{to_value, Value};
@@ -24,15 +25,15 @@ async fn func(event: LambdaEvent<Value>) -> Result<Value, Error> {
     let (mut event, _context) = event.into_parts();
 
     let repository = DYNAMODB_REPOSITORY
-        .get_or_init(get_dynamodb_repository)
-        .await;
+        .get_or_try_init(get_dynamodb_repository)
+        .await?;
 
     info!("pre-token triggered: {}", event);
     match serde_json::from_value::<TriggerEvent>(event.clone()) {
         Ok(trigger_event) => {
             let user_attributes = trigger_event.request.user_attributes;
             match serde_json::from_str::<Vec<Identity>>(&user_attributes.identities) {
-                Ok(identities) => match identities.get(0) {
+                Ok(identities) => match identities.first() {
                     Some(identity) => {
                         let user = CognitoUserData {
                             login: identity.user_id.clone(),
@@ -82,10 +83,23 @@ async fn func(event: LambdaEvent<Value>) -> Result<Value, Error> {
 
 static DYNAMODB_REPOSITORY: OnceCell<DynamoDBRepository> = OnceCell::const_new();
 
-async fn get_dynamodb_repository() -> DynamoDBRepository {
+async fn get_dynamodb_repository() -> Result<DynamoDBRepository, Error> {
+    let table_name = match RepositoryConfig::load() {
+        Ok(RepositoryConfig::DynamoDB { table_name }) => table_name,
+        Ok(_) => {
+            error!("users are only stored in DynamoDB");
+            return Err(anyhow!("the repository has to be DynamoDB").into());
+        }
+        Err(err) => {
+            let error_message = err.to_string();
+            error!(error_message, "invalid configuration");
+            return Err(err.into());
+        }
+    };
     let aws_config = aws_config::from_env().load().await;
     let db_client = Client::new(&aws_config);
-    DynamoDBRepository::new_from_env(db_client)
+
+    Ok(DynamoDBRepository::new(db_client, table_name))
 }
 
 #[derive(Clone, Debug, Serialize)]
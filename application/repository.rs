@@ -1,5 +1,12 @@
 mod base;
 pub mod dynamodb;
+pub mod memory;
+pub mod sql;
 
-pub use base::{DynRepository, Repository, UserRepository};
+pub use base::{
+    CrateRepository, DynRepository, PublicKeyRepository, Repository, TokenRepository,
+    UserRepository,
+};
 pub use dynamodb::DynamoDBRepository;
+pub use memory::MemoryRepository;
+pub use sql::SqlRepository;
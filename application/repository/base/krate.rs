@@ -1,14 +1,19 @@
 use crate::auth::AuthenticatedUser;
 use crate::error::AppResult;
 use crate::models::crate_summary::CrateSummary;
-use crate::models::index::PackageInfo;
+use crate::models::downloads::DailyDownloads;
+use crate::models::index::{IndexState, PackageInfo};
+use crate::models::limits::CrateLimits;
 use crate::models::metadata::Metadata;
 use crate::models::user::User;
 use semver::Version;
+use time::Date;
 
 #[async_trait::async_trait]
 pub trait CrateRepository {
     async fn get_package_info(&self, crate_name: &str) -> AppResult<String>;
+    /// Kept up to date on every change to the index file, so it can be checked without reading it.
+    async fn get_index_state(&self, crate_name: &str) -> AppResult<Option<IndexState>>;
     async fn store_package_info(
         &self,
         crate_name: &str,
@@ -21,6 +26,8 @@ pub trait CrateRepository {
     async fn list_owners(&self, crate_name: &str) -> AppResult<Vec<User>>;
     async fn add_owners(&self, crate_name: &str, user_ids: Vec<String>) -> AppResult<()>;
     async fn get_crate_summary(&self, crate_name: &str) -> AppResult<Option<CrateSummary>>;
+    /// The name a crate was published with, looked up by any spelling of its canonical name.
+    async fn resolve_crate_name(&self, crate_name: &str) -> AppResult<Option<String>>;
     async fn get_all_crate_details(
         &self,
         filter: Option<String>,
@@ -32,4 +39,23 @@ pub trait CrateRepository {
         version: &Version,
     ) -> AppResult<Option<Metadata>>;
     async fn list_crate_versions(&self, crate_name: &str) -> AppResult<Vec<Version>>;
+    /// The index entries of every version of the crate, in publish order.
+    async fn list_package_infos(&self, crate_name: &str) -> AppResult<Vec<PackageInfo>>;
+    /// The limits set for this crate, the crate doesn't have to exist yet.
+    async fn get_crate_limits(&self, crate_name: &str) -> AppResult<CrateLimits>;
+    async fn set_crate_limits(&self, crate_name: &str, limits: CrateLimits) -> AppResult<()>;
+    /// Counts a single download of the version on the given day.
+    async fn record_download(
+        &self,
+        crate_name: &str,
+        version: &Version,
+        date: Date,
+    ) -> AppResult<()>;
+    /// The downloads of the version on each day it was downloaded, oldest day first,
+    /// or of every version of the crate combined without one.
+    async fn get_download_counts(
+        &self,
+        crate_name: &str,
+        version: Option<&Version>,
+    ) -> AppResult<Vec<DailyDownloads>>;
 }
@@ -0,0 +1,23 @@
+use time::OffsetDateTime;
+
+use crate::error::AppResult;
+use crate::models::public_key::PublicKey;
+
+#[async_trait::async_trait]
+pub trait PublicKeyRepository {
+    /// Fails with `DuplicatePublicKey` if any user already registered the key.
+    async fn store_public_key(
+        &self,
+        key_id: String,
+        public_key: String,
+        name: String,
+        user_id: u32,
+    ) -> AppResult<PublicKey>;
+    async fn delete_public_key(&self, user_id: u32, key_id: String) -> AppResult<()>;
+    async fn list_public_keys(&self, user_id: u32) -> AppResult<Vec<PublicKey>>;
+    async fn get_public_key(&self, key_id: &str) -> AppResult<Option<PublicKey>>;
+    /// Records the use of a value that can only be used once, returning whether this is its
+    /// first use. The value is forgotten after `expires_at`, when whatever it came from is
+    /// rejected anyway.
+    async fn use_nonce(&self, nonce: &str, expires_at: OffsetDateTime) -> AppResult<bool>;
+}
@@ -1,10 +1,21 @@
+use time::OffsetDateTime;
+
 use crate::error::AppResult;
-use crate::models::token::Token;
+use crate::models::token::{Token, TokenScopes};
 
 #[async_trait::async_trait]
 pub trait TokenRepository {
-    async fn store_auth_token(&self, token: &[u8], name: String, user_id: u32) -> AppResult<Token>;
+    async fn store_auth_token(
+        &self,
+        token: &[u8],
+        name: String,
+        user_id: u32,
+        scopes: TokenScopes,
+        expires_at: Option<OffsetDateTime>,
+    ) -> AppResult<Token>;
     async fn delete_auth_token(&self, user_id: u32, token_id: String) -> AppResult<()>;
     async fn list_auth_tokens(&self, user_id: u32) -> AppResult<Vec<Token>>;
     async fn get_auth_token(&self, token: &[u8]) -> AppResult<Option<Token>>;
+    /// Does nothing if the token no longer exists.
+    async fn record_token_use(&self, token: &[u8], used_at: OffsetDateTime) -> AppResult<()>;
 }
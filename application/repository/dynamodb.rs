@@ -1,15 +1,29 @@
 mod krate;
+mod public_key;
 mod token;
 pub mod user;
 
+use aws_sdk_dynamodb::operation::query::builders::QueryFluentBuilder;
+use aws_sdk_dynamodb::types::AttributeValue;
 use aws_sdk_dynamodb::Client;
+use std::collections::HashMap;
+use tracing::info;
 
+use crate::error::AppResult;
 use crate::repository::Repository;
 
+/// Records which one-off data migrations have run on the table.
+static MIGRATIONS_PARTITION_KEY: &str = "MIGRATIONS";
+static CRATE_NAMES_MIGRATION: &str = "crate_names";
+
+/// How many items a single query page holds at most.
+const DEFAULT_PAGE_SIZE: i32 = 100;
+
 #[derive(Clone)]
 pub struct DynamoDBRepository {
     db_client: Client,
     table_name: String,
+    page_size: i32,
 }
 
 impl DynamoDBRepository {
@@ -17,16 +31,93 @@ impl DynamoDBRepository {
         Self {
             db_client,
             table_name,
+            page_size: DEFAULT_PAGE_SIZE,
         }
     }
 
-    pub fn new_from_env(db_client: Client) -> Self {
-        Self::new(
-            db_client,
-            std::env::var("TABLE_NAME").expect("TABLE_NAME to be set in environment"),
-        )
+    /// Sets how many items are requested per query page.
+    pub fn with_page_size(mut self, page_size: i32) -> Self {
+        self.page_size = page_size;
+        self
+    }
+
+    /// Brings items written by earlier versions of the registry up to date, the way
+    /// `SqlRepository::connect` runs the SQL migrations. Each migration only runs once.
+    pub async fn migrate(&self) -> AppResult<()> {
+        if !self.has_migrated(CRATE_NAMES_MIGRATION).await? {
+            let claimed =
+                krate::backfill_crate_names(&self.db_client, &self.table_name, self.page_size)
+                    .await?;
+            info!(claimed, "backfilled canonical crate names");
+            self.mark_migrated(CRATE_NAMES_MIGRATION).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn has_migrated(&self, migration: &str) -> AppResult<bool> {
+        let output = self
+            .db_client
+            .get_item()
+            .table_name(&self.table_name)
+            .key(
+                "pk",
+                AttributeValue::S(MIGRATIONS_PARTITION_KEY.to_string()),
+            )
+            .key("sk", AttributeValue::S(migration.to_string()))
+            .send()
+            .await?;
+
+        Ok(output.item().is_some())
+    }
+
+    async fn mark_migrated(&self, migration: &str) -> AppResult<()> {
+        self.db_client
+            .put_item()
+            .table_name(&self.table_name)
+            .item(
+                "pk",
+                AttributeValue::S(MIGRATIONS_PARTITION_KEY.to_string()),
+            )
+            .item("sk", AttributeValue::S(migration.to_string()))
+            .send()
+            .await?;
+
+        Ok(())
     }
 }
 
 #[async_trait::async_trait]
 impl Repository for DynamoDBRepository {}
+
+/// Runs the query page by page until it's exhausted, or until `max_items` have been read.
+///
+/// DynamoDB stops a page at `page_size` items or 1 MB of data, whichever comes first,
+/// so a single page is never guaranteed to hold every matching item.
+async fn query_all(
+    query: QueryFluentBuilder,
+    page_size: i32,
+    max_items: Option<usize>,
+) -> AppResult<Vec<HashMap<String, AttributeValue>>> {
+    let mut items = vec![];
+    let mut start_key = None;
+    loop {
+        let remaining = max_items.map_or(usize::MAX, |max_items| max_items - items.len());
+        if remaining == 0 {
+            return Ok(items);
+        }
+
+        let output = query
+            .clone()
+            .limit(page_size.min(remaining.try_into().unwrap_or(i32::MAX)))
+            .set_exclusive_start_key(start_key)
+            .send()
+            .await?;
+
+        items.extend(output.items().unwrap_or_default().iter().cloned());
+        start_key = output.last_evaluated_key().cloned();
+        if start_key.is_none() {
+            return Ok(items);
+        }
+    }
+}
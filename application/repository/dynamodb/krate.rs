@@ -2,50 +2,63 @@ use anyhow::anyhow;
 use aws_sdk_dynamodb::operation::put_item::PutItemError;
 use aws_sdk_dynamodb::operation::transact_write_items::TransactWriteItemsError;
 use aws_sdk_dynamodb::operation::update_item::UpdateItemError;
-use aws_sdk_dynamodb::types::{AttributeValue, Put, ReturnValue, TransactWriteItem};
+use aws_sdk_dynamodb::types::{AttributeValue, Put, TransactWriteItem, Update};
 use aws_sdk_dynamodb::Client;
 use semver::Version;
 use serde::Deserialize;
 use serde_dynamo::aws_sdk_dynamodb_0_27::from_items;
 use serde_dynamo::{from_item, to_item};
 use std::collections::HashMap;
-use tracing::{error, info};
+use std::future::Future;
+use time::Date;
+use tracing::{error, info, warn};
 
 use crate::auth::AuthenticatedUser;
 use crate::error::{AppError, AppResult};
+use crate::models::crate_name::canonical_crate_name;
 use crate::models::crate_summary::CrateSummary;
-use crate::models::index::PackageInfo;
+use crate::models::downloads::{format_date, parse_date, per_day, DailyDownloads};
+use crate::models::index::{in_publish_order, publish_time, IndexState, PackageInfo};
+use crate::models::limits::CrateLimits;
 use crate::models::metadata::Metadata;
-use crate::models::user::User;
+use crate::models::user::{User, UserId};
 use crate::repository::base::CrateRepository;
+use crate::repository::dynamodb::query_all;
 use crate::repository::DynamoDBRepository;
 
 pub static CRATES_PARTITION_KEY: &str = "CRATES";
+/// Maps canonical names to crate names, so similar names can't be published.
+/// Crates published before these were introduced get theirs from `backfill_crate_names`.
+static CRATE_NAMES_PARTITION_KEY: &str = "CRATE_NAMES";
+static CRATE_LIMITS_SORT_KEY: &str = "LIMITS";
+static INDEX_STATE_SORT_KEY: &str = "INDEX";
+/// How often a write to a crate is attempted when concurrent changes to it cancel it.
+const MAX_WRITE_ATTEMPTS: usize = 3;
 
 #[async_trait::async_trait]
 impl CrateRepository for DynamoDBRepository {
     async fn get_package_info(&self, crate_name: &str) -> AppResult<String> {
-        let result = self
-            .db_client
-            .query()
-            .table_name(&self.table_name)
-            .key_condition_expression("pk = :pk and begins_with(sk, :prefix)")
-            .expression_attribute_values(":pk", get_package_key(crate_name))
-            .expression_attribute_values(":prefix", AttributeValue::S("V#".to_string()))
-            .send()
-            .await?;
-
-        match result.items() {
-            None => Err(AppError::NonExistentPackageInfo(crate_name.to_string())),
-            Some(items) => {
-                let infos = from_items::<PackageInfo>(items.to_vec())?;
-                let info_strings: Vec<String> = infos
-                    .into_iter()
-                    .map(|info| serde_json::to_string(&info))
-                    .collect::<Result<Vec<_>, serde_json::Error>>()?;
-                Ok(info_strings.join("\n"))
-            }
+        let infos = get_package_infos(
+            &self.db_client,
+            &self.table_name,
+            self.page_size,
+            crate_name,
+        )
+        .await?;
+        // a crate without any versions doesn't have an index file either
+        if infos.is_empty() {
+            return Err(AppError::NonExistentPackageInfo(crate_name.to_string()));
         }
+
+        let info_strings: Vec<String> = infos
+            .into_iter()
+            .map(|info| serde_json::to_string(&info))
+            .collect::<Result<Vec<_>, serde_json::Error>>()?;
+        Ok(info_strings.join("\n"))
+    }
+
+    async fn get_index_state(&self, crate_name: &str) -> AppResult<Option<IndexState>> {
+        get_index_state(&self.db_client, &self.table_name, crate_name).await
     }
 
     async fn store_package_info(
@@ -56,99 +69,20 @@ impl CrateRepository for DynamoDBRepository {
         metadata: Metadata,
         authenticated_user: &AuthenticatedUser,
     ) -> AppResult<()> {
-        match get_crate_details(&self.db_client, &self.table_name, crate_name).await? {
-            // this is a brand new crate
-            None => {
-                let crate_details = CrateSummary {
-                    name: crate_name.to_string(),
-                    owners: vec![authenticated_user.id],
-                    max_version: package_info.vers.clone(),
-                    description: metadata.description.clone().unwrap_or("".to_string()),
-                };
-                put_package_version_with_new_details(
-                    &self.db_client,
-                    &self.table_name,
-                    crate_name,
-                    version,
-                    package_info,
-                    crate_details,
-                    true,
-                )
-                .await?;
-            }
-            // this is an update to an existing crate
-            Some(old_crate_details) => {
-                if !old_crate_details.owners.contains(&authenticated_user.id) {
-                    return Err(AppError::Unauthorized(
-                        "user is not an owner of this package".to_string(),
-                    ));
-                }
-
-                // should we update the head state of the crate?
-                // the head state represents the latest version, so while it's valid to
-                // publish a non-head version, this should not affect the crate details
-                if old_crate_details.max_version < package_info.vers {
-                    let crate_details = CrateSummary {
-                        name: crate_name.to_string(),
-                        owners: old_crate_details.owners,
-                        max_version: package_info.vers.clone(),
-                        description: metadata.description.clone().unwrap_or("".to_string()),
-                    };
-                    put_package_version_with_new_details(
-                        &self.db_client,
-                        &self.table_name,
-                        crate_name,
-                        version,
-                        package_info,
-                        crate_details,
-                        false,
-                    )
-                    .await?;
-                } else {
-                    put_package_version(
-                        &self.db_client,
-                        &self.table_name,
-                        crate_name,
-                        version,
-                        package_info,
-                    )
-                    .await?;
-                }
-            }
-        }
-
-        put_package_metadata(&self.db_client, &self.table_name, metadata).await
+        retry_concurrent_writes(|| {
+            self.try_store_package_info(
+                crate_name,
+                version,
+                package_info.clone(),
+                metadata.clone(),
+                authenticated_user,
+            )
+        })
+        .await
     }
 
     async fn set_yanked(&self, crate_name: &str, version: &Version, yanked: bool) -> AppResult<()> {
-        let pk = get_package_key(crate_name);
-        let sk = get_package_version_key(version);
-
-        self.db_client
-            .update_item()
-            .table_name(&self.table_name)
-            .key("pk", pk)
-            .key("sk", sk)
-            .update_expression("SET yanked = :y")
-            .condition_expression("attribute_exists(sk)")
-            .expression_attribute_values(":y", AttributeValue::Bool(yanked))
-            .send()
-            .await
-            .map_err(|err| match err.into_service_error() {
-                UpdateItemError::ConditionalCheckFailedException(_) => {
-                    AppError::NonExistentCrateVersion {
-                        crate_name: crate_name.to_string(),
-                        version: version.clone(),
-                    }
-                }
-                service_error => {
-                    let error_message = service_error.to_string();
-                    error!(error_message, "failed to yank package");
-                    anyhow!("internal server error").into()
-                }
-            })?;
-
-        Ok(())
+        retry_concurrent_writes(|| self.try_set_yanked(crate_name, version, yanked)).await
     }
 
     async fn list_owners(&self, crate_name: &str) -> AppResult<Vec<User>> {
@@ -172,16 +106,32 @@ impl CrateRepository for DynamoDBRepository {
     }
 
     async fn add_owners(&self, crate_name: &str, user_ids: Vec<String>) -> AppResult<()> {
+        // owners are stored as a number set, so make sure these are all valid IDs
+        let new_owners = user_ids
+            .iter()
+            .map(|id| id.parse::<UserId>().map(|id| id.to_string()))
+            .collect::<Result<Vec<_>, _>>()?;
+
         self.db_client
             .update_item()
             .table_name(&self.table_name)
             .set_key(get_crate_info_key(crate_name.to_string()))
-            .update_expression("ADD #owners = :new_owners")
+            .update_expression("ADD #owners :new_owners")
+            .condition_expression("attribute_exists(sk)")
             .expression_attribute_names("#owners", "owners".to_string())
-            .expression_attribute_values(":new_owners", AttributeValue::Ss(user_ids))
-            .return_values(ReturnValue::UpdatedOld)
+            .expression_attribute_values(":new_owners", AttributeValue::Ns(new_owners))
             .send()
-            .await?;
+            .await
+            .map_err(|err| match err.into_service_error() {
+                UpdateItemError::ConditionalCheckFailedException(_) => {
+                    AppError::NonExistentPackageInfo(crate_name.to_string())
+                }
+                service_error => {
+                    let error_message = service_error.to_string();
+                    error!(error_message, "failed to add owners");
+                    anyhow!("internal server error").into()
+                }
+            })?;
 
         Ok(())
     }
@@ -205,16 +155,17 @@ impl CrateRepository for DynamoDBRepository {
         Ok(crate_summary)
     }
 
+    async fn resolve_crate_name(&self, crate_name: &str) -> AppResult<Option<String>> {
+        let canonical_name = canonical_crate_name(crate_name);
+        get_crate_name(&self.db_client, &self.table_name, &canonical_name).await
+    }
+
     async fn get_all_crate_details(
         &self,
         filter: Option<String>,
         limit: usize,
     ) -> AppResult<Vec<CrateSummary>> {
-        let query_builder = self
-            .db_client
-            .query()
-            .table_name(&self.table_name)
-            .limit(limit as i32);
+        let query_builder = self.db_client.query().table_name(&self.table_name);
 
         let query_builder = if let Some(prefix) = filter {
             query_builder
@@ -233,9 +184,8 @@ impl CrateRepository for DynamoDBRepository {
                 )
         };
 
-        let output = query_builder.send().await?;
-        let items = output.items().unwrap_or(&[]);
-        let crates = from_items::<CrateSummary>(items.to_vec())?;
+        let items = query_all(query_builder, self.page_size, Some(limit)).await?;
+        let crates = from_items::<CrateSummary>(items)?;
 
         Ok(crates)
     }
@@ -264,140 +214,516 @@ impl CrateRepository for DynamoDBRepository {
     }
 
     async fn list_crate_versions(&self, crate_name: &str) -> AppResult<Vec<Version>> {
-        #[derive(Debug, Deserialize)]
-        struct QueryItem {
-            vers: Version,
-        }
+        let infos = get_package_infos(
+            &self.db_client,
+            &self.table_name,
+            self.page_size,
+            crate_name,
+        )
+        .await?;
+        Ok(infos.into_iter().map(|info| info.vers).collect())
+    }
+
+    async fn list_package_infos(&self, crate_name: &str) -> AppResult<Vec<PackageInfo>> {
+        get_package_infos(
+            &self.db_client,
+            &self.table_name,
+            self.page_size,
+            crate_name,
+        )
+        .await
+    }
+
+    async fn get_crate_limits(&self, crate_name: &str) -> AppResult<CrateLimits> {
+        let result = self
+            .db_client
+            .get_item()
+            .table_name(&self.table_name)
+            .key("pk", get_package_key(crate_name))
+            .key("sk", AttributeValue::S(CRATE_LIMITS_SORT_KEY.to_string()))
+            .send()
+            .await?;
+
+        let limits = if let Some(item) = result.item().cloned() {
+            from_item(item)?
+        } else {
+            CrateLimits::default()
+        };
+
+        Ok(limits)
+    }
+
+    async fn set_crate_limits(&self, crate_name: &str, limits: CrateLimits) -> AppResult<()> {
+        let item = to_item(limits)?;
+        self.db_client
+            .put_item()
+            .table_name(&self.table_name)
+            .set_item(Some(item))
+            .item("pk", get_package_key(crate_name))
+            .item("sk", AttributeValue::S(CRATE_LIMITS_SORT_KEY.to_string()))
+            .send()
+            .await?;
+
+        Ok(())
+    }
+
+    async fn record_download(
+        &self,
+        crate_name: &str,
+        version: &Version,
+        date: Date,
+    ) -> AppResult<()> {
+        // an atomic counter, so concurrent downloads cost one write each and never conflict
+        self.db_client
+            .update_item()
+            .table_name(&self.table_name)
+            .key("pk", get_package_key(crate_name))
+            .key("sk", get_download_key(version, date))
+            .update_expression("ADD downloads :one SET #date = :date")
+            .expression_attribute_names("#date", "date".to_string())
+            .expression_attribute_values(":one", AttributeValue::N("1".to_string()))
+            .expression_attribute_values(":date", AttributeValue::S(format_date(date)))
+            .send()
+            .await?;
+
+        Ok(())
+    }
 
-        let output = self
+    async fn get_download_counts(
+        &self,
+        crate_name: &str,
+        version: Option<&Version>,
+    ) -> AppResult<Vec<DailyDownloads>> {
+        let prefix = match version {
+            Some(version) => format!("DL#{}#", version),
+            None => "DL#".to_string(),
+        };
+        let query = self
             .db_client
             .query()
             .table_name(&self.table_name)
-            .key_condition_expression("pk = :pk AND begins_with(sk, :prefix)")
+            .key_condition_expression("pk = :pk and begins_with(sk, :prefix)")
             .expression_attribute_values(":pk", get_package_key(crate_name))
-            .expression_attribute_values(":prefix", AttributeValue::S("V#".to_string()))
-            .projection_expression("vers")
+            .expression_attribute_values(":prefix", AttributeValue::S(prefix));
+        let items = query_all(query, self.page_size, None).await?;
+
+        #[derive(Debug, Deserialize)]
+        struct DownloadCount {
+            date: String,
+            downloads: u64,
+        }
+
+        let counts = from_items::<DownloadCount>(items)?
+            .into_iter()
+            .map(|count| Ok((parse_date(&count.date)?, count.downloads)))
+            .collect::<AppResult<Vec<_>>>()?;
+
+        Ok(per_day(counts))
+    }
+}
+
+impl DynamoDBRepository {
+    async fn try_store_package_info(
+        &self,
+        crate_name: &str,
+        version: &Version,
+        package_info: PackageInfo,
+        metadata: Metadata,
+        authenticated_user: &AuthenticatedUser,
+    ) -> AppResult<()> {
+        let canonical_name = canonical_crate_name(crate_name);
+        if let Some(existing) =
+            get_crate_name(&self.db_client, &self.table_name, &canonical_name).await?
+        {
+            if existing != crate_name {
+                return Err(AppError::CrateNameCollision {
+                    crate_name: crate_name.to_string(),
+                    existing,
+                });
+            }
+        }
+
+        let new_details =
+            match get_crate_details(&self.db_client, &self.table_name, crate_name).await? {
+                // this is a brand new crate
+                None => Some(NewCrateDetails::NewCrate(CrateSummary {
+                    name: crate_name.to_string(),
+                    owners: vec![authenticated_user.id],
+                    max_version: package_info.vers.clone(),
+                    description: metadata.description.clone().unwrap_or("".to_string()),
+                })),
+                // this is an update to an existing crate
+                Some(old_crate_details) => {
+                    if !old_crate_details.owners.contains(&authenticated_user.id) {
+                        return Err(AppError::Unauthorized(
+                            "user is not an owner of this package".to_string(),
+                        ));
+                    }
+
+                    // should we update the head state of the crate?
+                    // the head state represents the latest version, so while it's valid to
+                    // publish a non-head version, this should not affect the crate details
+                    if old_crate_details.max_version < package_info.vers {
+                        Some(NewCrateDetails::NewMaxVersion {
+                            previous_max_version: old_crate_details.max_version,
+                            max_version: package_info.vers.clone(),
+                            description: metadata.description.clone().unwrap_or("".to_string()),
+                        })
+                    } else {
+                        None
+                    }
+                }
+            };
+
+        put_package_version(
+            &self.db_client,
+            &self.table_name,
+            self.page_size,
+            version,
+            package_info,
+            metadata,
+            new_details,
+        )
+        .await
+    }
+
+    async fn try_set_yanked(
+        &self,
+        crate_name: &str,
+        version: &Version,
+        yanked: bool,
+    ) -> AppResult<()> {
+        let non_existent_version = || AppError::NonExistentCrateVersion {
+            crate_name: crate_name.to_string(),
+            version: version.clone(),
+        };
+        let index_state = get_index_state(&self.db_client, &self.table_name, crate_name).await?;
+        let mut infos = get_package_infos(
+            &self.db_client,
+            &self.table_name,
+            self.page_size,
+            crate_name,
+        )
+        .await?;
+        let info = infos
+            .iter_mut()
+            .find(|info| &info.vers == version)
+            .ok_or_else(non_existent_version)?;
+        info.yanked = yanked;
+
+        // the index state is written along with the yank, so it can't be computed from
+        // versions a concurrent publish has already changed
+        let update_version = Update::builder()
+            .table_name(&self.table_name)
+            .key("pk", get_package_key(crate_name))
+            .key("sk", get_package_version_key(version))
+            .update_expression("SET yanked = :y")
+            .condition_expression("attribute_exists(sk)")
+            .expression_attribute_values(":y", AttributeValue::Bool(yanked))
+            .build();
+        let put_index_state = put_index_state(&self.table_name, crate_name, &infos, index_state)?;
+
+        self.db_client
+            .transact_write_items()
+            .transact_items(TransactWriteItem::builder().update(update_version).build())
+            .transact_items(TransactWriteItem::builder().put(put_index_state).build())
             .send()
-            .await?;
+            .await
+            .map_err(|err| match err.into_service_error() {
+                TransactWriteItemsError::TransactionCanceledException(cancelled) => {
+                    let version_is_missing = cancelled
+                        .cancellation_reasons()
+                        .and_then(|reasons| reasons.first())
+                        .and_then(|reason| reason.code())
+                        == Some("ConditionalCheckFailed");
+                    if version_is_missing {
+                        non_existent_version()
+                    } else {
+                        AppError::ConcurrentWrite(crate_name.to_string())
+                    }
+                }
+                service_error => {
+                    let error_message = service_error.to_string();
+                    error!(error_message, "failed to yank package");
+                    anyhow!("internal server error").into()
+                }
+            })?;
+
+        Ok(())
+    }
+}
 
-        Ok(match output.items() {
-            None => vec![],
-            Some(items) => {
-                let parsed_items: Vec<QueryItem> = from_items(items.to_vec())?;
-                parsed_items.into_iter().map(|item| item.vers).collect()
+/// Runs a write that reads the crate's versions first again when a concurrent change to
+/// the crate cancelled it, giving up with `ConcurrentWrite` after a few attempts.
+async fn retry_concurrent_writes<F, Fut>(mut write: F) -> AppResult<()>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = AppResult<()>>,
+{
+    let mut attempt = 1;
+    loop {
+        match write().await {
+            Err(AppError::ConcurrentWrite(crate_name)) if attempt < MAX_WRITE_ATTEMPTS => {
+                warn!(
+                    crate_name,
+                    attempt, "retrying a write that raced another one"
+                );
+                attempt += 1;
             }
-        })
+            result => return result,
+        }
     }
 }
 
-async fn put_package_metadata(
+async fn get_package_infos(
     db_client: &Client,
     table_name: &str,
-    metadata: Metadata,
-) -> AppResult<()> {
-    let pk = get_package_key(&metadata.name);
-    let sk = get_package_metadata_key(&metadata.vers);
-    let item = to_item(metadata)?;
-    db_client
-        .put_item()
+    page_size: i32,
+    crate_name: &str,
+) -> AppResult<Vec<PackageInfo>> {
+    let query = db_client
+        .query()
+        .table_name(table_name)
+        .key_condition_expression("pk = :pk and begins_with(sk, :prefix)")
+        .expression_attribute_values(":pk", get_package_key(crate_name))
+        .expression_attribute_values(":prefix", AttributeValue::S("V#".to_string()));
+    let items = query_all(query, page_size, None).await?;
+
+    #[derive(Debug, Deserialize)]
+    struct PublishOrder {
+        /// Missing on versions published before it was recorded.
+        #[serde(default)]
+        published_at: u64,
+    }
+
+    let infos: Vec<PackageInfo> = from_items(items.clone())?;
+    let orders: Vec<PublishOrder> = from_items(items)?;
+    let entries = orders
+        .into_iter()
+        .map(|order| order.published_at)
+        .zip(infos)
+        .collect();
+
+    Ok(in_publish_order(entries))
+}
+
+async fn get_index_state(
+    db_client: &Client,
+    table_name: &str,
+    crate_name: &str,
+) -> AppResult<Option<IndexState>> {
+    let result = db_client
+        .get_item()
         .table_name(table_name)
-        .set_item(Some(item))
-        .item("pk", pk)
-        .item("sk", sk)
+        .key("pk", get_package_key(crate_name))
+        .key("sk", AttributeValue::S(INDEX_STATE_SORT_KEY.to_string()))
         .send()
         .await?;
 
-    Ok(())
+    let state = if let Some(item) = result.item().cloned() {
+        Some(from_item(item)?)
+    } else {
+        None
+    };
+
+    Ok(state)
 }
 
-async fn put_package_version(
-    db_client: &Client,
+/// Writes the index state computed from `infos`, but only if the stored state is still
+/// `index_state`, so a concurrent change to the crate cancels the write instead.
+fn put_index_state(
     table_name: &str,
     crate_name: &str,
-    version: &Version,
-    package_info: PackageInfo,
-) -> AppResult<()> {
-    let pk = get_package_key(&package_info.name);
-    let sk = get_package_version_key(&package_info.vers);
+    infos: &[PackageInfo],
+    index_state: Option<IndexState>,
+) -> AppResult<Put> {
+    let put = Put::builder()
+        .table_name(table_name)
+        .set_item(Some(to_item(IndexState::modified_now(infos))?))
+        .item("pk", get_package_key(crate_name))
+        .item("sk", AttributeValue::S(INDEX_STATE_SORT_KEY.to_string()));
+    let put = match index_state {
+        Some(index_state) => put
+            .condition_expression("etag = :etag")
+            .expression_attribute_values(":etag", AttributeValue::S(index_state.etag)),
+        // crates that haven't changed since index states were introduced don't have one
+        None => put.condition_expression("attribute_not_exists(sk)"),
+    };
 
-    let item = to_item(package_info)?;
-    match db_client
-        .put_item()
+    Ok(put.build())
+}
+
+/// Claims the canonical names of the crates published before they were recorded,
+/// returning how many were claimed.
+///
+/// Existing crates whose names only differ in case or `-`/`_` can't both keep theirs,
+/// the first one claims it and the others are logged.
+pub(super) async fn backfill_crate_names(
+    db_client: &Client,
+    table_name: &str,
+    page_size: i32,
+) -> AppResult<usize> {
+    let query = db_client
+        .query()
         .table_name(table_name)
-        .set_item(Some(item))
-        .item("pk", pk)
-        .item("sk", sk)
-        .condition_expression("attribute_not_exists(sk)")
-        .send()
-        .await
-    {
-        Ok(_) => {
-            info!(
-                crate_name = crate_name,
-                version = version.to_string(),
-                "persisted package info"
-            );
-            Ok(())
-        }
-        Err(err) => {
-            let err = match err.into_service_error() {
-                PutItemError::ConditionalCheckFailedException(_) => {
-                    AppError::DuplicateCrateVersion {
-                        crate_name: crate_name.to_string(),
-                        version: version.clone(),
-                    }
-                }
-                _ => {
-                    error!("failed to store package info");
-                    anyhow::anyhow!("unexpected error in persisting crate").into()
+        .key_condition_expression("pk = :pk")
+        .expression_attribute_values(":pk", AttributeValue::S(CRATES_PARTITION_KEY.to_string()));
+    let items = query_all(query, page_size, None).await?;
+    let crates = from_items::<CrateSummary>(items)?;
+
+    let mut claimed = 0;
+    for crate_summary in crates {
+        let result = db_client
+            .put_item()
+            .table_name(table_name)
+            .item(
+                "pk",
+                AttributeValue::S(CRATE_NAMES_PARTITION_KEY.to_string()),
+            )
+            .item(
+                "sk",
+                AttributeValue::S(canonical_crate_name(&crate_summary.name)),
+            )
+            .item("name", AttributeValue::S(crate_summary.name.clone()))
+            .condition_expression("attribute_not_exists(sk)")
+            .send()
+            .await;
+
+        match result.map_err(|err| err.into_service_error()) {
+            Ok(_) => claimed += 1,
+            Err(PutItemError::ConditionalCheckFailedException(_)) => {
+                let canonical_name = canonical_crate_name(&crate_summary.name);
+                let existing = get_crate_name(db_client, table_name, &canonical_name).await?;
+                if existing.as_deref() != Some(crate_summary.name.as_str()) {
+                    warn!(
+                        crate_name = crate_summary.name,
+                        existing, "crate name collides with an existing crate"
+                    );
                 }
-            };
-
-            Err(err)
+            }
+            Err(service_error) => {
+                let error_message = service_error.to_string();
+                error!(error_message, "failed to backfill crate name");
+                return Err(anyhow!("internal server error").into());
+            }
         }
     }
+
+    Ok(claimed)
 }
 
-async fn put_package_version_with_new_details(
+/// Crate details to write along with a new version.
+enum NewCrateDetails {
+    /// Fails if the crate's details already exist.
+    NewCrate(CrateSummary),
+    /// Fails if another publish already moved the crate's max version on.
+    NewMaxVersion {
+        previous_max_version: Version,
+        max_version: Version,
+        description: String,
+    },
+}
+
+/// Writes the version, its metadata and the index state in a single transaction,
+/// so a failed publish never leaves behind a version that blocks publishing it again.
+///
+/// The index state is only replaced if it's still the one it was computed from,
+/// a concurrent change to the crate cancels the transaction instead. Every publish
+/// also claims the crate's canonical name, unless the crate already holds it.
+async fn put_package_version(
     db_client: &Client,
     table_name: &str,
-    crate_name: &str,
+    page_size: i32,
     version: &Version,
     package_info: PackageInfo,
-    crate_details: CrateSummary,
-    is_new: bool,
+    metadata: Metadata,
+    new_details: Option<NewCrateDetails>,
 ) -> AppResult<()> {
-    let item = to_item(crate_details)?;
-    // TODO: when it's not new, this should probably verify we're not overwriting a competing write
-    let condition_expression = if is_new {
-        Some("attribute_not_exists(sk)".to_string())
-    } else {
-        None
-    };
-    let put_item = Put::builder()
+    let crate_name = package_info.name.clone();
+    let mut items = vec![];
+    match new_details {
+        Some(NewCrateDetails::NewCrate(crate_details)) => {
+            let put_details = Put::builder()
+                .table_name(table_name)
+                .set_item(Some(to_item(crate_details)?))
+                .item("pk", AttributeValue::S(CRATES_PARTITION_KEY.to_string()))
+                .item("sk", AttributeValue::S(crate_name.clone()))
+                .condition_expression("attribute_not_exists(sk)")
+                .build();
+            items.push(TransactWriteItem::builder().put(put_details).build());
+        }
+        // only the head state is updated, so concurrent changes to the owners are kept
+        Some(NewCrateDetails::NewMaxVersion {
+            previous_max_version,
+            max_version,
+            description,
+        }) => {
+            let update_details = Update::builder()
+                .table_name(table_name)
+                .key("pk", AttributeValue::S(CRATES_PARTITION_KEY.to_string()))
+                .key("sk", AttributeValue::S(crate_name.clone()))
+                .update_expression("SET max_version = :max_version, description = :description")
+                .condition_expression("max_version = :previous_max_version")
+                .expression_attribute_values(
+                    ":max_version",
+                    AttributeValue::S(max_version.to_string()),
+                )
+                .expression_attribute_values(":description", AttributeValue::S(description))
+                .expression_attribute_values(
+                    ":previous_max_version",
+                    AttributeValue::S(previous_max_version.to_string()),
+                )
+                .build();
+            items.push(TransactWriteItem::builder().update(update_details).build());
+        }
+        None => {}
+    }
+
+    let canonical_name = canonical_crate_name(&crate_name);
+    let name_index = items.len();
+    let put_name = Put::builder()
         .table_name(table_name)
-        .set_item(Some(item))
-        .item("pk", AttributeValue::S(CRATES_PARTITION_KEY.to_string()))
-        .item("sk", AttributeValue::S(crate_name.to_string()))
-        .set_condition_expression(condition_expression)
+        .item(
+            "pk",
+            AttributeValue::S(CRATE_NAMES_PARTITION_KEY.to_string()),
+        )
+        .item("sk", AttributeValue::S(canonical_name.clone()))
+        .item("name", AttributeValue::S(crate_name.clone()))
+        .condition_expression("attribute_not_exists(sk) OR #name = :name")
+        .expression_attribute_names("#name", "name")
+        .expression_attribute_values(":name", AttributeValue::S(crate_name.clone()))
         .build();
-    let put_details_item = TransactWriteItem::builder().put(put_item).build();
+    items.push(TransactWriteItem::builder().put(put_name).build());
 
-    let pk = get_package_key(&package_info.name);
-    let sk = get_package_version_key(&package_info.vers);
-    let item = to_item(package_info)?;
-    let put = Put::builder()
+    let index_state = get_index_state(db_client, table_name, &crate_name).await?;
+    let mut infos = get_package_infos(db_client, table_name, page_size, &crate_name).await?;
+    infos.push(package_info.clone());
+
+    let version_index = items.len();
+    let put_version = Put::builder()
         .table_name(table_name)
-        .set_item(Some(item))
-        .item("pk", pk)
-        .item("sk", sk)
+        .set_item(Some(to_item(package_info)?))
+        .item("pk", get_package_key(&crate_name))
+        .item("sk", get_package_version_key(version))
+        .item("published_at", publish_time_attribute())
+        .condition_expression("attribute_not_exists(sk)")
         .build();
-    let put_item = TransactWriteItem::builder().put(put).build();
+    items.push(TransactWriteItem::builder().put(put_version).build());
+
+    let put_metadata = Put::builder()
+        .table_name(table_name)
+        .set_item(Some(to_item(metadata)?))
+        .item("pk", get_package_key(&crate_name))
+        .item("sk", get_package_metadata_key(version))
+        .build();
+    items.push(TransactWriteItem::builder().put(put_metadata).build());
+
+    let put_index_state = put_index_state(table_name, &crate_name, &infos, index_state)?;
+    items.push(TransactWriteItem::builder().put(put_index_state).build());
 
     match db_client
         .transact_write_items()
-        .transact_items(put_details_item)
-        .transact_items(put_item)
+        .set_transact_items(Some(items))
         .send()
         .await
     {
@@ -410,15 +736,71 @@ async fn put_package_version_with_new_details(
             Ok(())
         }
         Err(e) => Err(match e.into_service_error() {
-            TransactWriteItemsError::TransactionCanceledException(_) => {
-                // TODO: how should we handle this? retry? fail?
-                anyhow::anyhow!("write conflict on new crate").into()
+            TransactWriteItemsError::TransactionCanceledException(cancelled) => {
+                let failed_condition = |index: usize| {
+                    cancelled
+                        .cancellation_reasons()
+                        .and_then(|reasons| reasons.get(index))
+                        .and_then(|reason| reason.code())
+                        == Some("ConditionalCheckFailed")
+                };
+                if failed_condition(name_index) {
+                    // another crate claimed the name after it was checked
+                    let existing = get_crate_name(db_client, table_name, &canonical_name)
+                        .await?
+                        .unwrap_or(canonical_name);
+                    AppError::CrateNameCollision {
+                        crate_name,
+                        existing,
+                    }
+                } else if failed_condition(version_index) {
+                    AppError::DuplicateCrateVersion {
+                        crate_name,
+                        version: version.clone(),
+                    }
+                } else {
+                    // the crate's details or index state changed since they were read
+                    AppError::ConcurrentWrite(crate_name)
+                }
+            }
+            service_error => {
+                let error_message = service_error.to_string();
+                error!(error_message, "failed to store package info");
+                anyhow::anyhow!("unexpected error in persisting crate").into()
             }
-            _ => anyhow::anyhow!("unexpected error in persisting crate").into(),
         }),
     }
 }
 
+async fn get_crate_name(
+    db_client: &Client,
+    table_name: &str,
+    canonical_name: &str,
+) -> AppResult<Option<String>> {
+    #[derive(Debug, Deserialize)]
+    struct CrateNameItem {
+        name: String,
+    }
+
+    let res = db_client
+        .get_item()
+        .table_name(table_name)
+        .key(
+            "pk",
+            AttributeValue::S(CRATE_NAMES_PARTITION_KEY.to_string()),
+        )
+        .key("sk", AttributeValue::S(canonical_name.to_string()))
+        .send()
+        .await?;
+
+    let name = match res.item {
+        Some(item) => Some(from_item::<_, CrateNameItem>(item)?.name),
+        None => None,
+    };
+
+    Ok(name)
+}
+
 async fn get_crate_details(
     db_client: &Client,
     table_name: &str,
@@ -450,10 +832,20 @@ fn get_package_version_key(version: &Version) -> AttributeValue {
     AttributeValue::S(format!("V#{}", version))
 }
 
+/// Stored on version items, since their sort keys don't reflect the publish order.
+fn publish_time_attribute() -> AttributeValue {
+    AttributeValue::N(publish_time().to_string())
+}
+
 fn get_package_metadata_key(version: &Version) -> AttributeValue {
     AttributeValue::S(format!("META#{}", version))
 }
 
+/// The version comes first, so a single version's counts can be read with a prefix.
+fn get_download_key(version: &Version, date: Date) -> AttributeValue {
+    AttributeValue::S(format!("DL#{}#{}", version, format_date(date)))
+}
+
 fn get_crate_info_key(crate_name: String) -> Option<HashMap<String, AttributeValue>> {
     let mut key = HashMap::new();
     key.insert(
@@ -0,0 +1,197 @@
+use anyhow::anyhow;
+use aws_sdk_dynamodb::operation::delete_item::DeleteItemError;
+use aws_sdk_dynamodb::operation::put_item::PutItemError;
+use aws_sdk_dynamodb::types::AttributeValue;
+use aws_sdk_dynamodb::Client;
+use serde::{Deserialize, Serialize};
+use serde_dynamo::{from_item, from_items, to_item};
+use time::OffsetDateTime;
+use tracing::error;
+
+use crate::error::{AppError, AppResult};
+use crate::models::public_key::PublicKey;
+use crate::models::token::{from_unix_timestamp, now};
+use crate::models::user::UserId;
+use crate::repository::base::PublicKeyRepository;
+use crate::repository::dynamodb::query_all;
+use crate::repository::DynamoDBRepository;
+
+#[async_trait::async_trait]
+impl PublicKeyRepository for DynamoDBRepository {
+    async fn store_public_key(
+        &self,
+        key_id: String,
+        public_key: String,
+        name: String,
+        user_id: u32,
+    ) -> AppResult<PublicKey> {
+        let key_item = PublicKeyItem {
+            pk: PublicKeyItem::get_pk(&key_id),
+            sk: PublicKeyItem::get_sk(),
+            key_id,
+            public_key,
+            name,
+            user_id,
+            created_at: now().unix_timestamp(),
+        };
+        self.db_client
+            .put_item()
+            .table_name(&self.table_name)
+            .set_item(Some(to_item(key_item.clone())?))
+            .condition_expression("attribute_not_exists(pk)")
+            .send()
+            .await
+            .map_err(|err| match err.into_service_error() {
+                PutItemError::ConditionalCheckFailedException(_) => {
+                    AppError::DuplicatePublicKey(key_item.key_id.clone())
+                }
+                service_error => {
+                    let error_message = service_error.to_string();
+                    error!(error_message, "failed to store public key");
+                    anyhow!("internal server error").into()
+                }
+            })?;
+
+        key_item.try_into()
+    }
+
+    async fn delete_public_key(&self, user_id: u32, key_id: String) -> AppResult<()> {
+        let result = self
+            .db_client
+            .delete_item()
+            .table_name(&self.table_name)
+            .key("pk", AttributeValue::S(PublicKeyItem::get_pk(&key_id)))
+            .key("sk", AttributeValue::S(PublicKeyItem::get_sk()))
+            .condition_expression("user_id = :user_id")
+            .expression_attribute_values(":user_id", AttributeValue::N(user_id.to_string()))
+            .send()
+            .await;
+
+        // the condition fails for someone else's key, or when there's no key at all
+        match result.map_err(|err| err.into_service_error()) {
+            Ok(_) | Err(DeleteItemError::ConditionalCheckFailedException(_)) => Ok(()),
+            Err(service_error) => {
+                let error_message = service_error.to_string();
+                error!(error_message, "failed to delete public key");
+                Err(anyhow!("internal server error").into())
+            }
+        }
+    }
+
+    async fn list_public_keys(&self, user_id: u32) -> AppResult<Vec<PublicKey>> {
+        let key_items = PublicKeyItem::get_keys_for_user(
+            &self.db_client,
+            &self.table_name,
+            self.page_size,
+            user_id,
+        )
+        .await?;
+        key_items.into_iter().map(TryInto::try_into).collect()
+    }
+
+    async fn get_public_key(&self, key_id: &str) -> AppResult<Option<PublicKey>> {
+        let output = self
+            .db_client
+            .get_item()
+            .table_name(&self.table_name)
+            .key("pk", AttributeValue::S(PublicKeyItem::get_pk(key_id)))
+            .key("sk", AttributeValue::S(PublicKeyItem::get_sk()))
+            .send()
+            .await?;
+
+        let public_key = if let Some(item) = output.item().cloned() {
+            let key_item: PublicKeyItem = from_item(item)?;
+            Some(key_item.try_into()?)
+        } else {
+            None
+        };
+
+        Ok(public_key)
+    }
+
+    async fn use_nonce(&self, nonce: &str, expires_at: OffsetDateTime) -> AppResult<bool> {
+        // items past their TTL can linger until DynamoDB gets around to deleting them
+        let result = self
+            .db_client
+            .put_item()
+            .table_name(&self.table_name)
+            .item("pk", AttributeValue::S(format!("NONCE#{}", nonce)))
+            .item("sk", AttributeValue::S("NONCE".to_string()))
+            .item(
+                "ttl",
+                AttributeValue::N(expires_at.unix_timestamp().to_string()),
+            )
+            .condition_expression("attribute_not_exists(pk) OR #ttl <= :now")
+            .expression_attribute_names("#ttl", "ttl")
+            .expression_attribute_values(
+                ":now",
+                AttributeValue::N(now().unix_timestamp().to_string()),
+            )
+            .send()
+            .await;
+
+        match result.map_err(|err| err.into_service_error()) {
+            Ok(_) => Ok(true),
+            Err(PutItemError::ConditionalCheckFailedException(_)) => Ok(false),
+            Err(service_error) => {
+                let error_message = service_error.to_string();
+                error!(error_message, "failed to record the use of a nonce");
+                Err(anyhow!("internal server error").into())
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct PublicKeyItem {
+    pub pk: String,
+    pub sk: String,
+    pub key_id: String,
+    pub public_key: String,
+    pub name: String,
+    pub user_id: u32,
+    /// A Unix timestamp.
+    pub created_at: i64,
+}
+
+impl PublicKeyItem {
+    async fn get_keys_for_user(
+        db_client: &Client,
+        table_name: &str,
+        page_size: i32,
+        user_id: UserId,
+    ) -> AppResult<Vec<PublicKeyItem>> {
+        let query = db_client
+            .query()
+            .table_name(table_name)
+            .index_name("user_tokens")
+            .key_condition_expression("user_id = :user_id AND begins_with(pk, :prefix)")
+            .expression_attribute_values(":user_id", AttributeValue::N(user_id.to_string()))
+            .expression_attribute_values(":prefix", AttributeValue::S("PUBKEY#".to_string()));
+        let items = query_all(query, page_size, None).await?;
+
+        Ok(from_items(items)?)
+    }
+
+    fn get_pk(key_id: &str) -> String {
+        format!("PUBKEY#{}", key_id)
+    }
+
+    fn get_sk() -> String {
+        "PUBKEY".to_string()
+    }
+}
+
+impl TryFrom<PublicKeyItem> for PublicKey {
+    type Error = AppError;
+
+    fn try_from(item: PublicKeyItem) -> Result<Self, Self::Error> {
+        Ok(Self {
+            key_id: item.key_id,
+            public_key: item.public_key,
+            name: item.name,
+            user_id: item.user_id,
+            created_at: from_unix_timestamp(item.created_at)?,
+        })
+    }
+}
@@ -1,21 +1,33 @@
+use anyhow::anyhow;
+use aws_sdk_dynamodb::operation::update_item::UpdateItemError;
 use aws_sdk_dynamodb::types::AttributeValue;
 use aws_sdk_dynamodb::Client;
 use base64::Engine;
 use serde::{Deserialize, Serialize};
 use serde_dynamo::{from_item, from_items, to_item};
+use time::OffsetDateTime;
+use tracing::error;
 use uuid::Uuid;
 
 use crate::auth::hash;
-use crate::error::AppResult;
-use crate::models::token::Token;
+use crate::error::{AppError, AppResult};
+use crate::models::token::{from_unix_timestamp, now, Token, TokenScopes};
 use crate::models::user::UserId;
 use crate::repository::base::TokenRepository;
+use crate::repository::dynamodb::query_all;
 use crate::repository::DynamoDBRepository;
 
 #[async_trait::async_trait]
 impl TokenRepository for DynamoDBRepository {
-    async fn store_auth_token(&self, token: &[u8], name: String, user_id: u32) -> AppResult<Token> {
-        let token_item = TokenItem::new(token, name, user_id);
+    async fn store_auth_token(
+        &self,
+        token: &[u8],
+        name: String,
+        user_id: u32,
+        scopes: TokenScopes,
+        expires_at: Option<OffsetDateTime>,
+    ) -> AppResult<Token> {
+        let token_item = TokenItem::new(token, name, user_id, scopes, expires_at);
         let item = to_item(token_item.clone())?;
         self.db_client
             .put_item()
@@ -24,12 +36,17 @@ impl TokenRepository for DynamoDBRepository {
             .send()
             .await?;
 
-        Ok(token_item.into())
+        token_item.try_into()
     }
 
     async fn delete_auth_token(&self, user_id: u32, token_id: String) -> AppResult<()> {
-        let tokens =
-            TokenItem::get_tokens_for_user(&self.db_client, &self.table_name, user_id).await?;
+        let tokens = TokenItem::get_tokens_for_user(
+            &self.db_client,
+            &self.table_name,
+            self.page_size,
+            user_id,
+        )
+        .await?;
         if let Some(token_to_delete) = tokens.into_iter().find(|item| item.token_id == token_id) {
             self.db_client
                 .delete_item()
@@ -44,11 +61,14 @@ impl TokenRepository for DynamoDBRepository {
     }
 
     async fn list_auth_tokens(&self, user_id: u32) -> AppResult<Vec<Token>> {
-        let token_items =
-            TokenItem::get_tokens_for_user(&self.db_client, &self.table_name, user_id).await?;
-        let tokens = token_items.into_iter().map(|i| i.into()).collect();
-
-        Ok(tokens)
+        let token_items = TokenItem::get_tokens_for_user(
+            &self.db_client,
+            &self.table_name,
+            self.page_size,
+            user_id,
+        )
+        .await?;
+        token_items.into_iter().map(TryInto::try_into).collect()
     }
 
     async fn get_auth_token(&self, token: &[u8]) -> AppResult<Option<Token>> {
@@ -63,13 +83,39 @@ impl TokenRepository for DynamoDBRepository {
 
         let token = if let Some(item) = output.item().cloned() {
             let token_item: TokenItem = from_item(item)?;
-            Some(token_item.into())
+            Some(token_item.try_into()?)
         } else {
             None
         };
 
         Ok(token)
     }
+
+    async fn record_token_use(&self, token: &[u8], used_at: OffsetDateTime) -> AppResult<()> {
+        let result = self
+            .db_client
+            .update_item()
+            .table_name(&self.table_name)
+            .key("pk", AttributeValue::S(TokenItem::get_pk(token)))
+            .key("sk", AttributeValue::S(TokenItem::get_sk()))
+            .update_expression("SET last_used_at = :used_at")
+            .condition_expression("attribute_exists(pk)")
+            .expression_attribute_values(
+                ":used_at",
+                AttributeValue::N(used_at.unix_timestamp().to_string()),
+            )
+            .send()
+            .await;
+
+        match result.map_err(|err| err.into_service_error()) {
+            Ok(_) | Err(UpdateItemError::ConditionalCheckFailedException(_)) => Ok(()),
+            Err(service_error) => {
+                let error_message = service_error.to_string();
+                error!(error_message, "failed to record the use of a token");
+                Err(anyhow!("internal server error").into())
+            }
+        }
+    }
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -79,34 +125,55 @@ struct TokenItem {
     pub name: String,
     pub user_id: u32,
     pub token_id: String,
+    /// Missing on tokens created before scopes existed.
+    #[serde(default)]
+    pub scopes: TokenScopes,
+    /// Unix timestamps, the creation time is missing on tokens created before it was stored.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_used_at: Option<i64>,
 }
 
 impl TokenItem {
-    fn new(token: &[u8], name: String, user_id: u32) -> Self {
+    fn new(
+        token: &[u8],
+        name: String,
+        user_id: u32,
+        scopes: TokenScopes,
+        expires_at: Option<OffsetDateTime>,
+    ) -> Self {
         Self {
             pk: Self::get_pk(token),
             sk: Self::get_sk(),
             name,
             user_id,
             token_id: Uuid::new_v4().hyphenated().to_string(),
+            scopes,
+            created_at: Some(now().unix_timestamp()),
+            expires_at: expires_at.map(|expires_at| expires_at.unix_timestamp()),
+            last_used_at: None,
         }
     }
 
     async fn get_tokens_for_user(
         db_client: &Client,
         table_name: &str,
+        page_size: i32,
         user_id: UserId,
     ) -> AppResult<Vec<TokenItem>> {
-        let output = db_client
+        let query = db_client
             .query()
             .table_name(table_name)
             .index_name("user_tokens")
-            .key_condition_expression("user_id = :user_id")
+            .key_condition_expression("user_id = :user_id AND begins_with(pk, :prefix)")
             .expression_attribute_values(":user_id", AttributeValue::N(user_id.to_string()))
-            .send()
-            .await?;
+            // public keys are in the same index
+            .expression_attribute_values(":prefix", AttributeValue::S("TOK#".to_string()));
+        let items = query_all(query, page_size, None).await?;
 
-        let items = output.items().map(|items| items.to_vec()).unwrap_or(vec![]);
         Ok(from_items(items)?)
     }
 
@@ -120,12 +187,18 @@ impl TokenItem {
     }
 }
 
-impl From<TokenItem> for Token {
-    fn from(item: TokenItem) -> Self {
-        Self {
+impl TryFrom<TokenItem> for Token {
+    type Error = AppError;
+
+    fn try_from(item: TokenItem) -> Result<Self, Self::Error> {
+        Ok(Self {
             name: item.name,
             user_id: item.user_id,
             token_id: item.token_id,
-        }
+            scopes: item.scopes,
+            created_at: item.created_at.map(from_unix_timestamp).transpose()?,
+            expires_at: item.expires_at.map(from_unix_timestamp).transpose()?,
+            last_used_at: item.last_used_at.map(from_unix_timestamp).transpose()?,
+        })
     }
 }
@@ -7,6 +7,7 @@ use tracing::info;
 use crate::error::{internal_error, AppResult};
 use crate::models::user::{CognitoUserData, User, UserId};
 use crate::repository::base::UserRepository;
+use crate::repository::dynamodb::query_all;
 use crate::repository::DynamoDBRepository;
 
 #[async_trait::async_trait]
@@ -65,23 +66,16 @@ impl UserRepository for DynamoDBRepository {
     }
 
     async fn get_users(&self) -> AppResult<Vec<User>> {
-        let output = self
+        let query = self
             .db_client
             .query()
             .table_name(&self.table_name)
             .key_condition_expression("pk = :pk and begins_with(sk, :prefix)")
             .expression_attribute_values(":pk", AttributeValue::S("USERS".to_string()))
-            .expression_attribute_values(":prefix", AttributeValue::S("ID#".to_string()))
-            .send()
-            .await?;
+            .expression_attribute_values(":prefix", AttributeValue::S("ID#".to_string()));
+        let items = query_all(query, self.page_size, None).await?;
 
-        match output.items() {
-            None => Ok(vec![]),
-            Some(items) => {
-                let users = from_items(items.to_vec())?;
-                Ok(users)
-            }
-        }
+        Ok(from_items(items)?)
     }
 }
 
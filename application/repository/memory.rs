@@ -0,0 +1,69 @@
+mod krate;
+mod public_key;
+mod token;
+mod user;
+
+use std::collections::{BTreeMap, HashMap};
+
+use semver::Version;
+use time::{Date, OffsetDateTime};
+use tokio::sync::RwLock;
+
+use crate::models::crate_summary::CrateSummary;
+use crate::models::index::{IndexState, PackageInfo};
+use crate::models::limits::CrateLimits;
+use crate::models::metadata::Metadata;
+use crate::models::public_key::PublicKey;
+use crate::models::token::TokenScopes;
+use crate::models::user::{User, UserId};
+use crate::repository::Repository;
+
+/// A repository that keeps everything in memory.
+///
+/// Nothing is persisted, so this is meant for local development and tests,
+/// where it removes the need to run DynamoDB.
+#[derive(Debug, Default)]
+pub struct MemoryRepository {
+    state: RwLock<MemoryState>,
+}
+
+impl MemoryRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[derive(Debug, Default)]
+struct MemoryState {
+    crates: BTreeMap<String, CrateSummary>,
+    /// The crate name for each canonical name.
+    canonical_names: HashMap<String, String>,
+    /// Package info per crate, in the order the versions were published.
+    versions: HashMap<String, Vec<PackageInfo>>,
+    index_states: HashMap<String, IndexState>,
+    metadata: HashMap<(String, Version), Metadata>,
+    limits: HashMap<String, CrateLimits>,
+    /// Download counts per crate, keyed by version and day.
+    downloads: HashMap<String, HashMap<(Version, Date), u64>>,
+    users: BTreeMap<UserId, User>,
+    logins: HashMap<String, UserId>,
+    tokens: HashMap<String, TokenEntry>,
+    /// Public keys by their key ID.
+    public_keys: HashMap<String, PublicKey>,
+    /// Used nonces and when they can be forgotten.
+    nonces: HashMap<String, OffsetDateTime>,
+}
+
+#[derive(Clone, Debug)]
+struct TokenEntry {
+    name: String,
+    user_id: UserId,
+    token_id: String,
+    scopes: TokenScopes,
+    created_at: OffsetDateTime,
+    expires_at: Option<OffsetDateTime>,
+    last_used_at: Option<OffsetDateTime>,
+}
+
+#[async_trait::async_trait]
+impl Repository for MemoryRepository {}
@@ -0,0 +1,268 @@
+use semver::Version;
+use time::Date;
+
+use crate::auth::AuthenticatedUser;
+use crate::error::{AppError, AppResult};
+use crate::models::crate_name::canonical_crate_name;
+use crate::models::crate_summary::CrateSummary;
+use crate::models::downloads::{per_day, DailyDownloads};
+use crate::models::index::{IndexState, PackageInfo};
+use crate::models::limits::CrateLimits;
+use crate::models::metadata::Metadata;
+use crate::models::user::{User, UserId};
+use crate::repository::base::CrateRepository;
+use crate::repository::MemoryRepository;
+
+#[async_trait::async_trait]
+impl CrateRepository for MemoryRepository {
+    async fn get_package_info(&self, crate_name: &str) -> AppResult<String> {
+        let state = self.state.read().await;
+        let versions = state
+            .versions
+            .get(crate_name)
+            .filter(|versions| !versions.is_empty())
+            .ok_or_else(|| AppError::NonExistentPackageInfo(crate_name.to_string()))?;
+
+        let info_strings: Vec<String> = versions
+            .iter()
+            .map(serde_json::to_string)
+            .collect::<Result<Vec<_>, serde_json::Error>>()?;
+        Ok(info_strings.join("\n"))
+    }
+
+    async fn get_index_state(&self, crate_name: &str) -> AppResult<Option<IndexState>> {
+        let state = self.state.read().await;
+        Ok(state.index_states.get(crate_name).cloned())
+    }
+
+    async fn store_package_info(
+        &self,
+        crate_name: &str,
+        version: &Version,
+        package_info: PackageInfo,
+        metadata: Metadata,
+        authenticated_user: &AuthenticatedUser,
+    ) -> AppResult<()> {
+        let mut state = self.state.write().await;
+
+        let canonical_name = canonical_crate_name(crate_name);
+        if let Some(existing) = state.canonical_names.get(&canonical_name) {
+            if existing != crate_name {
+                return Err(AppError::CrateNameCollision {
+                    crate_name: crate_name.to_string(),
+                    existing: existing.clone(),
+                });
+            }
+        }
+
+        let crate_details = match state.crates.get(crate_name) {
+            // this is a brand new crate
+            None => CrateSummary {
+                name: crate_name.to_string(),
+                owners: vec![authenticated_user.id],
+                max_version: package_info.vers.clone(),
+                description: metadata.description.clone().unwrap_or_default(),
+            },
+            // this is an update to an existing crate
+            Some(old_crate_details) => {
+                if !old_crate_details.owners.contains(&authenticated_user.id) {
+                    return Err(AppError::Unauthorized(
+                        "user is not an owner of this package".to_string(),
+                    ));
+                }
+
+                // only a new head version updates the crate details
+                if old_crate_details.max_version < package_info.vers {
+                    CrateSummary {
+                        name: crate_name.to_string(),
+                        owners: old_crate_details.owners.clone(),
+                        max_version: package_info.vers.clone(),
+                        description: metadata.description.clone().unwrap_or_default(),
+                    }
+                } else {
+                    old_crate_details.clone()
+                }
+            }
+        };
+
+        let versions = state.versions.entry(crate_name.to_string()).or_default();
+        if versions.iter().any(|info| info.vers == *version) {
+            return Err(AppError::DuplicateCrateVersion {
+                crate_name: crate_name.to_string(),
+                version: version.clone(),
+            });
+        }
+        versions.push(package_info);
+        let index_state = IndexState::modified_now(versions);
+        state
+            .index_states
+            .insert(crate_name.to_string(), index_state);
+
+        state.crates.insert(crate_name.to_string(), crate_details);
+        state
+            .canonical_names
+            .insert(canonical_name, crate_name.to_string());
+        state
+            .metadata
+            .insert((metadata.name.clone(), metadata.vers.clone()), metadata);
+
+        Ok(())
+    }
+
+    async fn set_yanked(&self, crate_name: &str, version: &Version, yanked: bool) -> AppResult<()> {
+        let mut state = self.state.write().await;
+        let versions = state.versions.get_mut(crate_name);
+        let package_info = versions
+            .and_then(|versions| versions.iter_mut().find(|info| info.vers == *version))
+            .ok_or_else(|| AppError::NonExistentCrateVersion {
+                crate_name: crate_name.to_string(),
+                version: version.clone(),
+            })?;
+        package_info.yanked = yanked;
+
+        let index_state = IndexState::modified_now(&state.versions[crate_name]);
+        state
+            .index_states
+            .insert(crate_name.to_string(), index_state);
+
+        Ok(())
+    }
+
+    async fn list_owners(&self, crate_name: &str) -> AppResult<Vec<User>> {
+        let state = self.state.read().await;
+        let crate_details = state
+            .crates
+            .get(crate_name)
+            .ok_or_else(|| AppError::NonExistentPackageInfo(crate_name.to_string()))?;
+
+        let users = crate_details
+            .owners
+            .iter()
+            .filter_map(|id| state.users.get(id).cloned())
+            .collect();
+
+        Ok(users)
+    }
+
+    async fn add_owners(&self, crate_name: &str, user_ids: Vec<String>) -> AppResult<()> {
+        let new_owners = user_ids
+            .iter()
+            .map(|id| id.parse::<UserId>())
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut state = self.state.write().await;
+        let crate_details = state
+            .crates
+            .get_mut(crate_name)
+            .ok_or_else(|| AppError::NonExistentPackageInfo(crate_name.to_string()))?;
+
+        for owner in new_owners {
+            if !crate_details.owners.contains(&owner) {
+                crate_details.owners.push(owner);
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn get_crate_summary(&self, crate_name: &str) -> AppResult<Option<CrateSummary>> {
+        let state = self.state.read().await;
+        Ok(state.crates.get(crate_name).cloned())
+    }
+
+    async fn resolve_crate_name(&self, crate_name: &str) -> AppResult<Option<String>> {
+        let state = self.state.read().await;
+        let canonical_name = canonical_crate_name(crate_name);
+        Ok(state.canonical_names.get(&canonical_name).cloned())
+    }
+
+    async fn get_all_crate_details(
+        &self,
+        filter: Option<String>,
+        limit: usize,
+    ) -> AppResult<Vec<CrateSummary>> {
+        let state = self.state.read().await;
+        let prefix = filter.unwrap_or_default();
+        let crates = state
+            .crates
+            .values()
+            .filter(|summary| summary.name.starts_with(&prefix))
+            .take(limit)
+            .cloned()
+            .collect();
+
+        Ok(crates)
+    }
+
+    async fn get_crate_metadata(
+        &self,
+        crate_name: &str,
+        version: &Version,
+    ) -> AppResult<Option<Metadata>> {
+        let state = self.state.read().await;
+        let key = (crate_name.to_string(), version.clone());
+        Ok(state.metadata.get(&key).cloned())
+    }
+
+    async fn list_crate_versions(&self, crate_name: &str) -> AppResult<Vec<Version>> {
+        let state = self.state.read().await;
+        let versions = state
+            .versions
+            .get(crate_name)
+            .map(|versions| versions.iter().map(|info| info.vers.clone()).collect())
+            .unwrap_or_default();
+
+        Ok(versions)
+    }
+
+    async fn list_package_infos(&self, crate_name: &str) -> AppResult<Vec<PackageInfo>> {
+        let state = self.state.read().await;
+        Ok(state.versions.get(crate_name).cloned().unwrap_or_default())
+    }
+
+    async fn get_crate_limits(&self, crate_name: &str) -> AppResult<CrateLimits> {
+        let state = self.state.read().await;
+        Ok(state.limits.get(crate_name).cloned().unwrap_or_default())
+    }
+
+    async fn set_crate_limits(&self, crate_name: &str, limits: CrateLimits) -> AppResult<()> {
+        let mut state = self.state.write().await;
+        state.limits.insert(crate_name.to_string(), limits);
+
+        Ok(())
+    }
+
+    async fn record_download(
+        &self,
+        crate_name: &str,
+        version: &Version,
+        date: Date,
+    ) -> AppResult<()> {
+        let mut state = self.state.write().await;
+        *state
+            .downloads
+            .entry(crate_name.to_string())
+            .or_default()
+            .entry((version.clone(), date))
+            .or_insert(0) += 1;
+
+        Ok(())
+    }
+
+    async fn get_download_counts(
+        &self,
+        crate_name: &str,
+        version: Option<&Version>,
+    ) -> AppResult<Vec<DailyDownloads>> {
+        let state = self.state.read().await;
+        let counts = state
+            .downloads
+            .get(crate_name)
+            .into_iter()
+            .flatten()
+            .filter(|((vers, _), _)| version.is_none_or(|version| vers == version))
+            .map(|((_, date), downloads)| (*date, *downloads));
+
+        Ok(per_day(counts))
+    }
+}
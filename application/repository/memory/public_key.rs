@@ -0,0 +1,74 @@
+use time::OffsetDateTime;
+
+use crate::error::{AppError, AppResult};
+use crate::models::public_key::PublicKey;
+use crate::models::token::now;
+use crate::repository::base::PublicKeyRepository;
+use crate::repository::MemoryRepository;
+
+#[async_trait::async_trait]
+impl PublicKeyRepository for MemoryRepository {
+    async fn store_public_key(
+        &self,
+        key_id: String,
+        public_key: String,
+        name: String,
+        user_id: u32,
+    ) -> AppResult<PublicKey> {
+        let mut state = self.state.write().await;
+        if state.public_keys.contains_key(&key_id) {
+            return Err(AppError::DuplicatePublicKey(key_id));
+        }
+
+        let public_key = PublicKey {
+            key_id,
+            public_key,
+            name,
+            user_id,
+            created_at: now(),
+        };
+        state
+            .public_keys
+            .insert(public_key.key_id.clone(), public_key.clone());
+
+        Ok(public_key)
+    }
+
+    async fn delete_public_key(&self, user_id: u32, key_id: String) -> AppResult<()> {
+        let mut state = self.state.write().await;
+        if state
+            .public_keys
+            .get(&key_id)
+            .is_some_and(|key| key.user_id == user_id)
+        {
+            state.public_keys.remove(&key_id);
+        }
+
+        Ok(())
+    }
+
+    async fn list_public_keys(&self, user_id: u32) -> AppResult<Vec<PublicKey>> {
+        let state = self.state.read().await;
+        let keys = state
+            .public_keys
+            .values()
+            .filter(|key| key.user_id == user_id)
+            .cloned()
+            .collect();
+
+        Ok(keys)
+    }
+
+    async fn get_public_key(&self, key_id: &str) -> AppResult<Option<PublicKey>> {
+        let state = self.state.read().await;
+        Ok(state.public_keys.get(key_id).cloned())
+    }
+
+    async fn use_nonce(&self, nonce: &str, expires_at: OffsetDateTime) -> AppResult<bool> {
+        let mut state = self.state.write().await;
+        let now = now();
+        state.nonces.retain(|_, expires_at| *expires_at > now);
+
+        Ok(state.nonces.insert(nonce.to_string(), expires_at).is_none())
+    }
+}
@@ -0,0 +1,95 @@
+use base64::Engine;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use crate::auth::hash;
+use crate::error::AppResult;
+use crate::models::token::{now, Token, TokenScopes};
+use crate::repository::base::TokenRepository;
+use crate::repository::memory::TokenEntry;
+use crate::repository::MemoryRepository;
+
+#[async_trait::async_trait]
+impl TokenRepository for MemoryRepository {
+    async fn store_auth_token(
+        &self,
+        token: &[u8],
+        name: String,
+        user_id: u32,
+        scopes: TokenScopes,
+        expires_at: Option<OffsetDateTime>,
+    ) -> AppResult<Token> {
+        let entry = TokenEntry {
+            name,
+            user_id,
+            token_id: Uuid::new_v4().hyphenated().to_string(),
+            scopes,
+            created_at: now(),
+            expires_at,
+            last_used_at: None,
+        };
+        let mut state = self.state.write().await;
+        state.tokens.insert(get_token_key(token), entry.clone());
+
+        Ok(entry.into())
+    }
+
+    async fn delete_auth_token(&self, user_id: u32, token_id: String) -> AppResult<()> {
+        let mut state = self.state.write().await;
+        state
+            .tokens
+            .retain(|_, entry| !(entry.user_id == user_id && entry.token_id == token_id));
+
+        Ok(())
+    }
+
+    async fn list_auth_tokens(&self, user_id: u32) -> AppResult<Vec<Token>> {
+        let state = self.state.read().await;
+        let tokens = state
+            .tokens
+            .values()
+            .filter(|entry| entry.user_id == user_id)
+            .cloned()
+            .map(Into::into)
+            .collect();
+
+        Ok(tokens)
+    }
+
+    async fn get_auth_token(&self, token: &[u8]) -> AppResult<Option<Token>> {
+        let state = self.state.read().await;
+        Ok(state
+            .tokens
+            .get(&get_token_key(token))
+            .cloned()
+            .map(Into::into))
+    }
+
+    async fn record_token_use(&self, token: &[u8], used_at: OffsetDateTime) -> AppResult<()> {
+        let mut state = self.state.write().await;
+        if let Some(entry) = state.tokens.get_mut(&get_token_key(token)) {
+            entry.last_used_at = Some(used_at);
+        }
+
+        Ok(())
+    }
+}
+
+/// Only the hash of the token is kept, the same way the DynamoDB repository does it.
+fn get_token_key(token: &[u8]) -> String {
+    base64::engine::general_purpose::STANDARD.encode(hash(token))
+}
+
+impl From<TokenEntry> for Token {
+    fn from(entry: TokenEntry) -> Self {
+        Self {
+            name: entry.name,
+            user_id: entry.user_id,
+            token_id: entry.token_id,
+            scopes: entry.scopes,
+            created_at: Some(entry.created_at),
+            expires_at: entry.expires_at,
+            last_used_at: entry.last_used_at,
+        }
+    }
+}
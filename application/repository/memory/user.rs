@@ -0,0 +1,50 @@
+use tracing::info;
+
+use crate::error::AppResult;
+use crate::models::user::{CognitoUserData, User, UserId};
+use crate::repository::base::UserRepository;
+use crate::repository::MemoryRepository;
+
+#[async_trait::async_trait]
+impl UserRepository for MemoryRepository {
+    async fn update_or_create_user(&self, user_data: CognitoUserData) -> AppResult<User> {
+        let mut state = self.state.write().await;
+
+        match state.logins.get(&user_data.login).copied() {
+            None => {
+                info!("user not found, creating new user");
+                let next_id = state.users.keys().next_back().copied().unwrap_or(0) + 1;
+                let user = user_data.into_user(next_id);
+                state.logins.insert(user.login.clone(), user.id);
+                state.users.insert(user.id, user.clone());
+
+                Ok(user)
+            }
+            Some(id) => {
+                let user = state
+                    .users
+                    .get(&id)
+                    .cloned()
+                    .expect("user to exist for known login");
+
+                // if the existing user data is out of sync, update it
+                let existing_user_data: CognitoUserData = user.clone().into();
+                if existing_user_data != user_data {
+                    state.users.insert(id, user_data.into_user(id));
+                }
+
+                Ok(user)
+            }
+        }
+    }
+
+    async fn get_user_by_id(&self, user_id: UserId) -> AppResult<Option<User>> {
+        let state = self.state.read().await;
+        Ok(state.users.get(&user_id).cloned())
+    }
+
+    async fn get_users(&self) -> AppResult<Vec<User>> {
+        let state = self.state.read().await;
+        Ok(state.users.values().cloned().collect())
+    }
+}
@@ -0,0 +1,503 @@
+use semver::Version;
+use sqlx::{Any, Row, Transaction};
+use std::str::FromStr;
+use time::Date;
+use tracing::info;
+
+use crate::auth::AuthenticatedUser;
+use crate::error::{internal_error, AppError, AppResult};
+use crate::models::crate_name::canonical_crate_name;
+use crate::models::crate_summary::CrateSummary;
+use crate::models::downloads::{format_date, parse_date, per_day, DailyDownloads};
+use crate::models::index::{in_publish_order, publish_time, IndexState, PackageInfo};
+use crate::models::limits::CrateLimits;
+use crate::models::metadata::Metadata;
+use crate::models::user::{User, UserId};
+use crate::repository::base::CrateRepository;
+use crate::repository::SqlRepository;
+
+#[async_trait::async_trait]
+impl CrateRepository for SqlRepository {
+    async fn get_package_info(&self, crate_name: &str) -> AppResult<String> {
+        let infos = get_package_infos(&self.pool, crate_name).await?;
+        if infos.is_empty() {
+            return Err(AppError::NonExistentPackageInfo(crate_name.to_string()));
+        }
+
+        let info_strings: Vec<String> = infos
+            .iter()
+            .map(serde_json::to_string)
+            .collect::<Result<Vec<_>, serde_json::Error>>()?;
+        Ok(info_strings.join("\n"))
+    }
+
+    async fn get_index_state(&self, crate_name: &str) -> AppResult<Option<IndexState>> {
+        let row =
+            sqlx::query("SELECT etag, last_modified FROM crate_index_states WHERE crate_name = $1")
+                .bind(crate_name)
+                .fetch_optional(&self.pool)
+                .await?;
+
+        let state = match row {
+            Some(row) => Some(IndexState {
+                etag: row.try_get("etag")?,
+                last_modified: row
+                    .try_get::<i64, _>("last_modified")?
+                    .try_into()
+                    .map_err(|_| internal_error())?,
+            }),
+            None => None,
+        };
+
+        Ok(state)
+    }
+
+    async fn store_package_info(
+        &self,
+        crate_name: &str,
+        version: &Version,
+        package_info: PackageInfo,
+        metadata: Metadata,
+        authenticated_user: &AuthenticatedUser,
+    ) -> AppResult<()> {
+        let mut tx = self.pool.begin().await?;
+
+        let canonical_name = canonical_crate_name(crate_name);
+        let existing = sqlx::query("SELECT name FROM crates WHERE canonical_name = $1")
+            .bind(&canonical_name)
+            .fetch_optional(&mut *tx)
+            .await?;
+        if let Some(row) = existing {
+            let existing: String = row.try_get("name")?;
+            if existing != crate_name {
+                return Err(AppError::CrateNameCollision {
+                    crate_name: crate_name.to_string(),
+                    existing,
+                });
+            }
+        }
+
+        lock_crate(&mut tx, crate_name).await?;
+        match get_crate_details(&mut tx, crate_name).await? {
+            // this is a brand new crate
+            None => {
+                sqlx::query(
+                    "INSERT INTO crates (name, canonical_name, max_version, description) \
+                     VALUES ($1, $2, $3, $4)",
+                )
+                .bind(crate_name)
+                .bind(&canonical_name)
+                .bind(package_info.vers.to_string())
+                .bind(metadata.description.clone().unwrap_or_default())
+                .execute(&mut *tx)
+                .await
+                .map_err(|err| {
+                    // a concurrent publish created the crate after it was checked
+                    if is_unique_violation(&err) {
+                        AppError::ConcurrentWrite(crate_name.to_string())
+                    } else {
+                        AppError::from(err)
+                    }
+                })?;
+                sqlx::query("INSERT INTO crate_owners (crate_name, user_id) VALUES ($1, $2)")
+                    .bind(crate_name)
+                    .bind(authenticated_user.id as i64)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+            // this is an update to an existing crate
+            Some(old_crate_details) => {
+                if !old_crate_details.owners.contains(&authenticated_user.id) {
+                    return Err(AppError::Unauthorized(
+                        "user is not an owner of this package".to_string(),
+                    ));
+                }
+
+                // only a new head version updates the crate details
+                if old_crate_details.max_version < package_info.vers {
+                    sqlx::query(
+                        "UPDATE crates SET max_version = $1, description = $2 WHERE name = $3",
+                    )
+                    .bind(package_info.vers.to_string())
+                    .bind(metadata.description.clone().unwrap_or_default())
+                    .bind(crate_name)
+                    .execute(&mut *tx)
+                    .await?;
+                }
+            }
+        }
+
+        sqlx::query(
+            "INSERT INTO crate_versions (crate_name, version, package_info, published_at) \
+             VALUES ($1, $2, $3, $4)",
+        )
+        .bind(crate_name)
+        .bind(version.to_string())
+        .bind(serde_json::to_string(&package_info)?)
+        .bind(publish_time() as i64)
+        .execute(&mut *tx)
+        .await
+        .map_err(|err| {
+            if is_unique_violation(&err) {
+                AppError::DuplicateCrateVersion {
+                    crate_name: crate_name.to_string(),
+                    version: version.clone(),
+                }
+            } else {
+                err.into()
+            }
+        })?;
+
+        sqlx::query(
+            "INSERT INTO crate_metadata (crate_name, version, metadata) VALUES ($1, $2, $3)",
+        )
+        .bind(crate_name)
+        .bind(version.to_string())
+        .bind(serde_json::to_string(&metadata)?)
+        .execute(&mut *tx)
+        .await?;
+
+        update_index_state(&mut tx, crate_name).await?;
+        tx.commit().await?;
+        info!(
+            crate_name = crate_name,
+            version = version.to_string(),
+            "persisted package info"
+        );
+
+        Ok(())
+    }
+
+    async fn set_yanked(&self, crate_name: &str, version: &Version, yanked: bool) -> AppResult<()> {
+        let mut tx = self.pool.begin().await?;
+        lock_crate(&mut tx, crate_name).await?;
+        let row = sqlx::query(
+            "SELECT package_info FROM crate_versions WHERE crate_name = $1 AND version = $2",
+        )
+        .bind(crate_name)
+        .bind(version.to_string())
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| AppError::NonExistentCrateVersion {
+            crate_name: crate_name.to_string(),
+            version: version.clone(),
+        })?;
+
+        let mut package_info: PackageInfo =
+            serde_json::from_str(&row.try_get::<String, _>("package_info")?)?;
+        package_info.yanked = yanked;
+
+        sqlx::query(
+            "UPDATE crate_versions SET package_info = $1 WHERE crate_name = $2 AND version = $3",
+        )
+        .bind(serde_json::to_string(&package_info)?)
+        .bind(crate_name)
+        .bind(version.to_string())
+        .execute(&mut *tx)
+        .await?;
+
+        update_index_state(&mut tx, crate_name).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn list_owners(&self, crate_name: &str) -> AppResult<Vec<User>> {
+        let mut tx = self.pool.begin().await?;
+        if get_crate_details(&mut tx, crate_name).await?.is_none() {
+            return Err(AppError::NonExistentPackageInfo(crate_name.to_string()));
+        }
+
+        let rows = sqlx::query(
+            "SELECT u.id, u.login, u.given_name, u.family_name FROM crate_owners o \
+             JOIN users u ON u.id = o.user_id WHERE o.crate_name = $1 ORDER BY u.id",
+        )
+        .bind(crate_name)
+        .fetch_all(&mut *tx)
+        .await?;
+
+        rows.iter().map(super::user::user_from_row).collect()
+    }
+
+    async fn add_owners(&self, crate_name: &str, user_ids: Vec<String>) -> AppResult<()> {
+        let new_owners = user_ids
+            .iter()
+            .map(|id| id.parse::<UserId>())
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut tx = self.pool.begin().await?;
+        if get_crate_details(&mut tx, crate_name).await?.is_none() {
+            return Err(AppError::NonExistentPackageInfo(crate_name.to_string()));
+        }
+
+        for owner in new_owners {
+            sqlx::query(
+                "INSERT INTO crate_owners (crate_name, user_id) VALUES ($1, $2) \
+                 ON CONFLICT (crate_name, user_id) DO NOTHING",
+            )
+            .bind(crate_name)
+            .bind(owner as i64)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn get_crate_summary(&self, crate_name: &str) -> AppResult<Option<CrateSummary>> {
+        let mut tx = self.pool.begin().await?;
+        get_crate_details(&mut tx, crate_name).await
+    }
+
+    async fn resolve_crate_name(&self, crate_name: &str) -> AppResult<Option<String>> {
+        let row = sqlx::query("SELECT name FROM crates WHERE canonical_name = $1")
+            .bind(canonical_crate_name(crate_name))
+            .fetch_optional(&self.pool)
+            .await?;
+
+        let name = match row {
+            Some(row) => Some(row.try_get("name")?),
+            None => None,
+        };
+
+        Ok(name)
+    }
+
+    async fn get_all_crate_details(
+        &self,
+        filter: Option<String>,
+        limit: usize,
+    ) -> AppResult<Vec<CrateSummary>> {
+        let prefix = filter.unwrap_or_default();
+        let mut tx = self.pool.begin().await?;
+        let names: Vec<String> = sqlx::query(
+            "SELECT name FROM crates WHERE substr(name, 1, length($1)) = $1 ORDER BY name LIMIT $2",
+        )
+        .bind(prefix)
+        .bind(limit as i64)
+        .fetch_all(&mut *tx)
+        .await?
+        .iter()
+        .map(|row| row.try_get("name"))
+        .collect::<Result<_, _>>()?;
+
+        let mut crates = Vec::with_capacity(names.len());
+        for name in names {
+            if let Some(summary) = get_crate_details(&mut tx, &name).await? {
+                crates.push(summary);
+            }
+        }
+
+        Ok(crates)
+    }
+
+    async fn get_crate_metadata(
+        &self,
+        crate_name: &str,
+        version: &Version,
+    ) -> AppResult<Option<Metadata>> {
+        let row = sqlx::query(
+            "SELECT metadata FROM crate_metadata WHERE crate_name = $1 AND version = $2",
+        )
+        .bind(crate_name)
+        .bind(version.to_string())
+        .fetch_optional(&self.pool)
+        .await?;
+
+        let metadata = match row {
+            Some(row) => Some(serde_json::from_str(
+                &row.try_get::<String, _>("metadata")?,
+            )?),
+            None => None,
+        };
+
+        Ok(metadata)
+    }
+
+    async fn list_crate_versions(&self, crate_name: &str) -> AppResult<Vec<Version>> {
+        let infos = get_package_infos(&self.pool, crate_name).await?;
+        Ok(infos.into_iter().map(|info| info.vers).collect())
+    }
+
+    async fn list_package_infos(&self, crate_name: &str) -> AppResult<Vec<PackageInfo>> {
+        get_package_infos(&self.pool, crate_name).await
+    }
+
+    async fn get_crate_limits(&self, crate_name: &str) -> AppResult<CrateLimits> {
+        let row = sqlx::query("SELECT limits FROM crate_limits WHERE crate_name = $1")
+            .bind(crate_name)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        let limits = match row {
+            Some(row) => serde_json::from_str(&row.try_get::<String, _>("limits")?)?,
+            None => CrateLimits::default(),
+        };
+
+        Ok(limits)
+    }
+
+    async fn set_crate_limits(&self, crate_name: &str, limits: CrateLimits) -> AppResult<()> {
+        sqlx::query(
+            "INSERT INTO crate_limits (crate_name, limits) VALUES ($1, $2) \
+             ON CONFLICT (crate_name) DO UPDATE SET limits = excluded.limits",
+        )
+        .bind(crate_name)
+        .bind(serde_json::to_string(&limits)?)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn record_download(
+        &self,
+        crate_name: &str,
+        version: &Version,
+        date: Date,
+    ) -> AppResult<()> {
+        sqlx::query(
+            "INSERT INTO crate_downloads (crate_name, version, date, downloads) \
+             VALUES ($1, $2, $3, 1) \
+             ON CONFLICT (crate_name, version, date) DO UPDATE \
+             SET downloads = crate_downloads.downloads + 1",
+        )
+        .bind(crate_name)
+        .bind(version.to_string())
+        .bind(format_date(date))
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn get_download_counts(
+        &self,
+        crate_name: &str,
+        version: Option<&Version>,
+    ) -> AppResult<Vec<DailyDownloads>> {
+        let rows = match version {
+            Some(version) => {
+                sqlx::query(
+                    "SELECT date, downloads FROM crate_downloads \
+                     WHERE crate_name = $1 AND version = $2",
+                )
+                .bind(crate_name)
+                .bind(version.to_string())
+                .fetch_all(&self.pool)
+                .await?
+            }
+            None => {
+                sqlx::query("SELECT date, downloads FROM crate_downloads WHERE crate_name = $1")
+                    .bind(crate_name)
+                    .fetch_all(&self.pool)
+                    .await?
+            }
+        };
+
+        let counts = rows
+            .iter()
+            .map(|row| {
+                let date = parse_date(&row.try_get::<String, _>("date")?)?;
+                Ok((date, row.try_get::<i64, _>("downloads")? as u64))
+            })
+            .collect::<AppResult<Vec<_>>>()?;
+
+        Ok(per_day(counts))
+    }
+}
+
+async fn get_package_infos(
+    executor: impl sqlx::Executor<'_, Database = Any>,
+    crate_name: &str,
+) -> AppResult<Vec<PackageInfo>> {
+    let rows =
+        sqlx::query("SELECT package_info, published_at FROM crate_versions WHERE crate_name = $1")
+            .bind(crate_name)
+            .fetch_all(executor)
+            .await?;
+
+    let entries = rows
+        .iter()
+        .map(|row| {
+            let info = serde_json::from_str(&row.try_get::<String, _>("package_info")?)?;
+            Ok((row.try_get::<i64, _>("published_at")? as u64, info))
+        })
+        .collect::<AppResult<Vec<_>>>()?;
+
+    Ok(in_publish_order(entries))
+}
+
+/// Locks the crate's row until the transaction ends, so concurrent changes to the crate
+/// compute its index state one after the other, each from the versions of the last.
+///
+/// SQLite has no `SELECT … FOR UPDATE`, but a no-op update locks on both databases.
+async fn lock_crate(tx: &mut Transaction<'_, Any>, crate_name: &str) -> AppResult<()> {
+    sqlx::query("UPDATE crates SET max_version = max_version WHERE name = $1")
+        .bind(crate_name)
+        .execute(&mut **tx)
+        .await?;
+
+    Ok(())
+}
+
+/// Has to run after `lock_crate`, or a concurrent change can write a state computed
+/// from versions that miss this transaction's.
+async fn update_index_state(tx: &mut Transaction<'_, Any>, crate_name: &str) -> AppResult<()> {
+    let infos = get_package_infos(&mut **tx, crate_name).await?;
+    let state = IndexState::modified_now(&infos);
+
+    sqlx::query(
+        "INSERT INTO crate_index_states (crate_name, etag, last_modified) VALUES ($1, $2, $3) \
+         ON CONFLICT (crate_name) DO UPDATE \
+         SET etag = excluded.etag, last_modified = excluded.last_modified",
+    )
+    .bind(crate_name)
+    .bind(state.etag)
+    .bind(state.last_modified as i64)
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(())
+}
+
+async fn get_crate_details(
+    tx: &mut Transaction<'_, Any>,
+    crate_name: &str,
+) -> AppResult<Option<CrateSummary>> {
+    let row = sqlx::query("SELECT name, max_version, description FROM crates WHERE name = $1")
+        .bind(crate_name)
+        .fetch_optional(&mut **tx)
+        .await?;
+
+    let row = match row {
+        Some(row) => row,
+        None => return Ok(None),
+    };
+
+    let owners =
+        sqlx::query("SELECT user_id FROM crate_owners WHERE crate_name = $1 ORDER BY user_id")
+            .bind(crate_name)
+            .fetch_all(&mut **tx)
+            .await?
+            .iter()
+            .map(|row| {
+                let id: i64 = row.try_get("user_id")?;
+                UserId::try_from(id).map_err(|_| internal_error())
+            })
+            .collect::<AppResult<Vec<_>>>()?;
+
+    let max_version: String = row.try_get("max_version")?;
+    Ok(Some(CrateSummary {
+        name: row.try_get("name")?,
+        owners,
+        max_version: Version::from_str(&max_version).map_err(|_| internal_error())?,
+        description: row.try_get("description")?,
+    }))
+}
+
+pub(super) fn is_unique_violation(err: &sqlx::Error) -> bool {
+    err.as_database_error()
+        .map(|db_err| db_err.is_unique_violation())
+        .unwrap_or(false)
+}
@@ -0,0 +1,107 @@
+use sqlx::any::AnyRow;
+use sqlx::Row;
+use time::OffsetDateTime;
+
+use crate::error::{internal_error, AppError, AppResult};
+use crate::models::public_key::PublicKey;
+use crate::models::token::{from_unix_timestamp, now};
+use crate::models::user::UserId;
+use crate::repository::base::PublicKeyRepository;
+use crate::repository::SqlRepository;
+
+#[async_trait::async_trait]
+impl PublicKeyRepository for SqlRepository {
+    async fn store_public_key(
+        &self,
+        key_id: String,
+        public_key: String,
+        name: String,
+        user_id: u32,
+    ) -> AppResult<PublicKey> {
+        let created_at = now();
+        let result = sqlx::query(
+            "INSERT INTO public_keys (key_id, public_key, name, user_id, created_at) \
+             VALUES ($1, $2, $3, $4, $5) ON CONFLICT (key_id) DO NOTHING",
+        )
+        .bind(&key_id)
+        .bind(&public_key)
+        .bind(&name)
+        .bind(user_id as i64)
+        .bind(created_at.unix_timestamp())
+        .execute(&self.pool)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Err(AppError::DuplicatePublicKey(key_id));
+        }
+
+        Ok(PublicKey {
+            key_id,
+            public_key,
+            name,
+            user_id,
+            created_at,
+        })
+    }
+
+    async fn delete_public_key(&self, user_id: u32, key_id: String) -> AppResult<()> {
+        sqlx::query("DELETE FROM public_keys WHERE user_id = $1 AND key_id = $2")
+            .bind(user_id as i64)
+            .bind(key_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn list_public_keys(&self, user_id: u32) -> AppResult<Vec<PublicKey>> {
+        let rows = sqlx::query(
+            "SELECT key_id, public_key, name, user_id, created_at \
+             FROM public_keys WHERE user_id = $1",
+        )
+        .bind(user_id as i64)
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.iter().map(public_key_from_row).collect()
+    }
+
+    async fn get_public_key(&self, key_id: &str) -> AppResult<Option<PublicKey>> {
+        let row = sqlx::query(
+            "SELECT key_id, public_key, name, user_id, created_at \
+             FROM public_keys WHERE key_id = $1",
+        )
+        .bind(key_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        row.as_ref().map(public_key_from_row).transpose()
+    }
+
+    async fn use_nonce(&self, nonce: &str, expires_at: OffsetDateTime) -> AppResult<bool> {
+        sqlx::query("DELETE FROM used_nonces WHERE expires_at <= $1")
+            .bind(now().unix_timestamp())
+            .execute(&self.pool)
+            .await?;
+        let result = sqlx::query(
+            "INSERT INTO used_nonces (nonce, expires_at) VALUES ($1, $2) \
+             ON CONFLICT (nonce) DO NOTHING",
+        )
+        .bind(nonce)
+        .bind(expires_at.unix_timestamp())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+}
+
+fn public_key_from_row(row: &AnyRow) -> AppResult<PublicKey> {
+    let user_id: i64 = row.try_get("user_id")?;
+    Ok(PublicKey {
+        key_id: row.try_get("key_id")?,
+        public_key: row.try_get("public_key")?,
+        name: row.try_get("name")?,
+        user_id: UserId::try_from(user_id).map_err(|_| internal_error())?,
+        created_at: from_unix_timestamp(row.try_get("created_at")?)?,
+    })
+}
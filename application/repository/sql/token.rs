@@ -0,0 +1,120 @@
+use base64::Engine;
+use sqlx::any::AnyRow;
+use sqlx::{Row, TypeInfo, ValueRef};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use crate::auth::hash;
+use crate::error::{internal_error, AppResult};
+use crate::models::token::{from_unix_timestamp, now, Token, TokenScopes};
+use crate::models::user::UserId;
+use crate::repository::base::TokenRepository;
+use crate::repository::SqlRepository;
+
+#[async_trait::async_trait]
+impl TokenRepository for SqlRepository {
+    async fn store_auth_token(
+        &self,
+        token: &[u8],
+        name: String,
+        user_id: u32,
+        scopes: TokenScopes,
+        expires_at: Option<OffsetDateTime>,
+    ) -> AppResult<Token> {
+        let token_id = Uuid::new_v4().hyphenated().to_string();
+        let created_at = now();
+        sqlx::query(
+            "INSERT INTO tokens (token_hash, token_id, name, user_id, scopes, created_at, expires_at) \
+             VALUES ($1, $2, $3, $4, $5, $6, $7)",
+        )
+        .bind(get_token_hash(token))
+        .bind(&token_id)
+        .bind(&name)
+        .bind(user_id as i64)
+        .bind(serde_json::to_string(&scopes)?)
+        .bind(created_at.unix_timestamp())
+        .bind(expires_at.map(|expires_at| expires_at.unix_timestamp()))
+        .execute(&self.pool)
+        .await?;
+
+        Ok(Token {
+            name,
+            user_id,
+            token_id,
+            scopes,
+            created_at: Some(created_at),
+            expires_at,
+            last_used_at: None,
+        })
+    }
+
+    async fn delete_auth_token(&self, user_id: u32, token_id: String) -> AppResult<()> {
+        sqlx::query("DELETE FROM tokens WHERE user_id = $1 AND token_id = $2")
+            .bind(user_id as i64)
+            .bind(token_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn list_auth_tokens(&self, user_id: u32) -> AppResult<Vec<Token>> {
+        let rows = sqlx::query(
+            "SELECT name, user_id, token_id, scopes, created_at, expires_at, last_used_at \
+             FROM tokens WHERE user_id = $1",
+        )
+        .bind(user_id as i64)
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.iter().map(token_from_row).collect()
+    }
+
+    async fn get_auth_token(&self, token: &[u8]) -> AppResult<Option<Token>> {
+        let row = sqlx::query(
+            "SELECT name, user_id, token_id, scopes, created_at, expires_at, last_used_at \
+             FROM tokens WHERE token_hash = $1",
+        )
+        .bind(get_token_hash(token))
+        .fetch_optional(&self.pool)
+        .await?;
+
+        row.as_ref().map(token_from_row).transpose()
+    }
+
+    async fn record_token_use(&self, token: &[u8], used_at: OffsetDateTime) -> AppResult<()> {
+        sqlx::query("UPDATE tokens SET last_used_at = $1 WHERE token_hash = $2")
+            .bind(used_at.unix_timestamp())
+            .bind(get_token_hash(token))
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+}
+
+/// Only the hash of the token is stored, the same way the DynamoDB repository does it.
+fn get_token_hash(token: &[u8]) -> String {
+    base64::engine::general_purpose::STANDARD.encode(hash(token))
+}
+
+fn token_from_row(row: &AnyRow) -> AppResult<Token> {
+    let user_id: i64 = row.try_get("user_id")?;
+    Ok(Token {
+        name: row.try_get("name")?,
+        user_id: UserId::try_from(user_id).map_err(|_| internal_error())?,
+        token_id: row.try_get("token_id")?,
+        scopes: serde_json::from_str(&row.try_get::<String, _>("scopes")?)?,
+        created_at: timestamp_from_row(row, "created_at")?,
+        expires_at: timestamp_from_row(row, "expires_at")?,
+        last_used_at: timestamp_from_row(row, "last_used_at")?,
+    })
+}
+
+fn timestamp_from_row(row: &AnyRow, column: &str) -> AppResult<Option<OffsetDateTime>> {
+    // the `Any` driver never reports a value as null, so `Option`s can't be decoded
+    if row.try_get_raw(column)?.type_info().name() == "NULL" {
+        return Ok(None);
+    }
+    from_unix_timestamp(row.try_get(column)?).map(Some)
+}
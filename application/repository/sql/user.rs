@@ -0,0 +1,91 @@
+use sqlx::any::AnyRow;
+use sqlx::Row;
+use tracing::info;
+
+use crate::error::{internal_error, AppResult};
+use crate::models::user::{CognitoUserData, User, UserId};
+use crate::repository::base::UserRepository;
+use crate::repository::SqlRepository;
+
+#[async_trait::async_trait]
+impl UserRepository for SqlRepository {
+    async fn update_or_create_user(&self, user_data: CognitoUserData) -> AppResult<User> {
+        let mut tx = self.pool.begin().await?;
+        let row =
+            sqlx::query("SELECT id, login, given_name, family_name FROM users WHERE login = $1")
+                .bind(&user_data.login)
+                .fetch_optional(&mut *tx)
+                .await?;
+
+        let user = match row {
+            None => {
+                info!("user not found, creating new user");
+                let current_id: i64 = sqlx::query("SELECT COALESCE(MAX(id), 0) AS id FROM users")
+                    .fetch_one(&mut *tx)
+                    .await?
+                    .try_get("id")?;
+                let next_id = UserId::try_from(current_id + 1).map_err(|_| internal_error())?;
+                info!("next available ID is {}", next_id);
+
+                let user = user_data.into_user(next_id);
+                sqlx::query(
+                    "INSERT INTO users (id, login, given_name, family_name) VALUES ($1, $2, $3, $4)",
+                )
+                .bind(user.id as i64)
+                .bind(&user.login)
+                .bind(&user.given_name)
+                .bind(&user.family_name)
+                .execute(&mut *tx)
+                .await?;
+
+                user
+            }
+            Some(row) => {
+                let user = user_from_row(&row)?;
+
+                // if the existing user data is out of sync, update it
+                let existing_user_data: CognitoUserData = user.clone().into();
+                if existing_user_data != user_data {
+                    sqlx::query("UPDATE users SET given_name = $1, family_name = $2 WHERE id = $3")
+                        .bind(&user_data.given_name)
+                        .bind(&user_data.family_name)
+                        .bind(user.id as i64)
+                        .execute(&mut *tx)
+                        .await?;
+                }
+
+                user
+            }
+        };
+
+        tx.commit().await?;
+        Ok(user)
+    }
+
+    async fn get_user_by_id(&self, user_id: UserId) -> AppResult<Option<User>> {
+        let row = sqlx::query("SELECT id, login, given_name, family_name FROM users WHERE id = $1")
+            .bind(user_id as i64)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        row.as_ref().map(user_from_row).transpose()
+    }
+
+    async fn get_users(&self) -> AppResult<Vec<User>> {
+        let rows = sqlx::query("SELECT id, login, given_name, family_name FROM users ORDER BY id")
+            .fetch_all(&self.pool)
+            .await?;
+
+        rows.iter().map(user_from_row).collect()
+    }
+}
+
+pub(super) fn user_from_row(row: &AnyRow) -> AppResult<User> {
+    let id: i64 = row.try_get("id")?;
+    Ok(User {
+        id: UserId::try_from(id).map_err(|_| internal_error())?,
+        login: row.try_get("login")?,
+        given_name: row.try_get("given_name")?,
+        family_name: row.try_get("family_name")?,
+    })
+}
@@ -1,27 +1,35 @@
-use crate::auth::token_authenticator;
+use crate::auth::{token_authenticator, JwtVerifier};
 use crate::cargo_api::config::get_config_json;
 use crate::cargo_api::download::download_crate;
 use crate::cargo_api::index::{
-    get_info_for_long_name_crate, get_info_for_short_name_crate, get_info_for_three_letter_crate,
+    get_info_for_long_name_crate, get_info_for_one_letter_crate, get_info_for_three_letter_crate,
+    get_info_for_two_letter_crate,
 };
 use crate::cargo_api::me::redirect_for_token;
 use crate::cargo_api::owners::{add_owners, list_owners};
 use crate::cargo_api::publish::publish_crate_handler;
 use crate::cargo_api::unyank::unyank;
 use crate::cargo_api::yank::yank;
+use crate::config::{AuthMode, RaktarConfig};
 use crate::graphql::handler::{graphiql, graphql_handler};
 use crate::graphql::schema::build_schema;
 use crate::repository::DynRepository;
 use crate::storage::DynCrateStorage;
 use axum::routing::{delete, get, put, Router};
 use axum::Extension;
+use std::sync::Arc;
 
-pub type AppState = (DynRepository, DynCrateStorage);
+pub type AppState = (DynRepository, DynCrateStorage, Arc<RaktarConfig>);
 
-pub fn build_router(repository: DynRepository, storage: DynCrateStorage) -> Router {
-    let core_router = build_core_router(repository.clone());
-    let graphql_router = build_graphql_router(repository.clone());
-    let state = (repository, storage);
+pub fn build_router(
+    repository: DynRepository,
+    storage: DynCrateStorage,
+    config: Arc<RaktarConfig>,
+) -> Router {
+    let graphql_router = build_graphql_router(repository.clone(), storage.clone(), config.clone());
+    let auth_mode = config.auth.mode;
+    let state = (repository, storage, config);
+    let core_router = build_core_router(state.clone(), auth_mode);
 
     Router::new()
         .route("/config.json", get(get_config_json))
@@ -31,7 +39,16 @@ pub fn build_router(repository: DynRepository, storage: DynCrateStorage) -> Rout
         .with_state(state)
 }
 
-fn build_core_router(repository: DynRepository) -> Router<AppState> {
+fn build_core_router(state: AppState, auth_mode: AuthMode) -> Router<AppState> {
+    let read_router = build_read_router();
+    let read_router = match auth_mode {
+        AuthMode::Required => read_router.layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            token_authenticator,
+        )),
+        AuthMode::AnonymousRead => read_router,
+    };
+
     Router::new()
         .route("/api/v1/crates/new", put(publish_crate_handler))
         .route(
@@ -40,12 +57,22 @@ fn build_core_router(repository: DynRepository) -> Router<AppState> {
         )
         .route("/api/v1/crates/:crate_name/:version/yank", delete(yank))
         .route("/api/v1/crates/:crate_name/:version/unyank", put(unyank))
+        .layer(axum::middleware::from_fn_with_state(
+            state,
+            token_authenticator,
+        ))
+        .merge(read_router)
+}
+
+/// The index and crate downloads, everything cargo needs to build with the registry's crates.
+fn build_read_router() -> Router<AppState> {
+    Router::new()
         .route(
             "/api/v1/crates/:crate_name/:version/download",
             get(download_crate),
         )
-        .route("/1/:crate_name", get(get_info_for_short_name_crate))
-        .route("/2/:crate_name", get(get_info_for_short_name_crate))
+        .route("/1/:crate_name", get(get_info_for_one_letter_crate))
+        .route("/2/:crate_name", get(get_info_for_two_letter_crate))
         .route(
             "/3/:first_letter/:crate_name",
             get(get_info_for_three_letter_crate),
@@ -54,15 +81,17 @@ fn build_core_router(repository: DynRepository) -> Router<AppState> {
             "/:first_two/:second_two/:crate_name",
             get(get_info_for_long_name_crate),
         )
-        .layer(axum::middleware::from_fn_with_state(
-            repository,
-            token_authenticator,
-        ))
 }
 
-fn build_graphql_router(repository: DynRepository) -> Router<AppState> {
-    let schema = build_schema(repository);
+fn build_graphql_router(
+    repository: DynRepository,
+    storage: DynCrateStorage,
+    config: Arc<RaktarConfig>,
+) -> Router<AppState> {
+    let verifier = Arc::new(JwtVerifier::new(&config.auth.jwt));
+    let schema = build_schema(repository, storage, config);
     Router::new()
         .route("/", get(graphiql).post(graphql_handler))
         .layer(Extension(schema))
+        .layer(Extension(verifier))
 }
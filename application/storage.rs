@@ -1,5 +1,7 @@
 mod base;
+mod filesystem;
 mod s3;
 
-pub use base::{CrateStorage, DynCrateStorage};
+pub use base::{ByteStream, CrateData, CrateStorage, DynCrateStorage};
+pub use filesystem::FilesystemStorage;
 pub use s3::S3Storage;
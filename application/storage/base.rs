@@ -1,14 +1,70 @@
+use std::pin::Pin;
 use std::sync::Arc;
 
+use bytes::Bytes;
+use futures::{Stream, StreamExt, TryStreamExt};
 use semver::Version;
 
 use crate::error::AppResult;
 
+/// The bytes of a crate file as they are read or written, so they never have to be held
+/// in memory all at once.
+pub type ByteStream = Pin<Box<dyn Stream<Item = std::io::Result<Bytes>> + Send>>;
+
+/// A crate file together with its size, which some backends need before they start writing.
+pub struct CrateData {
+    pub content_length: u64,
+    pub stream: ByteStream,
+}
+
+impl CrateData {
+    pub fn new(content_length: u64, stream: ByteStream) -> Self {
+        Self {
+            content_length,
+            stream,
+        }
+    }
+
+    pub fn from_bytes(data: impl Into<Bytes>) -> Self {
+        let data = data.into();
+        Self::new(
+            data.len() as u64,
+            futures::stream::once(async { Ok(data) }).boxed(),
+        )
+    }
+
+    /// Reads the whole crate file into memory.
+    pub async fn into_bytes(self) -> std::io::Result<Bytes> {
+        let chunks: Vec<Bytes> = self.stream.try_collect().await?;
+        Ok(chunks.concat().into())
+    }
+}
+
 #[async_trait::async_trait]
 pub trait CrateStorage {
-    async fn store_crate(&self, crate_name: &str, version: Version, data: Vec<u8>)
-        -> AppResult<()>;
-    async fn get_crate(&self, crate_name: &str, version: Version) -> AppResult<Vec<u8>>;
+    /// Stores a new crate file, failing with `DuplicateCrateVersion` when one is already
+    /// stored for the version, so concurrent publishes can't overwrite each other's files.
+    async fn store_crate(
+        &self,
+        crate_name: &str,
+        version: Version,
+        data: CrateData,
+    ) -> AppResult<()>;
+    async fn get_crate(&self, crate_name: &str, version: Version) -> AppResult<CrateData>;
+    /// Removes a stored crate, e.g. when it turns out to be invalid after it was written.
+    ///
+    /// Only the publish that stored a crate file may remove it again.
+    async fn delete_crate(&self, crate_name: &str, version: Version) -> AppResult<()>;
+
+    /// A URL clients can download the crate from directly, so its bytes don't have to be
+    /// proxied through the registry. Backends that can't provide one return `None`.
+    async fn get_download_url(
+        &self,
+        _crate_name: &str,
+        _version: &Version,
+    ) -> AppResult<Option<String>> {
+        Ok(None)
+    }
 }
 
 pub type DynCrateStorage = Arc<dyn CrateStorage + Send + Sync>;
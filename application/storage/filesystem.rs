@@ -0,0 +1,340 @@
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use futures::{StreamExt, TryStreamExt};
+use semver::Version;
+use tokio::io::AsyncWriteExt;
+use tokio_util::io::ReaderStream;
+use tracing::error;
+use url::Url;
+
+use crate::error::{AppError, AppResult};
+use crate::storage::{CrateData, CrateStorage};
+
+/// Stores crates on the local filesystem, for deployments that have no S3 available.
+///
+/// The layout under the root directory mirrors the S3 keys used by `S3Storage`.
+#[derive(Clone, Debug)]
+pub struct FilesystemStorage {
+    root: PathBuf,
+    prefix: String,
+    /// Where the root directory is served from, if clients can download from it directly.
+    public_url: Option<Url>,
+}
+
+impl FilesystemStorage {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            prefix: "crates".to_string(),
+            public_url: None,
+        }
+    }
+
+    pub fn with_public_url(mut self, public_url: Url) -> Self {
+        self.public_url = Some(public_url);
+        self
+    }
+
+    pub fn crate_path(&self, name: &str, version: &Version) -> PathBuf {
+        self.root
+            .join(&self.prefix)
+            .join(name)
+            .join(format!("{}-{}.crate", name, version))
+    }
+}
+
+#[async_trait::async_trait]
+impl CrateStorage for FilesystemStorage {
+    async fn store_crate(
+        &self,
+        crate_name: &str,
+        version: Version,
+        data: CrateData,
+    ) -> AppResult<()> {
+        let path = self.crate_path(crate_name, &version);
+        match write_new_file(&path, data).await {
+            Ok(()) => Ok(()),
+            Err(err) if err.kind() == ErrorKind::AlreadyExists => {
+                Err(AppError::DuplicateCrateVersion {
+                    crate_name: crate_name.to_string(),
+                    version,
+                })
+            }
+            Err(err) => {
+                let error_message = err.to_string();
+                error!(error_message, "failed to write crate to filesystem");
+                Err(anyhow::anyhow!("unexpected error in storing crate").into())
+            }
+        }
+    }
+
+    async fn get_crate(&self, crate_name: &str, version: Version) -> AppResult<CrateData> {
+        let path = self.crate_path(crate_name, &version);
+        match open_crate(&path).await {
+            Ok(data) => Ok(data),
+            Err(err) if err.kind() == ErrorKind::NotFound => {
+                Err(AppError::NonExistentCrateVersion {
+                    crate_name: crate_name.to_string(),
+                    version,
+                })
+            }
+            Err(err) => {
+                let error_message = err.to_string();
+                error!(error_message, "failed to read crate from filesystem");
+                Err(anyhow::anyhow!("unexpected error in getting crate from filesystem").into())
+            }
+        }
+    }
+
+    async fn delete_crate(&self, crate_name: &str, version: Version) -> AppResult<()> {
+        let path = self.crate_path(crate_name, &version);
+        match tokio::fs::remove_file(&path).await {
+            Ok(()) => Ok(()),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(()),
+            Err(err) => {
+                let error_message = err.to_string();
+                error!(error_message, "failed to delete crate from filesystem");
+                Err(anyhow::anyhow!("unexpected error in deleting crate from filesystem").into())
+            }
+        }
+    }
+
+    async fn get_download_url(
+        &self,
+        crate_name: &str,
+        version: &Version,
+    ) -> AppResult<Option<String>> {
+        let url = self.public_url.as_ref().map(|public_url| {
+            format!(
+                "{}/{}/{}/{}-{}.crate",
+                public_url.as_str().trim_end_matches('/'),
+                self.prefix,
+                crate_name,
+                crate_name,
+                version
+            )
+        });
+
+        Ok(url)
+    }
+}
+
+async fn open_crate(path: &Path) -> std::io::Result<CrateData> {
+    let file = tokio::fs::File::open(path).await?;
+    let content_length = file.metadata().await?.len();
+    Ok(CrateData::new(
+        content_length,
+        ReaderStream::new(file).boxed(),
+    ))
+}
+
+/// Writes the data into a new file at the target, removing it again if the write fails.
+///
+/// Creating the file fails with `AlreadyExists` when the target is already there, so an
+/// existing file is never replaced. Readers can't see a partially written crate, because
+/// its version is only added to the index once the file is complete.
+async fn write_new_file(path: &Path, mut data: CrateData) -> std::io::Result<()> {
+    let parent = path
+        .parent()
+        .ok_or_else(|| std::io::Error::new(ErrorKind::InvalidInput, "path has no parent"))?;
+    tokio::fs::create_dir_all(parent).await?;
+
+    let mut file = tokio::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(path)
+        .await?;
+    if let Err(err) = write_data(&mut file, &mut data).await {
+        let _ = tokio::fs::remove_file(path).await;
+        return Err(err);
+    }
+
+    Ok(())
+}
+
+async fn write_data(file: &mut tokio::fs::File, data: &mut CrateData) -> std::io::Result<()> {
+    let mut written = 0;
+    while let Some(chunk) = data.stream.try_next().await? {
+        file.write_all(&chunk).await?;
+        written += chunk.len() as u64;
+    }
+    if written != data.content_length {
+        return Err(std::io::Error::new(
+            ErrorKind::UnexpectedEof,
+            format!("expected {} bytes, got {}", data.content_length, written),
+        ));
+    }
+
+    file.sync_all().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    fn build_storage() -> FilesystemStorage {
+        let root = std::env::temp_dir().join(format!("raktar-{}", Uuid::new_v4()));
+        FilesystemStorage::new(root)
+    }
+
+    #[test]
+    fn test_crate_path_matches_s3_layout() {
+        let storage = FilesystemStorage::new("/var/lib/raktar");
+        let version = Version::new(0, 1, 2);
+
+        let path = storage.crate_path("testcrate", &version);
+        assert_eq!(
+            path,
+            PathBuf::from("/var/lib/raktar/crates/testcrate/testcrate-0.1.2.crate")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_store_and_get_crate() {
+        let storage = build_storage();
+        let version = Version::new(0, 1, 0);
+
+        storage
+            .store_crate(
+                "testcrate",
+                version.clone(),
+                CrateData::from_bytes(vec![1, 2, 3]),
+            )
+            .await
+            .unwrap();
+        let data = storage
+            .get_crate("testcrate", version.clone())
+            .await
+            .unwrap();
+
+        assert_eq!(data.content_length, 3);
+        assert_eq!(data.into_bytes().await.unwrap(), vec![1, 2, 3]);
+
+        storage
+            .delete_crate("testcrate", version.clone())
+            .await
+            .unwrap();
+        let result = storage.get_crate("testcrate", version).await;
+        assert!(matches!(
+            result,
+            Err(AppError::NonExistentCrateVersion { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_existing_crate_is_not_overwritten() {
+        let storage = build_storage();
+        let version = Version::new(0, 1, 0);
+        storage
+            .store_crate(
+                "testcrate",
+                version.clone(),
+                CrateData::from_bytes(vec![1, 2, 3]),
+            )
+            .await
+            .unwrap();
+
+        let result = storage
+            .store_crate(
+                "testcrate",
+                version.clone(),
+                CrateData::from_bytes(vec![4, 5, 6]),
+            )
+            .await;
+
+        assert!(matches!(
+            result,
+            Err(AppError::DuplicateCrateVersion { .. })
+        ));
+        let data = storage.get_crate("testcrate", version).await.unwrap();
+        assert_eq!(data.into_bytes().await.unwrap(), vec![1, 2, 3]);
+    }
+
+    #[tokio::test]
+    async fn test_truncated_stream_is_not_stored() {
+        let storage = build_storage();
+        let version = Version::new(0, 1, 0);
+        let data = CrateData::new(
+            10,
+            futures::stream::once(async { Ok(vec![1, 2, 3].into()) }).boxed(),
+        );
+
+        let result = storage
+            .store_crate("testcrate", version.clone(), data)
+            .await;
+
+        assert!(result.is_err());
+        let result = storage.get_crate("testcrate", version).await;
+        assert!(matches!(
+            result,
+            Err(AppError::NonExistentCrateVersion { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_failed_write_can_be_retried() {
+        let storage = build_storage();
+        let version = Version::new(0, 1, 0);
+        let data = CrateData::new(
+            10,
+            futures::stream::once(async { Ok(vec![1, 2, 3].into()) }).boxed(),
+        );
+        let result = storage
+            .store_crate("testcrate", version.clone(), data)
+            .await;
+        assert!(result.is_err());
+
+        storage
+            .store_crate(
+                "testcrate",
+                version.clone(),
+                CrateData::from_bytes(vec![1, 2, 3]),
+            )
+            .await
+            .unwrap();
+
+        let data = storage
+            .get_crate("testcrate", version.clone())
+            .await
+            .unwrap();
+        assert_eq!(data.into_bytes().await.unwrap(), vec![1, 2, 3]);
+        let path = storage.crate_path("testcrate", &version);
+        let files = std::fs::read_dir(path.parent().unwrap()).unwrap().count();
+        assert_eq!(files, 1);
+    }
+
+    #[tokio::test]
+    async fn test_missing_crate_is_reported_as_non_existent_version() {
+        let storage = build_storage();
+
+        let result = storage.get_crate("testcrate", Version::new(1, 0, 0)).await;
+
+        assert!(matches!(
+            result,
+            Err(AppError::NonExistentCrateVersion { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_download_url_needs_a_public_url() {
+        let version = Version::new(0, 1, 2);
+        let storage = FilesystemStorage::new("/var/lib/raktar");
+        let url = storage
+            .get_download_url("testcrate", &version)
+            .await
+            .unwrap();
+        assert_eq!(url, None);
+
+        let storage = storage.with_public_url("https://static.raktar.io/".parse().unwrap());
+        let url = storage
+            .get_download_url("testcrate", &version)
+            .await
+            .unwrap();
+        assert_eq!(
+            url.as_deref(),
+            Some("https://static.raktar.io/crates/testcrate/testcrate-0.1.2.crate")
+        );
+    }
+}
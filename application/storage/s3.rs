@@ -1,10 +1,23 @@
+use std::time::Duration;
+
 use anyhow::anyhow;
+use aws_sdk_s3::error::ProvideErrorMetadata;
 use aws_sdk_s3::operation::get_object::GetObjectError;
+use aws_sdk_s3::presigning::PresigningConfig;
+use aws_sdk_s3::primitives::ByteStream;
 use aws_sdk_s3::Client;
+use aws_smithy_http::body::SdkBody;
+use futures::{StreamExt, TryStreamExt};
+use http::header::IF_NONE_MATCH;
+use http::HeaderValue;
 use semver::Version;
+use tracing::error;
 
 use crate::error::{AppError, AppResult};
-use crate::storage::CrateStorage;
+use crate::storage::{CrateData, CrateStorage};
+
+/// How long a pre-signed download URL stays valid, cargo follows it right away.
+const DOWNLOAD_URL_EXPIRY: Duration = Duration::from_secs(5 * 60);
 
 #[derive(Clone)]
 pub struct S3Storage {
@@ -14,10 +27,8 @@ pub struct S3Storage {
 }
 
 impl S3Storage {
-    pub async fn new() -> Self {
+    pub async fn new(bucket: String) -> Self {
         let aws_config = aws_config::from_env().load().await;
-        let bucket =
-            std::env::var("CRATES_BUCKET_NAME").expect("S3 bucket to be configured in env");
         Self {
             bucket,
             prefix: "crates".to_string(),
@@ -36,24 +47,50 @@ impl CrateStorage for S3Storage {
         &self,
         crate_name: &str,
         version: Version,
-        data: Vec<u8>,
+        data: CrateData,
     ) -> AppResult<()> {
         let key = self.crate_key(crate_name, &version);
-        match self
+        let body = ByteStream::new(SdkBody::from(hyper::Body::wrap_stream(data.stream)));
+        let stored = self
             .client
             .put_object()
             .bucket(&self.bucket)
             .key(key)
-            .body(data.into())
+            .content_length(data.content_length as i64)
+            .body(body)
+            .customize()
+            .await?
+            // only create the object, an existing crate file belongs to another publish
+            .mutate_request(|request| {
+                request
+                    .headers_mut()
+                    .insert(IF_NONE_MATCH, HeaderValue::from_static("*"));
+            })
             .send()
-            .await
-        {
+            .await;
+        match stored {
             Ok(_) => Ok(()),
-            Err(_) => Err(anyhow::anyhow!("unexpected error in storing crate").into()),
+            // 409 is returned while a conflicting upload is still in progress
+            Err(err)
+                if matches!(
+                    err.code(),
+                    Some("PreconditionFailed" | "ConditionalRequestConflict")
+                ) =>
+            {
+                Err(AppError::DuplicateCrateVersion {
+                    crate_name: crate_name.to_string(),
+                    version,
+                })
+            }
+            Err(err) => {
+                let error_message = err.to_string();
+                error!(error_message, "failed to store crate in S3");
+                Err(anyhow::anyhow!("unexpected error in storing crate").into())
+            }
         }
     }
 
-    async fn get_crate(&self, crate_name: &str, version: Version) -> AppResult<Vec<u8>> {
+    async fn get_crate(&self, crate_name: &str, version: Version) -> AppResult<CrateData> {
         let key = self.crate_key(crate_name, &version);
         match self
             .client
@@ -74,12 +111,51 @@ impl CrateStorage for S3Storage {
 
                 Err(mapped_err)
             }
-            Ok(output) => output
-                .body
-                .collect()
-                .await
-                .map_err(|_| anyhow!("failed to collect bytes").into())
-                .map(|data| data.into_bytes().to_vec()),
+            Ok(output) => {
+                let content_length = output.content_length() as u64;
+                let stream = output.body.map_err(std::io::Error::other).boxed();
+                Ok(CrateData::new(content_length, stream))
+            }
         }
     }
+
+    async fn delete_crate(&self, crate_name: &str, version: Version) -> AppResult<()> {
+        let key = self.crate_key(crate_name, &version);
+        self.client
+            .delete_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .send()
+            .await
+            .map_err(|err| {
+                let error_message = err.to_string();
+                error!(error_message, "failed to delete crate from S3");
+                anyhow!("unexpected error in deleting crate from S3")
+            })?;
+
+        Ok(())
+    }
+
+    async fn get_download_url(
+        &self,
+        crate_name: &str,
+        version: &Version,
+    ) -> AppResult<Option<String>> {
+        let presigning_config = PresigningConfig::expires_in(DOWNLOAD_URL_EXPIRY)
+            .map_err(|_| anyhow!("invalid presigning configuration"))?;
+        let request = self
+            .client
+            .get_object()
+            .bucket(&self.bucket)
+            .key(self.crate_key(crate_name, version))
+            .presigned(presigning_config)
+            .await
+            .map_err(|err| {
+                let error_message = err.to_string();
+                error!(error_message, "failed to pre-sign crate download");
+                anyhow!("unexpected error in pre-signing crate download")
+            })?;
+
+        Ok(Some(request.uri().to_string()))
+    }
 }
@@ -30,13 +30,15 @@ class Database(Construct):
             billing_mode=dynamodb.BillingMode.PROVISIONED,
             read_capacity=5,
             write_capacity=1,
+            # used challenges and signed tokens are only kept until they'd be rejected anyway
+            time_to_live_attribute="ttl",
         )
 
     @staticmethod
     def _setup_user_data_gsi(table: dynamodb.Table) -> None:
-        """Create user -> tokens mapping.
+        """Create user -> tokens and public keys mapping.
 
-        This is so that we can list all tokens for the logged-in user.
+        This is so that we can list all tokens and public keys for the logged-in user.
         """
         pk = dynamodb.Attribute(name="user_id", type=dynamodb.AttributeType.NUMBER)
         sk = dynamodb.Attribute(name="pk", type=dynamodb.AttributeType.STRING)
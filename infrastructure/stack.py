@@ -55,6 +55,12 @@ class RaktarStack(Stack):
             sso_metadata_url=settings.sso_metadata_url,
             settings=settings,
         )
+        backend_function.add_environment(
+            "RAKTAR_JWT_ISSUER", user_pool.user_pool_provider_url
+        )
+        backend_function.add_environment(
+            "RAKTAR_JWT_AUDIENCE", user_pool.user_pool_client_id
+        )
         table.grant_read_write_data(backend_function)
         table.grant_read_write_data(pre_token_function)
         bucket.grant_read_write(backend_function)
@@ -45,6 +45,11 @@ class RaktarUserPool(Construct):
         """The ID of the user pool."""
         return self._user_pool.user_pool_id
 
+    @property
+    def user_pool_provider_url(self) -> str:
+        """The issuer of the user pool's tokens."""
+        return self._user_pool.user_pool_provider_url
+
     @property
     def user_pool_client_id(self) -> str:
         """The ID of the application client."""
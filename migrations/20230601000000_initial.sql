@@ -0,0 +1,43 @@
+-- The schema is kept to types both SQLite and PostgreSQL understand,
+-- so the same migrations can be used for either database.
+CREATE TABLE crates (
+    name TEXT PRIMARY KEY,
+    max_version TEXT NOT NULL,
+    description TEXT NOT NULL
+);
+
+CREATE TABLE crate_owners (
+    crate_name TEXT NOT NULL REFERENCES crates (name),
+    user_id BIGINT NOT NULL,
+    PRIMARY KEY (crate_name, user_id)
+);
+
+CREATE TABLE crate_versions (
+    crate_name TEXT NOT NULL REFERENCES crates (name),
+    version TEXT NOT NULL,
+    package_info TEXT NOT NULL,
+    PRIMARY KEY (crate_name, version)
+);
+
+CREATE TABLE crate_metadata (
+    crate_name TEXT NOT NULL REFERENCES crates (name),
+    version TEXT NOT NULL,
+    metadata TEXT NOT NULL,
+    PRIMARY KEY (crate_name, version)
+);
+
+CREATE TABLE users (
+    id BIGINT PRIMARY KEY,
+    login TEXT NOT NULL UNIQUE,
+    given_name TEXT NOT NULL,
+    family_name TEXT NOT NULL
+);
+
+CREATE TABLE tokens (
+    token_hash TEXT PRIMARY KEY,
+    token_id TEXT NOT NULL UNIQUE,
+    name TEXT NOT NULL,
+    user_id BIGINT NOT NULL
+);
+
+CREATE INDEX tokens_user_id ON tokens (user_id);
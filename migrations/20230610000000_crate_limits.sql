@@ -0,0 +1,5 @@
+-- publish limits raised by an admin for a single crate, stored as JSON
+CREATE TABLE crate_limits (
+    crate_name TEXT PRIMARY KEY,
+    limits TEXT NOT NULL
+);
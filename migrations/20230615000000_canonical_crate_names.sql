@@ -0,0 +1,7 @@
+-- cargo treats `-` and `_` the same and crates.io compares names case-insensitively,
+-- so no two crates can share a canonical name
+ALTER TABLE crates ADD COLUMN canonical_name TEXT;
+
+UPDATE crates SET canonical_name = lower(replace(name, '-', '_'));
+
+CREATE UNIQUE INDEX crates_canonical_name ON crates (canonical_name);
@@ -0,0 +1,6 @@
+-- lets clients check whether their copy of an index file is current without reading it
+CREATE TABLE crate_index_states (
+    crate_name TEXT PRIMARY KEY,
+    etag TEXT NOT NULL,
+    last_modified BIGINT NOT NULL
+);
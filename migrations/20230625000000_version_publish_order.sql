@@ -0,0 +1,2 @@
+-- index files list versions in the order they were published
+ALTER TABLE crate_versions ADD COLUMN published_at BIGINT NOT NULL DEFAULT 0;
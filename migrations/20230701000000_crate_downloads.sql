@@ -0,0 +1,8 @@
+-- download counts per version and day, dates are stored as YYYY-MM-DD in UTC
+CREATE TABLE crate_downloads (
+    crate_name TEXT NOT NULL,
+    version TEXT NOT NULL,
+    date TEXT NOT NULL,
+    downloads BIGINT NOT NULL,
+    PRIMARY KEY (crate_name, version, date)
+);
@@ -0,0 +1,3 @@
+-- the endpoints and crates a token is restricted to, stored as JSON, tokens created
+-- before scopes existed are unrestricted
+ALTER TABLE tokens ADD COLUMN scopes TEXT NOT NULL DEFAULT '{}';
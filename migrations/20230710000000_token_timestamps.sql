@@ -0,0 +1,4 @@
+-- Unix timestamps, tokens created before they were recorded have no creation time
+ALTER TABLE tokens ADD COLUMN created_at BIGINT;
+ALTER TABLE tokens ADD COLUMN expires_at BIGINT;
+ALTER TABLE tokens ADD COLUMN last_used_at BIGINT;
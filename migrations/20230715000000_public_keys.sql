@@ -0,0 +1,16 @@
+-- public keys users sign registry requests with, by their PASERK ID
+CREATE TABLE public_keys (
+    key_id TEXT PRIMARY KEY,
+    public_key TEXT NOT NULL,
+    name TEXT NOT NULL,
+    user_id BIGINT NOT NULL,
+    created_at BIGINT NOT NULL
+);
+
+CREATE INDEX public_keys_user_id ON public_keys (user_id);
+
+-- challenges and signed tokens that have been used, kept until they'd be rejected anyway
+CREATE TABLE used_nonces (
+    nonce TEXT PRIMARY KEY,
+    expires_at BIGINT NOT NULL
+);
@@ -0,0 +1,87 @@
+mod common;
+
+use std::sync::Arc;
+
+use axum::body::{Body, HttpBody};
+use axum::http::{Request, StatusCode};
+use axum::response::Response;
+use hyper::service::Service;
+use raktar::config::AuthMode;
+use raktar::repository::{DynRepository, MemoryRepository};
+use raktar::router::build_router;
+use raktar::storage::DynCrateStorage;
+use serde_json::Value;
+
+use common::memory_storage::MemoryStorage;
+use common::setup::build_config;
+
+async fn call_without_token(mode: AuthMode, request: Request<Body>) -> Response {
+    let repository = Arc::new(MemoryRepository::new()) as DynRepository;
+    let storage = Arc::new(MemoryStorage::default()) as DynCrateStorage;
+    let mut config = (*build_config(vec![])).clone();
+    config.auth.mode = mode;
+    let mut router = build_router(repository, storage, Arc::new(config));
+
+    router.call(request).await.unwrap()
+}
+
+async fn is_auth_required(mode: AuthMode) -> bool {
+    let request = Request::get("/config.json").body(Body::empty()).unwrap();
+    let response = call_without_token(mode, request).await;
+    let body = response.into_body().data().await.unwrap().unwrap();
+    let config: Value = serde_json::from_slice(&body).unwrap();
+
+    config["auth-required"].as_bool().unwrap()
+}
+
+fn get(uri: &str) -> Request<Body> {
+    Request::get(uri).body(Body::empty()).unwrap()
+}
+
+#[tokio::test]
+async fn test_index_and_downloads_are_public_in_anonymous_read_mode() {
+    let index = call_without_token(AuthMode::AnonymousRead, get("/te/st/testcrate")).await;
+    assert_eq!(index.status(), StatusCode::NOT_FOUND);
+    let download = get("/api/v1/crates/testcrate/0.1.0/download");
+    let download = call_without_token(AuthMode::AnonymousRead, download).await;
+    assert_eq!(download.status(), StatusCode::NOT_FOUND);
+
+    assert!(!is_auth_required(AuthMode::AnonymousRead).await);
+}
+
+#[tokio::test]
+async fn test_changes_need_a_token_in_anonymous_read_mode() {
+    let publish = Request::put("/api/v1/crates/new")
+        .body(Body::empty())
+        .unwrap();
+    let publish = call_without_token(AuthMode::AnonymousRead, publish).await;
+    assert_eq!(publish.status(), StatusCode::UNAUTHORIZED);
+
+    let yank = Request::delete("/api/v1/crates/testcrate/0.1.0/yank")
+        .body(Body::empty())
+        .unwrap();
+    let yank = call_without_token(AuthMode::AnonymousRead, yank).await;
+    assert_eq!(yank.status(), StatusCode::UNAUTHORIZED);
+
+    let owners = get("/api/v1/crates/testcrate/owners");
+    let owners = call_without_token(AuthMode::AnonymousRead, owners).await;
+    assert_eq!(owners.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_everything_needs_a_token_by_default() {
+    let index = call_without_token(AuthMode::Required, get("/te/st/testcrate")).await;
+    assert_eq!(index.status(), StatusCode::UNAUTHORIZED);
+    let download = get("/api/v1/crates/testcrate/0.1.0/download");
+    let download = call_without_token(AuthMode::Required, download).await;
+    assert_eq!(download.status(), StatusCode::UNAUTHORIZED);
+
+    assert!(is_auth_required(AuthMode::Required).await);
+}
+
+#[tokio::test]
+async fn test_invalid_versions_are_rejected_in_anonymous_read_mode() {
+    let download = get("/api/v1/crates/testcrate/not-a-version/download");
+    let download = call_without_token(AuthMode::AnonymousRead, download).await;
+    assert_eq!(download.status(), StatusCode::BAD_REQUEST);
+}
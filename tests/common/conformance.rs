@@ -0,0 +1,770 @@
+//! Behaviour every `Repository` implementation has to share.
+//!
+//! Each check gets a fresh repository from the factory, so backends can be
+//! verified with `run_conformance_suite(|| async { ... }).await`.
+use std::collections::HashMap;
+use std::future::Future;
+
+use raktar::auth::AuthenticatedUser;
+use raktar::error::AppError;
+use raktar::models::downloads::DailyDownloads;
+use raktar::models::index::{IndexState, PackageInfo};
+use raktar::models::limits::CrateLimits;
+use raktar::models::metadata::Metadata;
+use raktar::models::token::{now, CratePattern, EndpointScope, TokenScopes};
+use raktar::models::user::CognitoUserData;
+use raktar::repository::DynRepository;
+use semver::Version;
+use time::{Date, Duration, Month};
+
+pub async fn run_conformance_suite<F, Fut>(factory: F)
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = DynRepository>,
+{
+    check_publish_ordering(factory().await).await;
+    check_non_head_publish_keeps_max_version(factory().await).await;
+    check_duplicate_version_is_rejected(factory().await).await;
+    check_only_owners_can_publish(factory().await).await;
+    check_yank_and_unyank(factory().await).await;
+    check_owner_additions(factory().await).await;
+    check_user_id_allocation(factory().await).await;
+    check_token_lookup(factory().await).await;
+    check_token_scopes(factory().await).await;
+    check_token_timestamps(factory().await).await;
+    check_public_keys(factory().await).await;
+    check_nonces(factory().await).await;
+    check_crate_limits(factory().await).await;
+    check_canonical_name_collisions(factory().await).await;
+    check_crate_name_resolution(factory().await).await;
+    check_index_state(factory().await).await;
+    check_concurrent_index_changes(factory().await).await;
+    check_many_versions(factory().await).await;
+    check_download_counts(factory().await).await;
+}
+
+async fn check_publish_ordering(repository: DynRepository) {
+    let user = AuthenticatedUser { id: 1 };
+    // neither the semver nor the lexicographic order of these is the publish order
+    for version in ["1.2.0", "1.10.0", "0.9.0"] {
+        publish(&repository, "ordering", version, &user).await;
+        // publish times have millisecond precision
+        tokio::time::sleep(std::time::Duration::from_millis(2)).await;
+    }
+
+    let versions = repository.list_crate_versions("ordering").await.unwrap();
+    assert_eq!(
+        versions,
+        vec![
+            Version::new(1, 2, 0),
+            Version::new(1, 10, 0),
+            Version::new(0, 9, 0)
+        ],
+        "versions should be listed in publish order"
+    );
+
+    let index = repository.get_package_info("ordering").await.unwrap();
+    let index_versions: Vec<Version> = index
+        .lines()
+        .map(|line| serde_json::from_str::<PackageInfo>(line).unwrap().vers)
+        .collect();
+    assert_eq!(
+        index_versions, versions,
+        "index lines should follow the same order"
+    );
+
+    let infos = repository.list_package_infos("ordering").await.unwrap();
+    let info_versions: Vec<Version> = infos.into_iter().map(|info| info.vers).collect();
+    assert_eq!(info_versions, versions);
+    let infos = repository.list_package_infos("missing").await.unwrap();
+    assert!(infos.is_empty());
+}
+
+async fn check_non_head_publish_keeps_max_version(repository: DynRepository) {
+    let user = AuthenticatedUser { id: 1 };
+    publish(&repository, "head", "1.0.0", &user).await;
+    publish(&repository, "head", "0.9.0", &user).await;
+
+    let summary = repository.get_crate_summary("head").await.unwrap().unwrap();
+    assert_eq!(
+        summary.max_version,
+        Version::new(1, 0, 0),
+        "publishing an older version should not move the head"
+    );
+    assert_eq!(summary.description, "head 1.0.0");
+
+    publish(&repository, "head", "1.1.0", &user).await;
+    let summary = repository.get_crate_summary("head").await.unwrap().unwrap();
+    assert_eq!(summary.max_version, Version::new(1, 1, 0));
+    assert_eq!(summary.description, "head 1.1.0");
+
+    let metadata = repository
+        .get_crate_metadata("head", &Version::new(0, 9, 0))
+        .await
+        .unwrap();
+    assert_eq!(metadata.map(|m| m.vers), Some(Version::new(0, 9, 0)));
+}
+
+async fn check_duplicate_version_is_rejected(repository: DynRepository) {
+    let user = AuthenticatedUser { id: 1 };
+    publish(&repository, "duplicate", "0.1.0", &user).await;
+    publish(&repository, "duplicate", "0.2.0", &user).await;
+
+    for version in ["0.1.0", "0.2.0"] {
+        let result = try_publish(&repository, "duplicate", version, &user).await;
+        assert!(
+            matches!(result, Err(AppError::DuplicateCrateVersion { .. })),
+            "republishing {} should be rejected",
+            version
+        );
+    }
+}
+
+async fn check_only_owners_can_publish(repository: DynRepository) {
+    publish(&repository, "owned", "0.1.0", &AuthenticatedUser { id: 1 }).await;
+
+    let result = try_publish(&repository, "owned", "0.2.0", &AuthenticatedUser { id: 2 }).await;
+    assert!(matches!(result, Err(AppError::Unauthorized(_))));
+
+    let versions = repository.list_crate_versions("owned").await.unwrap();
+    assert_eq!(versions, vec![Version::new(0, 1, 0)]);
+}
+
+async fn check_yank_and_unyank(repository: DynRepository) {
+    let user = AuthenticatedUser { id: 1 };
+    publish(&repository, "yankable", "0.1.0", &user).await;
+    let version = Version::new(0, 1, 0);
+
+    repository
+        .set_yanked("yankable", &version, true)
+        .await
+        .unwrap();
+    assert!(is_yanked(&repository, "yankable").await);
+
+    repository
+        .set_yanked("yankable", &version, false)
+        .await
+        .unwrap();
+    assert!(!is_yanked(&repository, "yankable").await);
+
+    let result = repository
+        .set_yanked("yankable", &Version::new(9, 9, 9), true)
+        .await;
+    assert!(matches!(
+        result,
+        Err(AppError::NonExistentCrateVersion { .. })
+    ));
+}
+
+async fn check_owner_additions(repository: DynRepository) {
+    let owner = AuthenticatedUser { id: 1 };
+    publish(&repository, "shared", "0.1.0", &owner).await;
+
+    repository
+        .add_owners("shared", vec!["2".to_string()])
+        .await
+        .unwrap();
+
+    let summary = repository
+        .get_crate_summary("shared")
+        .await
+        .unwrap()
+        .unwrap();
+    let mut owners = summary.owners;
+    owners.sort();
+    assert_eq!(owners, vec![1, 2]);
+
+    // the new owner can now publish
+    publish(&repository, "shared", "0.2.0", &AuthenticatedUser { id: 2 }).await;
+}
+
+async fn check_user_id_allocation(repository: DynRepository) {
+    let bruce = user_data("bruce@raktar.io", "Bruce", "Wayne");
+    let clark = user_data("clark@raktar.io", "Clark", "Kent");
+
+    let user1 = repository
+        .update_or_create_user(bruce.clone())
+        .await
+        .unwrap();
+    let user2 = repository.update_or_create_user(clark).await.unwrap();
+    assert_eq!(user1.id, 1);
+    assert_eq!(user2.id, 2);
+
+    // logging in again does not create a new user
+    let again = repository.update_or_create_user(bruce).await.unwrap();
+    assert_eq!(again.id, 1);
+
+    // changed details are stored against the existing user
+    let renamed = user_data("bruce@raktar.io", "Batman", "Wayne");
+    let user = repository.update_or_create_user(renamed).await.unwrap();
+    assert_eq!(user.id, 1);
+    let stored = repository.get_user_by_id(1).await.unwrap().unwrap();
+    assert_eq!(stored.given_name, "Batman");
+
+    let users = repository.get_users().await.unwrap();
+    assert_eq!(users.len(), 2);
+}
+
+async fn check_token_lookup(repository: DynRepository) {
+    let token = repository
+        .store_auth_token(
+            b"first-secret",
+            "ci".to_string(),
+            1,
+            TokenScopes::default(),
+            None,
+        )
+        .await
+        .unwrap();
+    repository
+        .store_auth_token(
+            b"other-secret",
+            "laptop".to_string(),
+            2,
+            TokenScopes::default(),
+            None,
+        )
+        .await
+        .unwrap();
+
+    let found = repository
+        .get_auth_token(b"first-secret")
+        .await
+        .unwrap()
+        .expect("token to be found by its key");
+    assert_eq!(found.token_id, token.token_id);
+    assert_eq!(found.user_id, 1);
+    assert_eq!(found.name, "ci");
+    assert!(repository
+        .get_auth_token(b"unknown-secret")
+        .await
+        .unwrap()
+        .is_none());
+
+    let tokens = repository.list_auth_tokens(1).await.unwrap();
+    assert_eq!(tokens.len(), 1);
+
+    // users can't delete each other's tokens
+    repository
+        .delete_auth_token(2, token.token_id.clone())
+        .await
+        .unwrap();
+    assert!(repository
+        .get_auth_token(b"first-secret")
+        .await
+        .unwrap()
+        .is_some());
+
+    repository
+        .delete_auth_token(1, token.token_id)
+        .await
+        .unwrap();
+    assert!(repository
+        .get_auth_token(b"first-secret")
+        .await
+        .unwrap()
+        .is_none());
+}
+
+async fn check_token_scopes(repository: DynRepository) {
+    let scopes = TokenScopes {
+        endpoints: Some(vec![EndpointScope::PublishUpdate, EndpointScope::Yank]),
+        crates: Some(vec![CratePattern::try_from("raktar-*".to_string()).unwrap()]),
+    };
+    repository
+        .store_auth_token(b"scoped-secret", "ci".to_string(), 1, scopes.clone(), None)
+        .await
+        .unwrap();
+    repository
+        .store_auth_token(
+            b"full-secret",
+            "laptop".to_string(),
+            1,
+            TokenScopes::default(),
+            None,
+        )
+        .await
+        .unwrap();
+
+    let found = repository
+        .get_auth_token(b"scoped-secret")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(found.scopes, scopes);
+    let found = repository
+        .get_auth_token(b"full-secret")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(found.scopes, TokenScopes::default());
+
+    let mut listed: Vec<_> = repository
+        .list_auth_tokens(1)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|token| (token.name, token.scopes))
+        .collect();
+    listed.sort_by(|a, b| a.0.cmp(&b.0));
+    assert_eq!(
+        listed,
+        vec![
+            ("ci".to_string(), scopes),
+            ("laptop".to_string(), TokenScopes::default())
+        ]
+    );
+}
+
+async fn check_token_timestamps(repository: DynRepository) {
+    let created_at = now();
+    let expires_at = created_at + Duration::days(30);
+    let token = repository
+        .store_auth_token(
+            b"expiring-secret",
+            "ci".to_string(),
+            1,
+            TokenScopes::default(),
+            Some(expires_at),
+        )
+        .await
+        .unwrap();
+    assert!(token.created_at.is_some_and(|t| t >= created_at));
+
+    let found = repository
+        .get_auth_token(b"expiring-secret")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(found.created_at, token.created_at);
+    assert_eq!(found.expires_at, Some(expires_at));
+    assert_eq!(found.last_used_at, None);
+
+    let used_at = created_at + Duration::minutes(5);
+    repository
+        .record_token_use(b"expiring-secret", used_at)
+        .await
+        .unwrap();
+    let found = repository
+        .get_auth_token(b"expiring-secret")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(found.last_used_at, Some(used_at));
+
+    // the use of a deleted token isn't recorded
+    repository
+        .record_token_use(b"unknown-secret", used_at)
+        .await
+        .unwrap();
+    assert!(repository
+        .get_auth_token(b"unknown-secret")
+        .await
+        .unwrap()
+        .is_none());
+}
+
+async fn check_public_keys(repository: DynRepository) {
+    let key = repository
+        .store_public_key(
+            "k3.pid.first".to_string(),
+            "k3.public.first".to_string(),
+            "laptop".to_string(),
+            1,
+        )
+        .await
+        .unwrap();
+    repository
+        .store_public_key(
+            "k3.pid.other".to_string(),
+            "k3.public.other".to_string(),
+            "ci".to_string(),
+            2,
+        )
+        .await
+        .unwrap();
+    // a token for the same user mustn't show up as a key, or the other way around
+    repository
+        .store_auth_token(b"secret", "ci".to_string(), 1, TokenScopes::default(), None)
+        .await
+        .unwrap();
+
+    let found = repository
+        .get_public_key("k3.pid.first")
+        .await
+        .unwrap()
+        .expect("key to be found by its ID");
+    assert_eq!(found, key);
+    assert!(repository
+        .get_public_key("k3.pid.unknown")
+        .await
+        .unwrap()
+        .is_none());
+    assert_eq!(repository.list_public_keys(1).await.unwrap(), vec![key]);
+    assert_eq!(repository.list_auth_tokens(1).await.unwrap().len(), 1);
+
+    // a key can only be registered once, whoever registers it
+    let result = repository
+        .store_public_key(
+            "k3.pid.first".to_string(),
+            "k3.public.first".to_string(),
+            "stolen".to_string(),
+            2,
+        )
+        .await;
+    assert!(matches!(result, Err(AppError::DuplicatePublicKey(_))));
+
+    // users can't delete each other's keys
+    repository
+        .delete_public_key(2, "k3.pid.first".to_string())
+        .await
+        .unwrap();
+    assert!(repository
+        .get_public_key("k3.pid.first")
+        .await
+        .unwrap()
+        .is_some());
+
+    repository
+        .delete_public_key(1, "k3.pid.first".to_string())
+        .await
+        .unwrap();
+    assert!(repository.list_public_keys(1).await.unwrap().is_empty());
+}
+
+async fn check_nonces(repository: DynRepository) {
+    let expires_at = now() + Duration::minutes(15);
+    assert!(repository.use_nonce("challenge", expires_at).await.unwrap());
+    assert!(!repository.use_nonce("challenge", expires_at).await.unwrap());
+    assert!(repository.use_nonce("other", expires_at).await.unwrap());
+
+    // expired nonces can be used again, whatever they came from is rejected by then
+    let expired = now() - Duration::minutes(1);
+    assert!(repository.use_nonce("expired", expired).await.unwrap());
+    assert!(repository.use_nonce("expired", expires_at).await.unwrap());
+    assert!(!repository.use_nonce("expired", expires_at).await.unwrap());
+}
+
+async fn check_crate_limits(repository: DynRepository) {
+    // limits can be raised before the crate is first published
+    assert_eq!(
+        repository.get_crate_limits("limited").await.unwrap(),
+        CrateLimits::default()
+    );
+    let limits = CrateLimits {
+        max_crate_size: Some(50 * 1024 * 1024),
+        max_dependencies: None,
+        max_features: Some(1000),
+        max_readme_length: None,
+    };
+    repository
+        .set_crate_limits("limited", limits.clone())
+        .await
+        .unwrap();
+    assert_eq!(
+        repository.get_crate_limits("limited").await.unwrap(),
+        limits
+    );
+
+    // setting them again replaces the previous limits
+    let limits = CrateLimits {
+        max_dependencies: Some(1000),
+        ..CrateLimits::default()
+    };
+    repository
+        .set_crate_limits("limited", limits.clone())
+        .await
+        .unwrap();
+    assert_eq!(
+        repository.get_crate_limits("limited").await.unwrap(),
+        limits
+    );
+
+    publish(
+        &repository,
+        "limited",
+        "0.1.0",
+        &AuthenticatedUser { id: 1 },
+    )
+    .await;
+    let versions = repository.list_crate_versions("limited").await.unwrap();
+    assert_eq!(versions, vec![Version::new(0, 1, 0)]);
+}
+
+async fn check_canonical_name_collisions(repository: DynRepository) {
+    let user = AuthenticatedUser { id: 1 };
+    publish(&repository, "foo-bar", "0.1.0", &user).await;
+
+    for name in ["foo_bar", "Foo-Bar", "FOO_BAR"] {
+        let result = try_publish(&repository, name, "0.2.0", &user).await;
+        assert!(
+            matches!(
+                &result,
+                Err(AppError::CrateNameCollision { existing, .. }) if existing == "foo-bar"
+            ),
+            "publishing {} should collide with foo-bar, got {:?}",
+            name,
+            result
+        );
+    }
+
+    // the crate itself can still be published to
+    publish(&repository, "foo-bar", "0.2.0", &user).await;
+    assert!(repository
+        .get_crate_summary("foo_bar")
+        .await
+        .unwrap()
+        .is_none());
+}
+
+async fn check_crate_name_resolution(repository: DynRepository) {
+    let user = AuthenticatedUser { id: 1 };
+    publish(&repository, "Serde-Json", "1.0.0", &user).await;
+
+    for name in ["Serde-Json", "serde_json", "serde-json", "SERDE_JSON"] {
+        assert_eq!(
+            repository
+                .resolve_crate_name(name)
+                .await
+                .unwrap()
+                .as_deref(),
+            Some("Serde-Json"),
+            "{} should resolve to the published name",
+            name
+        );
+    }
+    assert!(repository
+        .resolve_crate_name("serde_yaml")
+        .await
+        .unwrap()
+        .is_none());
+
+    // crates without versions don't have an index file
+    assert!(matches!(
+        repository.get_package_info("serde_yaml").await,
+        Err(AppError::NonExistentPackageInfo(_))
+    ));
+
+    // the index keeps the published name
+    let index = repository.get_package_info("Serde-Json").await.unwrap();
+    let info: PackageInfo = serde_json::from_str(index.lines().next().unwrap()).unwrap();
+    assert_eq!(info.name, "Serde-Json");
+}
+
+async fn check_index_state(repository: DynRepository) {
+    let user = AuthenticatedUser { id: 1 };
+    assert!(repository
+        .get_index_state("cached")
+        .await
+        .unwrap()
+        .is_none());
+
+    publish(&repository, "cached", "0.1.0", &user).await;
+    let first = repository.get_index_state("cached").await.unwrap().unwrap();
+    assert_eq!(
+        repository.get_index_state("cached").await.unwrap(),
+        Some(first.clone()),
+        "reading the state should not change it"
+    );
+
+    publish(&repository, "cached", "0.2.0", &user).await;
+    let second = repository.get_index_state("cached").await.unwrap().unwrap();
+    assert_ne!(first.etag, second.etag);
+    assert!(second.last_modified >= first.last_modified);
+
+    let version = Version::new(0, 1, 0);
+    repository
+        .set_yanked("cached", &version, true)
+        .await
+        .unwrap();
+    let yanked = repository.get_index_state("cached").await.unwrap().unwrap();
+    assert_ne!(yanked.etag, second.etag);
+
+    // the ETag follows the contents of the index file
+    repository
+        .set_yanked("cached", &version, false)
+        .await
+        .unwrap();
+    let unyanked = repository.get_index_state("cached").await.unwrap().unwrap();
+    assert_eq!(unyanked.etag, second.etag);
+}
+
+async fn check_concurrent_index_changes(repository: DynRepository) {
+    let user = AuthenticatedUser { id: 1 };
+    publish(&repository, "racing", "0.1.0", &user).await;
+    let index_state_matches = || async {
+        let infos = repository.list_package_infos("racing").await.unwrap();
+        let state = repository.get_index_state("racing").await.unwrap().unwrap();
+        state.etag == IndexState::modified_now(&infos).etag
+    };
+
+    let (first, second) = tokio::join!(
+        try_publish(&repository, "racing", "0.2.0", &user),
+        try_publish(&repository, "racing", "0.3.0", &user),
+    );
+    first.unwrap();
+    second.unwrap();
+    let versions = repository.list_crate_versions("racing").await.unwrap();
+    assert_eq!(versions.len(), 3);
+    assert!(
+        index_state_matches().await,
+        "the ETag should cover both versions"
+    );
+    let summary = repository
+        .get_crate_summary("racing")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(summary.max_version, Version::new(0, 3, 0));
+
+    let (older, newer) = (Version::new(0, 1, 0), Version::new(0, 2, 0));
+    let (first, second) = tokio::join!(
+        repository.set_yanked("racing", &older, true),
+        repository.set_yanked("racing", &newer, true),
+    );
+    first.unwrap();
+    second.unwrap();
+    assert!(
+        index_state_matches().await,
+        "the ETag should cover both yanks"
+    );
+}
+
+pub async fn publish(
+    repository: &DynRepository,
+    name: &str,
+    version: &str,
+    user: &AuthenticatedUser,
+) {
+    try_publish(repository, name, version, user)
+        .await
+        .unwrap_or_else(|err| panic!("publishing {} {} failed: {}", name, version, err));
+}
+
+async fn try_publish(
+    repository: &DynRepository,
+    name: &str,
+    version: &str,
+    user: &AuthenticatedUser,
+) -> Result<(), AppError> {
+    let metadata = build_metadata(name, version);
+    let version = metadata.vers.clone();
+    let package_info = PackageInfo::from_metadata(metadata.clone(), "cksum");
+    repository
+        .store_package_info(name, &version, package_info, metadata, user)
+        .await
+}
+
+async fn is_yanked(repository: &DynRepository, name: &str) -> bool {
+    let index = repository.get_package_info(name).await.unwrap();
+    let info: PackageInfo = serde_json::from_str(index.lines().next().unwrap()).unwrap();
+    info.yanked
+}
+
+pub fn build_metadata(name: &str, version: &str) -> Metadata {
+    Metadata {
+        name: name.to_string(),
+        vers: version.parse().unwrap(),
+        deps: vec![],
+        features: HashMap::new(),
+        authors: vec![],
+        description: Some(format!("{} {}", name, version)),
+        documentation: None,
+        homepage: None,
+        readme: None,
+        readme_file: None,
+        keywords: vec![],
+        categories: vec![],
+        license: None,
+        license_file: None,
+        repository: None,
+        badges: HashMap::new(),
+        links: None,
+        rust_version: None,
+        yanked: false,
+    }
+}
+
+fn user_data(login: &str, given_name: &str, family_name: &str) -> CognitoUserData {
+    CognitoUserData {
+        login: login.to_string(),
+        given_name: given_name.to_string(),
+        family_name: family_name.to_string(),
+    }
+}
+
+async fn check_many_versions(repository: DynRepository) {
+    let user = AuthenticatedUser { id: 1 };
+    let published: Vec<Version> = (0..12).map(|patch| Version::new(0, 1, patch)).collect();
+    for version in &published {
+        publish(&repository, "many", &version.to_string(), &user).await;
+    }
+    for name in ["many_a", "many_b", "many_c", "many_d"] {
+        publish(&repository, name, "0.1.0", &user).await;
+    }
+
+    let versions = repository.list_crate_versions("many").await.unwrap();
+    assert_eq!(versions, published, "every version should be listed");
+    let index = repository.get_package_info("many").await.unwrap();
+    assert_eq!(index.lines().count(), published.len());
+
+    let crates = repository
+        .get_all_crate_details(Some("many".to_string()), 10)
+        .await
+        .unwrap();
+    assert_eq!(crates.len(), 5, "every matching crate should be listed");
+    let crates = repository
+        .get_all_crate_details(Some("many".to_string()), 3)
+        .await
+        .unwrap();
+    assert_eq!(crates.len(), 3, "the limit should still apply");
+}
+
+async fn check_download_counts(repository: DynRepository) {
+    let first_day = Date::from_calendar_date(2023, Month::July, 31).unwrap();
+    let second_day = first_day.next_day().unwrap();
+    let release = Version::new(0, 1, 0);
+    let pre_release: Version = "0.1.0-alpha.1".parse().unwrap();
+    assert!(repository
+        .get_download_counts("counted", None)
+        .await
+        .unwrap()
+        .is_empty());
+
+    for (version, date) in [
+        (&release, first_day),
+        (&release, first_day),
+        (&release, second_day),
+        (&pre_release, first_day),
+    ] {
+        repository
+            .record_download("counted", version, date)
+            .await
+            .unwrap();
+    }
+
+    let daily = |date, downloads| DailyDownloads { date, downloads };
+    assert_eq!(
+        repository
+            .get_download_counts("counted", Some(&release))
+            .await
+            .unwrap(),
+        vec![daily(first_day, 2), daily(second_day, 1)],
+        "a version's counts shouldn't include versions it is a prefix of"
+    );
+    assert_eq!(
+        repository
+            .get_download_counts("counted", Some(&pre_release))
+            .await
+            .unwrap(),
+        vec![daily(first_day, 1)]
+    );
+    assert_eq!(
+        repository
+            .get_download_counts("counted", None)
+            .await
+            .unwrap(),
+        vec![daily(first_day, 3), daily(second_day, 1)],
+        "the crate's counts should combine every version"
+    );
+}
@@ -1,14 +1,41 @@
 use async_trait::async_trait;
 use semver::Version;
+use std::collections::hash_map::Entry;
 use std::collections::HashMap;
-use tokio::sync::RwLock;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tokio::sync::{Barrier, RwLock};
 
-use raktar::error::AppResult;
-use raktar::storage::CrateStorage;
+use raktar::error::{AppError, AppResult};
+use raktar::storage::{CrateData, CrateStorage};
 
+#[allow(dead_code)] // not all tests use this
 #[derive(Debug, Default)]
 pub struct MemoryStorage {
     data: RwLock<HashMap<(String, Version), Vec<u8>>>,
+    fail_writes: AtomicBool,
+    write_barrier: Option<Barrier>,
+}
+
+#[allow(dead_code)] // not all tests use this
+impl MemoryStorage {
+    /// Holds every write until this many of them are in progress, to line up concurrent
+    /// publishes.
+    pub fn with_write_barrier(writers: usize) -> Self {
+        Self {
+            write_barrier: Some(Barrier::new(writers)),
+            ..Self::default()
+        }
+    }
+
+    /// Makes every following write fail, like an unavailable bucket would.
+    pub fn set_fail_writes(&self, fail_writes: bool) {
+        self.fail_writes.store(fail_writes, Ordering::SeqCst);
+    }
+
+    pub async fn contains(&self, crate_name: &str, version: &Version) -> bool {
+        let key = (crate_name.to_string(), version.clone());
+        self.data.read().await.contains_key(&key)
+    }
 }
 
 #[async_trait]
@@ -17,20 +44,46 @@ impl CrateStorage for MemoryStorage {
         &self,
         crate_name: &str,
         version: Version,
-        data: Vec<u8>,
+        data: CrateData,
     ) -> AppResult<()> {
+        if self.fail_writes.load(Ordering::SeqCst) {
+            return Err(anyhow::anyhow!("storage is unavailable").into());
+        }
+
         let key = (crate_name.to_string(), version);
+        let data = data
+            .into_bytes()
+            .await
+            .map_err(anyhow::Error::from)?
+            .to_vec();
+        if let Some(barrier) = &self.write_barrier {
+            barrier.wait().await;
+        }
         let mut lock = self.data.write().await;
-        lock.insert(key, data);
-
-        Ok(())
+        match lock.entry(key) {
+            Entry::Occupied(entry) => Err(AppError::DuplicateCrateVersion {
+                crate_name: entry.key().0.clone(),
+                version: entry.key().1.clone(),
+            }),
+            Entry::Vacant(entry) => {
+                entry.insert(data);
+                Ok(())
+            }
+        }
     }
 
-    async fn get_crate(&self, crate_name: &str, version: Version) -> AppResult<Vec<u8>> {
+    async fn get_crate(&self, crate_name: &str, version: Version) -> AppResult<CrateData> {
         let key = (crate_name.to_string(), version);
         let lock = self.data.read().await;
         let data = lock.get(&key).cloned().unwrap();
 
-        Ok(data)
+        Ok(CrateData::from_bytes(data))
+    }
+
+    async fn delete_crate(&self, crate_name: &str, version: Version) -> AppResult<()> {
+        let key = (crate_name.to_string(), version);
+        self.data.write().await.remove(&key);
+
+        Ok(())
     }
 }
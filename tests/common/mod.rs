@@ -1,3 +1,7 @@
+#[allow(dead_code)] // not all tests use this
+pub mod conformance;
+pub mod fixtures;
 pub mod graphql;
 pub mod memory_storage;
+#[allow(dead_code)] // not all tests use this
 pub mod setup;
@@ -3,9 +3,47 @@ use aws_sdk_dynamodb::types::{
     ProjectionType, ProvisionedThroughput, ScalarAttributeType,
 };
 use aws_sdk_dynamodb::Client;
-use raktar::repository::DynamoDBRepository;
+use raktar::config::{
+    AuthConfig, AuthMode, DownloadMode, JwksSource, JwtConfig, PublishLimits, RaktarConfig,
+    RepositoryConfig, StorageConfig,
+};
+use raktar::repository::{DynamoDBRepository, SqlRepository};
 use rand::distributions::{Alphanumeric, DistString};
+use sqlx::any::install_default_drivers;
+use sqlx::{AnyConnection, Connection};
+use std::sync::Arc;
 use std::time::Duration;
+use url::Url;
+
+pub const JWT_ISSUER: &str = "https://cognito-idp.eu-west-1.amazonaws.com/eu-west-1_test";
+pub const JWT_AUDIENCE: &str = "raktar-test-client";
+
+pub fn build_config(admins: Vec<u32>) -> Arc<RaktarConfig> {
+    Arc::new(RaktarConfig {
+        repository: RepositoryConfig::Memory,
+        storage: StorageConfig::Filesystem {
+            path: std::env::temp_dir(),
+            public_url: None,
+        },
+        downloads: DownloadMode::Redirect,
+        verify_downloads: false,
+        listen_address: "127.0.0.1:3026".parse().unwrap(),
+        domain_name: "localhost".to_string(),
+        auth: AuthConfig {
+            mode: AuthMode::Required,
+            admins,
+            // signed with tests/fixtures/jwt_signing_key.pem
+            jwt: JwtConfig {
+                jwks: JwksSource::File(
+                    concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/jwks.json").into(),
+                ),
+                issuer: JWT_ISSUER.to_string(),
+                audience: JWT_AUDIENCE.to_string(),
+            },
+        },
+        limits: PublishLimits::default(),
+    })
+}
 
 fn generate_random_key() -> String {
     Alphanumeric.sample_string(&mut rand::thread_rng(), 16)
@@ -122,3 +160,33 @@ fn build_user_data_gsi() -> GlobalSecondaryIndex {
         )
         .build()
 }
+
+pub async fn build_sqlite_repository() -> SqlRepository {
+    SqlRepository::connect("sqlite::memory:")
+        .await
+        .expect("to be able to connect to SQLite")
+}
+
+/// Creates a fresh database on the PostgreSQL server in `TEST_POSTGRES_URL`,
+/// returning `None` when no server is configured.
+pub async fn build_postgres_repository() -> Option<SqlRepository> {
+    let server_url = std::env::var("TEST_POSTGRES_URL").ok()?;
+    let database_name = format!("raktar_{}", generate_random_key().to_lowercase());
+
+    install_default_drivers();
+    let mut connection = AnyConnection::connect(&server_url)
+        .await
+        .expect("to be able to connect to PostgreSQL");
+    sqlx::query(&format!("CREATE DATABASE {}", database_name))
+        .execute(&mut connection)
+        .await
+        .expect("to be able to create database");
+
+    let mut database_url = Url::parse(&server_url).expect("TEST_POSTGRES_URL to be a valid URL");
+    database_url.set_path(&database_name);
+    let repository = SqlRepository::connect(database_url.as_str())
+        .await
+        .expect("to be able to connect to the new database");
+
+    Some(repository)
+}
@@ -0,0 +1,177 @@
+mod common;
+
+use axum::body::HttpBody;
+use axum::extract::{Path, State};
+use axum::http::header::LOCATION;
+use axum::http::StatusCode;
+use raktar::auth::AuthenticatedUser;
+use raktar::cargo_api::download::download_crate;
+use raktar::cargo_api::publish::publish_crate;
+use raktar::config::{DownloadMode, PublishLimits, RaktarConfig};
+use raktar::error::AppError;
+use raktar::models::downloads::{today, DailyDownloads};
+use raktar::models::token::TokenScopes;
+use raktar::repository::{DynRepository, MemoryRepository};
+use raktar::storage::{DynCrateStorage, FilesystemStorage};
+use semver::Version;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use common::fixtures::CRATE_BYTES_V1;
+use common::setup::build_config;
+
+async fn publish_to(storage: FilesystemStorage) -> (DynRepository, DynCrateStorage) {
+    let storage = Arc::new(storage) as DynCrateStorage;
+    let repository = Arc::new(MemoryRepository::new()) as DynRepository;
+    publish_crate(
+        AuthenticatedUser { id: 1 },
+        &TokenScopes::default(),
+        None,
+        storage.clone(),
+        repository.clone(),
+        &PublishLimits::default(),
+        &CRATE_BYTES_V1[..],
+    )
+    .await
+    .expect("publish to succeed");
+
+    (repository, storage)
+}
+
+fn build_storage() -> FilesystemStorage {
+    FilesystemStorage::new(std::env::temp_dir().join(format!("raktar-{}", Uuid::new_v4())))
+}
+
+fn with_downloads(downloads: DownloadMode) -> Arc<RaktarConfig> {
+    let mut config = (*build_config(vec![])).clone();
+    config.downloads = downloads;
+    Arc::new(config)
+}
+
+fn with_verified_downloads() -> Arc<RaktarConfig> {
+    let mut config = (*build_config(vec![])).clone();
+    config.verify_downloads = true;
+    Arc::new(config)
+}
+
+fn download_path() -> Path<(String, String)> {
+    Path(("testcrate_1".to_string(), "0.1.1".to_string()))
+}
+
+#[tokio::test]
+async fn test_download_redirects_to_storage() {
+    let storage = build_storage().with_public_url("https://static.raktar.io".parse().unwrap());
+    let (repository, storage) = publish_to(storage).await;
+    let state = (repository, storage, with_downloads(DownloadMode::Redirect));
+
+    let response = download_crate(download_path(), State(state)).await.unwrap();
+
+    assert_eq!(response.status(), StatusCode::FOUND);
+    assert_eq!(
+        response.headers()[LOCATION],
+        "https://static.raktar.io/crates/testcrate_1/testcrate_1-0.1.1.crate"
+    );
+}
+
+#[tokio::test]
+async fn test_download_is_proxied_without_a_url() {
+    let (repository, storage) = publish_to(build_storage()).await;
+    let state = (repository, storage, with_downloads(DownloadMode::Redirect));
+
+    let response = download_crate(download_path(), State(state)).await.unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = response.into_body().data().await.unwrap().unwrap();
+    assert_eq!(body.len(), 796);
+}
+
+#[tokio::test]
+async fn test_download_is_proxied_when_configured() {
+    let storage = build_storage().with_public_url("https://static.raktar.io".parse().unwrap());
+    let (repository, storage) = publish_to(storage).await;
+    let state = (repository, storage, with_downloads(DownloadMode::Proxy));
+
+    let response = download_crate(download_path(), State(state)).await.unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.headers().get(LOCATION).is_none());
+}
+
+#[tokio::test]
+async fn test_verified_download_is_served() {
+    let storage = build_storage().with_public_url("https://static.raktar.io".parse().unwrap());
+    let (repository, storage) = publish_to(storage).await;
+    let state = (repository, storage, with_verified_downloads());
+
+    let response = download_crate(download_path(), State(state)).await.unwrap();
+
+    // checked downloads are never redirected, the bytes have to pass through the check
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = response.into_body().data().await.unwrap().unwrap();
+    assert_eq!(body.len(), 796);
+}
+
+#[tokio::test]
+async fn test_corrupted_download_is_not_served() {
+    let filesystem_storage = build_storage();
+    let (repository, storage) = publish_to(filesystem_storage.clone()).await;
+    let path = filesystem_storage.crate_path("testcrate_1", &Version::new(0, 1, 1));
+    tokio::fs::write(&path, b"corrupted").await.unwrap();
+    let state = (repository, storage, with_verified_downloads());
+
+    let result = download_crate(download_path(), State(state)).await;
+
+    assert!(matches!(result, Err(AppError::CorruptCrateFile { .. })));
+}
+
+#[tokio::test]
+async fn test_downloads_are_counted() {
+    let (repository, storage) = publish_to(build_storage()).await;
+    let state = (
+        repository.clone(),
+        storage,
+        with_downloads(DownloadMode::Proxy),
+    );
+
+    for _ in 0..2 {
+        download_crate(download_path(), State(state.clone()))
+            .await
+            .unwrap();
+    }
+
+    let counts = repository
+        .get_download_counts("testcrate_1", Some(&Version::new(0, 1, 1)))
+        .await
+        .unwrap();
+    assert_eq!(
+        counts,
+        vec![DailyDownloads {
+            date: today(),
+            downloads: 2
+        }]
+    );
+}
+
+#[tokio::test]
+async fn test_unknown_versions_are_not_redirected_or_counted() {
+    let storage = build_storage().with_public_url("https://static.raktar.io".parse().unwrap());
+    let (repository, storage) = publish_to(storage).await;
+    let state = (
+        repository.clone(),
+        storage,
+        with_downloads(DownloadMode::Redirect),
+    );
+
+    let path = Path(("testcrate_1".to_string(), "9.9.9".to_string()));
+    let result = download_crate(path, State(state)).await;
+
+    assert!(matches!(
+        result,
+        Err(AppError::NonExistentCrateVersion { .. })
+    ));
+    let counts = repository
+        .get_download_counts("testcrate_1", None)
+        .await
+        .unwrap();
+    assert!(counts.is_empty());
+}
@@ -0,0 +1,65 @@
+use async_graphql::value;
+use raktar::graphql::schema::build_schema;
+use raktar::repository::{DynRepository, MemoryRepository};
+use std::sync::Arc;
+
+use crate::common::graphql::build_request;
+use crate::common::memory_storage::MemoryStorage;
+use crate::common::setup::build_config;
+
+// the crate size is more than the registry-wide upload size
+const SET_LIMITS: &str = r#"
+    mutation {
+        setCrateLimits(crateName: "big-crate", limits: { maxCrateSize: 104857600, maxFeatures: 1000 }) {
+            maxCrateSize
+            maxDependencies
+            maxFeatures
+        }
+    }
+"#;
+
+#[tokio::test]
+async fn test_admins_can_set_crate_limits() {
+    let repository = Arc::new(MemoryRepository::new()) as DynRepository;
+    let schema = build_schema(
+        repository.clone(),
+        Arc::new(MemoryStorage::default()),
+        build_config(vec![1]),
+    );
+
+    let response = schema.execute(build_request(SET_LIMITS, 1)).await;
+
+    assert_eq!(response.errors.len(), 0);
+    assert_eq!(
+        response.data,
+        value!({
+            "setCrateLimits": {
+                "maxCrateSize": 104857600,
+                "maxDependencies": null,
+                "maxFeatures": 1000,
+            }
+        })
+    );
+    let limits = repository.get_crate_limits("big-crate").await.unwrap();
+    assert_eq!(limits.max_crate_size, Some(104857600));
+}
+
+#[tokio::test]
+async fn test_other_users_cant_set_crate_limits() {
+    let repository = Arc::new(MemoryRepository::new()) as DynRepository;
+    let schema = build_schema(
+        repository.clone(),
+        Arc::new(MemoryStorage::default()),
+        build_config(vec![1]),
+    );
+
+    let response = schema.execute(build_request(SET_LIMITS, 2)).await;
+
+    assert_eq!(response.errors.len(), 1);
+    assert_eq!(
+        response.errors[0].message,
+        "only admins can change the limits of a crate"
+    );
+    let limits = repository.get_crate_limits("big-crate").await.unwrap();
+    assert_eq!(limits.max_crate_size, None);
+}
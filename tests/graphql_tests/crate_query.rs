@@ -1,8 +1,9 @@
 use async_graphql::{value, Request, Variables};
-use axum::body::Bytes;
 use raktar::auth::AuthenticatedUser;
 use raktar::cargo_api::publish::publish_crate;
+use raktar::config::PublishLimits;
 use raktar::graphql::schema::{build_schema, RaktarSchema};
+use raktar::models::token::TokenScopes;
 use raktar::repository::DynRepository;
 use raktar::storage::DynCrateStorage;
 use serde::Deserialize;
@@ -10,30 +11,46 @@ use std::sync::Arc;
 
 use crate::common::graphql::build_request;
 use crate::common::memory_storage::MemoryStorage;
-use crate::common::setup::build_repository;
+use crate::common::setup::{build_config, build_repository};
 
 #[tokio::test]
 async fn test_crate_query_with_head_version_works() {
     let repository = Arc::new(build_repository().await) as DynRepository;
-    let schema = build_schema(repository.clone());
     let storage = Arc::new(MemoryStorage::default()) as DynCrateStorage;
+    let schema = build_schema(repository.clone(), storage.clone(), build_config(vec![]));
     let user = AuthenticatedUser { id: 1 };
 
     // publish version 0.1.1
-    let data = Bytes::from_static(CRATE_BYTES_V1);
-    publish_crate(user.clone(), storage.clone(), repository.clone(), data)
-        .await
-        .expect("publish to succeed");
+    let data = &CRATE_BYTES_V1[..];
+    publish_crate(
+        user.clone(),
+        &TokenScopes::default(),
+        None,
+        storage.clone(),
+        repository.clone(),
+        &PublishLimits::default(),
+        data,
+    )
+    .await
+    .expect("publish to succeed");
 
     // head state is 0.1.1, assert that the query works and reflects this
     let crate_version = get_crate_version(&schema, "testcrate_1").await;
     assert_eq!(crate_version.version, "0.1.1");
 
     // publish version 0.1.2
-    let data = Bytes::from_static(CRATE_BYTES_V2);
-    publish_crate(user.clone(), storage.clone(), repository.clone(), data)
-        .await
-        .expect("publish to succeed");
+    let data = &CRATE_BYTES_V2[..];
+    publish_crate(
+        user.clone(),
+        &TokenScopes::default(),
+        None,
+        storage.clone(),
+        repository.clone(),
+        &PublishLimits::default(),
+        data,
+    )
+    .await
+    .expect("publish to succeed");
 
     // the query should now return 0.1.2
     let crate_version = get_crate_version(&schema, "testcrate_1").await;
@@ -44,7 +61,8 @@ async fn test_crate_query_with_head_version_works() {
 #[tokio::test]
 async fn test_crate_query_returns_null_when_crate_is_missing() {
     let repository = Arc::new(build_repository().await) as DynRepository;
-    let schema = build_schema(repository.clone());
+    let storage = Arc::new(MemoryStorage::default());
+    let schema = build_schema(repository.clone(), storage, build_config(vec![]));
 
     let request = build_crate_request(1, "missing_crate", None);
     let response = schema.execute(request).await;
@@ -59,15 +77,23 @@ async fn test_crate_query_returns_null_when_crate_is_missing() {
 #[tokio::test]
 async fn test_crate_query_returns_null_when_crate_version_is_missing() {
     let repository = Arc::new(build_repository().await) as DynRepository;
-    let schema = build_schema(repository.clone());
     let storage = Arc::new(MemoryStorage::default()) as DynCrateStorage;
+    let schema = build_schema(repository.clone(), storage.clone(), build_config(vec![]));
     let user = AuthenticatedUser { id: 1 };
 
     // publish version 0.1.1
-    let data = Bytes::from_static(CRATE_BYTES_V1);
-    publish_crate(user.clone(), storage.clone(), repository.clone(), data)
-        .await
-        .expect("publish to succeed");
+    let data = &CRATE_BYTES_V1[..];
+    publish_crate(
+        user.clone(),
+        &TokenScopes::default(),
+        None,
+        storage.clone(),
+        repository.clone(),
+        &PublishLimits::default(),
+        data,
+    )
+    .await
+    .expect("publish to succeed");
 
     // head state is 0.1.1, assert that the query works and reflects this
     let crate_version = get_crate_version(&schema, "testcrate_1").await;
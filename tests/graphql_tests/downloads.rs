@@ -0,0 +1,105 @@
+use async_graphql::value;
+use raktar::auth::AuthenticatedUser;
+use raktar::cargo_api::publish::publish_crate;
+use raktar::config::PublishLimits;
+use raktar::graphql::schema::{build_schema, RaktarSchema};
+use raktar::models::downloads::today;
+use raktar::models::token::TokenScopes;
+use raktar::repository::{DynRepository, MemoryRepository};
+use raktar::storage::DynCrateStorage;
+use semver::Version;
+use std::sync::Arc;
+use time::Duration;
+
+use crate::common::fixtures::{CRATE_BYTES_V1, CRATE_BYTES_V2};
+use crate::common::graphql::build_request;
+use crate::common::memory_storage::MemoryStorage;
+use crate::common::setup::build_config;
+
+async fn build_downloaded_schema() -> RaktarSchema {
+    let repository = Arc::new(MemoryRepository::new()) as DynRepository;
+    let storage = Arc::new(MemoryStorage::default()) as DynCrateStorage;
+    for data in [&CRATE_BYTES_V1[..], &CRATE_BYTES_V2[..]] {
+        publish_crate(
+            AuthenticatedUser { id: 1 },
+            &TokenScopes::default(),
+            None,
+            storage.clone(),
+            repository.clone(),
+            &PublishLimits::default(),
+            data,
+        )
+        .await
+        .unwrap();
+    }
+
+    let old = Version::new(0, 1, 1);
+    let new = Version::new(0, 1, 2);
+    let long_ago = today() - Duration::days(100);
+    for (version, date) in [(&old, long_ago), (&old, today()), (&new, today())] {
+        repository
+            .record_download("testcrate_1", version, date)
+            .await
+            .unwrap();
+    }
+
+    build_schema(repository, storage, build_config(vec![]))
+}
+
+#[tokio::test]
+async fn test_crate_downloads() {
+    let schema = build_downloaded_schema().await;
+    let query = r#"
+        query {
+            crate(name: "testcrate_1") {
+                downloads
+                dailyDownloads {
+                    date
+                    downloads
+                }
+            }
+        }
+    "#;
+
+    let response = schema.execute(build_request(query, 1)).await;
+
+    assert_eq!(response.errors.len(), 0);
+    // only the last 90 days are listed by default, the total covers every day
+    assert_eq!(
+        response.data,
+        value!({
+            "crate": {
+                "downloads": 3,
+                "dailyDownloads": [{ "date": today().to_string(), "downloads": 2 }],
+            }
+        })
+    );
+}
+
+#[tokio::test]
+async fn test_version_downloads() {
+    let schema = build_downloaded_schema().await;
+    let query = r#"
+        query {
+            crateVersion(name: "testcrate_1", version: "0.1.1") {
+                downloads
+                dailyDownloads(days: 365) {
+                    downloads
+                }
+            }
+        }
+    "#;
+
+    let response = schema.execute(build_request(query, 1)).await;
+
+    assert_eq!(response.errors.len(), 0);
+    assert_eq!(
+        response.data,
+        value!({
+            "crateVersion": {
+                "downloads": 2,
+                "dailyDownloads": [{ "downloads": 1 }, { "downloads": 1 }],
+            }
+        })
+    );
+}
@@ -1,2 +1,6 @@
+mod crate_limits;
 mod crate_query;
+mod downloads;
+mod public_keys;
 mod tokens;
+mod verify_crates;
@@ -0,0 +1,92 @@
+use async_graphql::{value, Request, Variables};
+use raktar::graphql::schema::build_schema;
+use raktar::repository::{DynRepository, MemoryRepository};
+use serde_json::Value;
+use std::sync::Arc;
+
+use crate::common::graphql::build_request;
+use crate::common::memory_storage::MemoryStorage;
+use crate::common::setup::build_config;
+
+const PUBLIC_KEY: &str =
+    "k3.public.AgL-9brv6ACk7klXRDVYPRg7Ds2r1XJrk-SsxK1WqCmSO0cvVs8JXuHP9UPMFXJwWg";
+
+#[tokio::test]
+async fn test_public_key_registration() {
+    let repository = Arc::new(MemoryRepository::new()) as DynRepository;
+    let schema = build_schema(
+        repository,
+        Arc::new(MemoryStorage::default()),
+        build_config(vec![]),
+    );
+
+    let response = schema
+        .execute(build_register_key_request(50, PUBLIC_KEY))
+        .await;
+    assert_eq!(response.errors.len(), 0, "{:?}", response.errors);
+    let data = response.data.into_json().unwrap();
+    let key = &data["registerPublicKey"];
+    assert!(key["id"].as_str().unwrap().starts_with("k3.pid."));
+    assert_eq!(key["publicKey"], PUBLIC_KEY);
+    assert_eq!(key["name"], "laptop");
+
+    // a key can only belong to a single user
+    let response = schema
+        .execute(build_register_key_request(51, PUBLIC_KEY))
+        .await;
+    assert_eq!(response.errors.len(), 1);
+    let response = schema
+        .execute(build_register_key_request(50, "k3.public.invalid"))
+        .await;
+    assert_eq!(response.errors.len(), 1);
+
+    let response = schema.execute(build_my_public_keys_request(50)).await;
+    let data = response.data.into_json().unwrap();
+    assert_eq!(data["myPublicKeys"][0]["id"], key["id"]);
+
+    let key_id = key["id"].as_str().unwrap();
+    let response = schema.execute(build_delete_key_request(50, key_id)).await;
+    assert_eq!(response.errors.len(), 0, "{:?}", response.errors);
+    let response = schema.execute(build_my_public_keys_request(50)).await;
+    let data = response.data.into_json().unwrap();
+    assert_eq!(data["myPublicKeys"], Value::Array(vec![]));
+}
+
+fn build_register_key_request(user_id: u32, public_key: &str) -> Request {
+    let mutation = r#"
+    mutation RegisterPublicKey($publicKey: String!) {
+        registerPublicKey(name: "laptop", publicKey: $publicKey) {
+            id
+            name
+            publicKey
+            createdAt
+        }
+    }
+    "#;
+    let variables = Variables::from_value(value!({ "publicKey": public_key }));
+
+    build_request(mutation, user_id).variables(variables)
+}
+
+fn build_delete_key_request(user_id: u32, key_id: &str) -> Request {
+    let mutation = r#"
+    mutation DeletePublicKey($keyId: String!) {
+      deletePublicKey(keyId: $keyId) {
+        id
+      }
+    }
+    "#;
+    let variables = Variables::from_value(value!({ "keyId": key_id }));
+
+    build_request(mutation, user_id).variables(variables)
+}
+
+fn build_my_public_keys_request(user_id: u32) -> Request {
+    let query = r#"
+    query {
+      myPublicKeys {
+        id
+      }
+    }"#;
+    build_request(query, user_id)
+}
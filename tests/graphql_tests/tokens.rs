@@ -1,16 +1,21 @@
 use async_graphql::{value, Name, Request, Value, Variables};
 use raktar::graphql::schema::build_schema;
-use raktar::repository::DynRepository;
+use raktar::repository::{DynRepository, MemoryRepository};
 use std::collections::HashSet;
 use std::sync::Arc;
 
 use crate::common::graphql::build_request;
-use crate::common::setup::build_repository;
+use crate::common::memory_storage::MemoryStorage;
+use crate::common::setup::{build_config, build_repository};
 
 #[tokio::test]
 async fn test_token_generation() {
     let repository = Arc::new(build_repository().await) as DynRepository;
-    let schema = build_schema(repository);
+    let schema = build_schema(
+        repository,
+        Arc::new(MemoryStorage::default()),
+        build_config(vec![]),
+    );
 
     let request = build_generate_token_request(0, "test token");
     let response = schema.execute(request).await;
@@ -31,7 +36,11 @@ async fn test_token_generation() {
 #[tokio::test]
 async fn test_my_tokens() {
     let repository = Arc::new(build_repository().await) as DynRepository;
-    let schema = build_schema(repository);
+    let schema = build_schema(
+        repository,
+        Arc::new(MemoryStorage::default()),
+        build_config(vec![]),
+    );
 
     // We create a new token for user 10
     let request = build_generate_token_request(10, "test token");
@@ -71,7 +80,11 @@ async fn test_my_tokens() {
 #[tokio::test]
 async fn test_delete_token() {
     let repository = Arc::new(build_repository().await) as DynRepository;
-    let schema = build_schema(repository);
+    let schema = build_schema(
+        repository,
+        Arc::new(MemoryStorage::default()),
+        build_config(vec![]),
+    );
 
     let request = build_generate_token_request(20, "test token");
     let response = schema.execute(request).await;
@@ -101,6 +114,71 @@ async fn test_delete_token() {
     }
 }
 
+#[tokio::test]
+async fn test_scoped_token_generation() {
+    let repository = Arc::new(MemoryRepository::new()) as DynRepository;
+    let schema = build_schema(
+        repository,
+        Arc::new(MemoryStorage::default()),
+        build_config(vec![]),
+    );
+
+    let scopes = value!({ "endpoints": ["PUBLISH_UPDATE", "YANK"], "crates": ["raktar-*"] });
+    let response = schema
+        .execute(build_scoped_token_request(30, scopes.clone()))
+        .await;
+    assert_eq!(response.errors.len(), 0, "{:?}", response.errors);
+    let actual = extract_data(&response.data, &["generateToken", "token", "scopes"]);
+    assert_eq!(actual, scopes);
+
+    // tokens without scopes aren't restricted
+    let response = schema
+        .execute(build_generate_token_request(30, "test token"))
+        .await;
+    assert_eq!(response.errors.len(), 0);
+    let response = schema.execute(build_my_tokens_request(30)).await;
+    let tokens = extract_data(&response.data, &["myTokens"]);
+    assert!(matches!(&tokens, Value::List(tokens) if tokens.len() == 2));
+
+    let scopes = value!({ "crates": ["raktar*cli"] });
+    let response = schema.execute(build_scoped_token_request(30, scopes)).await;
+    assert_eq!(response.errors.len(), 1);
+}
+
+#[tokio::test]
+async fn test_expiring_token_generation() {
+    let repository = Arc::new(MemoryRepository::new()) as DynRepository;
+    let schema = build_schema(
+        repository,
+        Arc::new(MemoryStorage::default()),
+        build_config(vec![]),
+    );
+
+    let response = schema
+        .execute(build_expiring_token_request(40, "2100-01-01T00:00:00Z"))
+        .await;
+    assert_eq!(response.errors.len(), 0, "{:?}", response.errors);
+    let token = extract_data(&response.data, &["generateToken", "token"]);
+    assert_eq!(
+        extract_data(&token, &["expiresAt"]),
+        Value::String("2100-01-01T00:00:00Z".to_string())
+    );
+    assert!(matches!(
+        extract_data(&token, &["createdAt"]),
+        Value::String(_)
+    ));
+    assert_eq!(extract_data(&token, &["lastUsedAt"]), Value::Null);
+
+    let response = schema
+        .execute(build_expiring_token_request(40, "2000-01-01T00:00:00Z"))
+        .await;
+    assert_eq!(response.errors.len(), 1);
+    let response = schema
+        .execute(build_expiring_token_request(40, "tomorrow"))
+        .await;
+    assert_eq!(response.errors.len(), 1);
+}
+
 fn extract_data(data: &Value, path: &[&str]) -> Value {
     let mut actual = data.clone();
     for p in path {
@@ -134,6 +212,41 @@ fn build_generate_token_request(user_id: u32, name: &str) -> Request {
     build_request(mutation, user_id).variables(variables)
 }
 
+fn build_scoped_token_request(user_id: u32, scopes: Value) -> Request {
+    let mutation = r#"
+    mutation GenerateToken($scopes: TokenScopesInput!) {
+        generateToken(name: "ci", scopes: $scopes) {
+            token {
+                scopes {
+                    endpoints
+                    crates
+                }
+            }
+        }
+    }
+    "#;
+    let variables = Variables::from_value(value!({ "scopes": scopes }));
+
+    build_request(mutation, user_id).variables(variables)
+}
+
+fn build_expiring_token_request(user_id: u32, expires_at: &str) -> Request {
+    let mutation = r#"
+    mutation GenerateToken($expiresAt: String!) {
+        generateToken(name: "ci", expiresAt: $expiresAt) {
+            token {
+                createdAt
+                expiresAt
+                lastUsedAt
+            }
+        }
+    }
+    "#;
+    let variables = Variables::from_value(value!({ "expiresAt": expires_at }));
+
+    build_request(mutation, user_id).variables(variables)
+}
+
 fn build_delete_token_request(user_id: u32, token_id: String) -> Request {
     let mutation = r#"
     mutation DeleteToken($tokenId: String!) {
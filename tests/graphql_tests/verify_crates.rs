@@ -0,0 +1,91 @@
+use async_graphql::value;
+use raktar::auth::AuthenticatedUser;
+use raktar::cargo_api::publish::publish_crate;
+use raktar::config::PublishLimits;
+use raktar::graphql::schema::{build_schema, RaktarSchema};
+use raktar::models::token::TokenScopes;
+use raktar::repository::{DynRepository, MemoryRepository};
+use raktar::storage::{CrateData, DynCrateStorage};
+use semver::Version;
+use std::sync::Arc;
+
+use crate::common::fixtures::{CRATE_BYTES_V1, CRATE_BYTES_V2};
+use crate::common::graphql::build_request;
+use crate::common::memory_storage::MemoryStorage;
+use crate::common::setup::build_config;
+
+const VERIFY_CRATES: &str = r#"
+    mutation {
+        verifyCrates {
+            crateName
+            version
+            actual
+        }
+    }
+"#;
+
+async fn build_published_schema() -> (RaktarSchema, DynCrateStorage) {
+    let repository = Arc::new(MemoryRepository::new()) as DynRepository;
+    let storage = Arc::new(MemoryStorage::default()) as DynCrateStorage;
+    for data in [&CRATE_BYTES_V1[..], &CRATE_BYTES_V2[..]] {
+        publish_crate(
+            AuthenticatedUser { id: 1 },
+            &TokenScopes::default(),
+            None,
+            storage.clone(),
+            repository.clone(),
+            &PublishLimits::default(),
+            data,
+        )
+        .await
+        .unwrap();
+    }
+
+    let schema = build_schema(repository, storage.clone(), build_config(vec![1]));
+    (schema, storage)
+}
+
+#[tokio::test]
+async fn test_admins_can_verify_crates() {
+    let (schema, storage) = build_published_schema().await;
+
+    let response = schema.execute(build_request(VERIFY_CRATES, 1)).await;
+    assert_eq!(response.errors.len(), 0);
+    assert_eq!(response.data, value!({ "verifyCrates": [] }));
+
+    // storage only stores new files, so the published one has to go first
+    storage
+        .delete_crate("testcrate_1", Version::new(0, 1, 2))
+        .await
+        .unwrap();
+    storage
+        .store_crate(
+            "testcrate_1",
+            Version::new(0, 1, 2),
+            CrateData::from_bytes(&b"corrupted"[..]),
+        )
+        .await
+        .unwrap();
+    let response = schema.execute(build_request(VERIFY_CRATES, 1)).await;
+    assert_eq!(response.errors.len(), 0);
+    assert_eq!(
+        response.data,
+        value!({
+            "verifyCrates": [{
+                "crateName": "testcrate_1",
+                "version": "0.1.2",
+                "actual": "3dbb3963d11aa418de8b61f846c3dbd5af43b40d252842adb823f90936fe6920",
+            }]
+        })
+    );
+}
+
+#[tokio::test]
+async fn test_other_users_cant_verify_crates() {
+    let (schema, _) = build_published_schema().await;
+
+    let response = schema.execute(build_request(VERIFY_CRATES, 2)).await;
+
+    assert_eq!(response.errors.len(), 1);
+    assert_eq!(response.errors[0].message, "only admins can verify crates");
+}
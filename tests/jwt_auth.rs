@@ -0,0 +1,166 @@
+mod common;
+
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use axum::body::{Body, HttpBody};
+use axum::http::{Request, StatusCode};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use hyper::service::Service;
+use jsonwebtoken::{encode, Algorithm, EncodingKey, Header};
+use raktar::auth::JwtVerifier;
+use raktar::config::{JwksSource, JwtConfig};
+use raktar::error::AppError;
+use raktar::repository::{DynRepository, MemoryRepository};
+use raktar::router::build_router;
+use raktar::storage::DynCrateStorage;
+use serde_json::{json, Value};
+
+use common::memory_storage::MemoryStorage;
+use common::setup::{build_config, JWT_AUDIENCE, JWT_ISSUER};
+
+const SIGNING_KEY: &[u8] = include_bytes!("fixtures/jwt_signing_key.pem");
+/// A key that isn't in the JWKS, used to sign tokens that claim to use the trusted key.
+const UNTRUSTED_KEY: &[u8] = include_bytes!("fixtures/jwt_untrusted_key.pem");
+const KEY_ID: &str = "raktar-test-key";
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+fn claims() -> Value {
+    json!({
+        "autogen_id": "42",
+        "iss": JWT_ISSUER,
+        "aud": JWT_AUDIENCE,
+        "iat": now(),
+        "exp": now() + 3600,
+    })
+}
+
+fn sign(claims: &Value, key: &[u8]) -> String {
+    let mut header = Header::new(Algorithm::RS256);
+    header.kid = Some(KEY_ID.to_string());
+    encode(&header, claims, &EncodingKey::from_rsa_pem(key).unwrap()).unwrap()
+}
+
+fn build_verifier() -> JwtVerifier {
+    JwtVerifier::new(&build_config(vec![]).auth.jwt)
+}
+
+async fn assert_rejected(verifier: &JwtVerifier, token: &str) {
+    let result = verifier.verify(token).await;
+    assert!(
+        matches!(result, Err(AppError::Unauthorized(_))),
+        "unexpected result: {:?}",
+        result
+    );
+}
+
+#[tokio::test]
+async fn test_valid_token_is_accepted() {
+    let verifier = build_verifier();
+
+    let user = verifier
+        .verify(&sign(&claims(), SIGNING_KEY))
+        .await
+        .unwrap();
+
+    assert_eq!(user.id, 42);
+}
+
+#[tokio::test]
+async fn test_expired_token_is_rejected() {
+    let mut claims = claims();
+    claims["exp"] = json!(now() - 3600);
+
+    assert_rejected(&build_verifier(), &sign(&claims, SIGNING_KEY)).await;
+}
+
+#[tokio::test]
+async fn test_wrongly_signed_token_is_rejected() {
+    assert_rejected(&build_verifier(), &sign(&claims(), UNTRUSTED_KEY)).await;
+}
+
+#[tokio::test]
+async fn test_token_for_another_issuer_or_audience_is_rejected() {
+    let verifier = build_verifier();
+
+    let mut claims_with_issuer = claims();
+    claims_with_issuer["iss"] = json!("https://example.com");
+    assert_rejected(&verifier, &sign(&claims_with_issuer, SIGNING_KEY)).await;
+
+    let mut claims_with_audience = claims();
+    claims_with_audience["aud"] = json!("another-client");
+    assert_rejected(&verifier, &sign(&claims_with_audience, SIGNING_KEY)).await;
+}
+
+#[tokio::test]
+async fn test_unsigned_token_is_rejected() {
+    let header = URL_SAFE_NO_PAD.encode(json!({ "alg": "none", "kid": KEY_ID }).to_string());
+    let payload = URL_SAFE_NO_PAD.encode(claims().to_string());
+
+    assert_rejected(&build_verifier(), &format!("{}.{}.", header, payload)).await;
+}
+
+async fn query_my_tokens(authorization: &str) -> Value {
+    let repository = Arc::new(MemoryRepository::new()) as DynRepository;
+    let storage = Arc::new(MemoryStorage::default()) as DynCrateStorage;
+    let mut router = build_router(repository, storage, build_config(vec![]));
+    let request = Request::post("/gql")
+        .header("Authorization", authorization)
+        .header("Content-Type", "application/json")
+        .body(Body::from(
+            json!({ "query": "{ myTokens { id } }" }).to_string(),
+        ))
+        .unwrap();
+
+    let response = router.call(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = response.into_body().data().await.unwrap().unwrap();
+    serde_json::from_slice(&body).unwrap()
+}
+
+#[tokio::test]
+async fn test_graphql_requires_a_verified_token() {
+    let token = sign(&claims(), SIGNING_KEY);
+    let response = query_my_tokens(&format!("Bearer {}", token)).await;
+    assert_eq!(response, json!({ "data": { "myTokens": [] } }));
+
+    // a token with the right claims that was never signed by the identity provider
+    let forged = sign(&claims(), UNTRUSTED_KEY);
+    let response = query_my_tokens(&format!("Bearer {}", forged)).await;
+    assert!(response["errors"].is_array(), "{}", response);
+    assert!(response["data"].is_null(), "{}", response);
+}
+
+#[tokio::test]
+async fn test_unresponsive_jwks_fetch_times_out() {
+    // accepts the connection but never answers
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    let server = tokio::spawn(async move {
+        let (_socket, _) = listener.accept().await.unwrap();
+        std::future::pending::<()>().await;
+    });
+    let config = JwtConfig {
+        jwks: JwksSource::Url(format!("http://{}/jwks.json", address).parse().unwrap()),
+        issuer: JWT_ISSUER.to_string(),
+        audience: JWT_AUDIENCE.to_string(),
+    };
+    let verifier = JwtVerifier::new(&config).with_fetch_timeout(Duration::from_millis(100));
+
+    let result = tokio::time::timeout(
+        Duration::from_secs(5),
+        verifier.verify(&sign(&claims(), SIGNING_KEY)),
+    )
+    .await
+    .expect("the fetch to time out");
+
+    assert!(result.is_err());
+    server.abort();
+}
@@ -0,0 +1,149 @@
+mod common;
+
+use raktar::auth::AuthenticatedUser;
+use raktar::cargo_api::publish::publish_crate;
+use raktar::config::PublishLimits;
+use raktar::error::{AppError, AppResult};
+use raktar::models::token::TokenScopes;
+use raktar::models::user::CognitoUserData;
+use raktar::repository::{DynRepository, MemoryRepository, TokenRepository, UserRepository};
+use raktar::storage::DynCrateStorage;
+use semver::Version;
+use std::sync::Arc;
+
+use common::fixtures::{CRATE_BYTES_V1, CRATE_BYTES_V2};
+use common::memory_storage::MemoryStorage;
+
+fn build() -> (DynCrateStorage, DynRepository) {
+    let storage = Arc::new(MemoryStorage::default()) as DynCrateStorage;
+    let repository = Arc::new(MemoryRepository::new()) as DynRepository;
+    (storage, repository)
+}
+
+#[tokio::test]
+async fn test_duplicate_versions_are_rejected() {
+    let (storage, repository) = build();
+    let user = AuthenticatedUser { id: 1 };
+
+    publish_crate(
+        user.clone(),
+        &TokenScopes::default(),
+        None,
+        storage.clone(),
+        repository.clone(),
+        &PublishLimits::default(),
+        &CRATE_BYTES_V1[..],
+    )
+    .await
+    .expect("publish to succeed");
+
+    let result = publish_crate(
+        user,
+        &TokenScopes::default(),
+        None,
+        storage,
+        repository,
+        &PublishLimits::default(),
+        &CRATE_BYTES_V1[..],
+    )
+    .await;
+
+    assert!(matches!(
+        result,
+        AppResult::Err(AppError::DuplicateCrateVersion { .. })
+    ));
+}
+
+#[tokio::test]
+async fn test_only_owner_can_publish() {
+    let (storage, repository) = build();
+
+    publish_crate(
+        AuthenticatedUser { id: 1 },
+        &TokenScopes::default(),
+        None,
+        storage.clone(),
+        repository.clone(),
+        &PublishLimits::default(),
+        &CRATE_BYTES_V1[..],
+    )
+    .await
+    .expect("publish to succeed");
+
+    let result = publish_crate(
+        AuthenticatedUser { id: 2 },
+        &TokenScopes::default(),
+        None,
+        storage,
+        repository,
+        &PublishLimits::default(),
+        &CRATE_BYTES_V2[..],
+    )
+    .await;
+
+    assert!(matches!(result, AppResult::Err(AppError::Unauthorized(_))))
+}
+
+#[tokio::test]
+async fn test_yanking_missing_version_fails() {
+    let (storage, repository) = build();
+
+    publish_crate(
+        AuthenticatedUser { id: 1 },
+        &TokenScopes::default(),
+        None,
+        storage,
+        repository.clone(),
+        &PublishLimits::default(),
+        &CRATE_BYTES_V1[..],
+    )
+    .await
+    .expect("publish to succeed");
+
+    repository
+        .set_yanked("testcrate_1", &Version::new(0, 1, 1), true)
+        .await
+        .expect("yank to succeed");
+    let result = repository
+        .set_yanked("testcrate_1", &Version::new(0, 2, 0), true)
+        .await;
+
+    assert!(matches!(
+        result,
+        AppResult::Err(AppError::NonExistentCrateVersion { .. })
+    ));
+}
+
+#[tokio::test]
+async fn test_tokens_are_looked_up_by_key() {
+    let repository = MemoryRepository::new();
+
+    let token = repository
+        .store_auth_token(b"secret", "ci".to_string(), 7, TokenScopes::default(), None)
+        .await
+        .unwrap();
+
+    let found = repository.get_auth_token(b"secret").await.unwrap().unwrap();
+    assert_eq!(found.token_id, token.token_id);
+    assert_eq!(found.user_id, 7);
+    assert!(repository.get_auth_token(b"other").await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_users_are_not_recreated() {
+    let repository = MemoryRepository::new();
+    let user_data = CognitoUserData {
+        login: "test@raktar.io".to_string(),
+        given_name: "Bruce".to_string(),
+        family_name: "Wayne".to_string(),
+    };
+
+    let user = repository
+        .update_or_create_user(user_data.clone())
+        .await
+        .unwrap();
+    let user2 = repository.update_or_create_user(user_data).await.unwrap();
+
+    assert_eq!(user.id, 1);
+    assert_eq!(user, user2);
+}
@@ -0,0 +1,230 @@
+mod common;
+
+use std::sync::Arc;
+
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use hyper::service::Service;
+use pasetors::keys::{AsymmetricKeyPair, Generate};
+use pasetors::paserk::FormatAsPaserk;
+use pasetors::version3::{PublicToken, V3};
+use raktar::auth::{public_key_id, AuthenticatedUser};
+use raktar::models::token::{format_timestamp, now};
+use raktar::repository::{DynRepository, MemoryRepository};
+use raktar::router::build_router;
+use raktar::storage::DynCrateStorage;
+use serde_json::{json, Value};
+use time::Duration;
+
+use common::conformance::publish;
+use common::memory_storage::MemoryStorage;
+use common::setup::build_config;
+
+/// The index URL cargo puts in the footer, for the `localhost` domain of the test config.
+const INDEX_URL: &str = "sparse+https://localhost/";
+
+struct Signer {
+    key_pair: AsymmetricKeyPair<V3>,
+    key_id: String,
+}
+
+impl Signer {
+    fn new() -> Self {
+        let key_pair = AsymmetricKeyPair::<V3>::generate().unwrap();
+        let key_id = public_key_id(&paserk(&key_pair)).unwrap();
+        Self { key_pair, key_id }
+    }
+
+    async fn register(&self, repository: &DynRepository) {
+        repository
+            .store_public_key(
+                self.key_id.clone(),
+                paserk(&self.key_pair),
+                "laptop".to_string(),
+                1,
+            )
+            .await
+            .unwrap();
+    }
+
+    fn sign(&self, claims: Value, url: &str) -> String {
+        let footer = json!({ "url": url, "kip": self.key_id });
+        PublicToken::sign(
+            &self.key_pair.secret,
+            claims.to_string().as_bytes(),
+            Some(footer.to_string().as_bytes()),
+            None,
+        )
+        .unwrap()
+    }
+}
+
+fn paserk(key_pair: &AsymmetricKeyPair<V3>) -> String {
+    let mut public_key = String::new();
+    key_pair.public.fmt(&mut public_key).unwrap();
+    public_key
+}
+
+fn issued_now() -> String {
+    format_timestamp(now())
+}
+
+async fn call(repository: &DynRepository, request: Request<Body>) -> StatusCode {
+    let storage = Arc::new(MemoryStorage::default()) as DynCrateStorage;
+    let mut router = build_router(repository.clone(), storage, build_config(vec![]));
+
+    router.call(request).await.unwrap().status()
+}
+
+fn read_index(token: &str) -> Request<Body> {
+    Request::get("/te/st/testcrate")
+        .header("Authorization", token)
+        .body(Body::empty())
+        .unwrap()
+}
+
+fn yank(token: &str) -> Request<Body> {
+    Request::delete("/api/v1/crates/testcrate/0.1.0/yank")
+        .header("Authorization", token)
+        .body(Body::empty())
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_signed_tokens_can_read_the_registry() {
+    let repository = Arc::new(MemoryRepository::new()) as DynRepository;
+    let signer = Signer::new();
+    signer.register(&repository).await;
+
+    let token = signer.sign(json!({ "iat": issued_now(), "v": 1 }), INDEX_URL);
+    // the crate doesn't exist, but the request got past authentication
+    assert_eq!(
+        call(&repository, read_index(&token)).await,
+        StatusCode::NOT_FOUND
+    );
+    // reading doesn't use the token up
+    assert_eq!(
+        call(&repository, read_index(&token)).await,
+        StatusCode::NOT_FOUND
+    );
+}
+
+#[tokio::test]
+async fn test_invalid_signed_tokens_are_rejected() {
+    let repository = Arc::new(MemoryRepository::new()) as DynRepository;
+    let signer = Signer::new();
+    signer.register(&repository).await;
+
+    let other_registry = signer.sign(json!({ "iat": issued_now() }), "sparse+https://other/");
+    let stale = format_timestamp(now() - Duration::hours(1));
+    let stale = signer.sign(json!({ "iat": stale }), INDEX_URL);
+    let unknown_version = signer.sign(json!({ "iat": issued_now(), "v": 2 }), INDEX_URL);
+    let unregistered = Signer::new().sign(json!({ "iat": issued_now() }), INDEX_URL);
+    // a key pair pretending to be the registered one
+    let impostor = Signer {
+        key_id: signer.key_id.clone(),
+        ..Signer::new()
+    };
+    let forged = impostor.sign(json!({ "iat": issued_now() }), INDEX_URL);
+
+    for token in [
+        other_registry,
+        stale,
+        unknown_version,
+        unregistered,
+        forged,
+        "v3.public.garbage".to_string(),
+    ] {
+        assert_eq!(
+            call(&repository, read_index(&token)).await,
+            StatusCode::UNAUTHORIZED
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_mutation_tokens_can_only_be_used_once() {
+    let repository = Arc::new(MemoryRepository::new()) as DynRepository;
+    let signer = Signer::new();
+    signer.register(&repository).await;
+
+    let claims = json!({
+        "iat": issued_now(),
+        "mutation": "yank",
+        "name": "testcrate",
+        "vers": "0.1.0",
+    });
+    let token = signer.sign(claims, INDEX_URL);
+    assert_eq!(call(&repository, yank(&token)).await, StatusCode::NOT_FOUND);
+    assert_eq!(
+        call(&repository, yank(&token)).await,
+        StatusCode::UNAUTHORIZED
+    );
+}
+
+#[tokio::test]
+async fn test_challenges_are_rejected() {
+    let repository = Arc::new(MemoryRepository::new()) as DynRepository;
+    let signer = Signer::new();
+    signer.register(&repository).await;
+
+    // the registry never issued it
+    let claims = json!({ "iat": issued_now(), "challenge": "abc" });
+    let token = signer.sign(claims, INDEX_URL);
+    assert_eq!(
+        call(&repository, read_index(&token)).await,
+        StatusCode::UNAUTHORIZED
+    );
+}
+
+#[tokio::test]
+async fn test_mutation_tokens_only_allow_the_signed_request() {
+    let repository = Arc::new(MemoryRepository::new()) as DynRepository;
+    let signer = Signer::new();
+    signer.register(&repository).await;
+    publish(
+        &repository,
+        "testcrate",
+        "0.1.0",
+        &AuthenticatedUser { id: 1 },
+    )
+    .await;
+
+    let other_version = json!({
+        "iat": issued_now(),
+        "mutation": "yank",
+        "name": "testcrate",
+        "vers": "0.2.0",
+    });
+    let token = signer.sign(other_version, INDEX_URL);
+    assert_eq!(call(&repository, yank(&token)).await, StatusCode::FORBIDDEN);
+
+    let unyank = json!({
+        "iat": issued_now(),
+        "mutation": "unyank",
+        "name": "testcrate",
+        "vers": "0.1.0",
+    });
+    let token = signer.sign(unyank, INDEX_URL);
+    assert_eq!(call(&repository, yank(&token)).await, StatusCode::FORBIDDEN);
+
+    let read_only = signer.sign(json!({ "iat": issued_now() }), INDEX_URL);
+    assert_eq!(
+        call(&repository, yank(&read_only)).await,
+        StatusCode::FORBIDDEN
+    );
+
+    // a publish token has to name the checksum of the crate file
+    let publish = json!({
+        "iat": issued_now(),
+        "mutation": "publish",
+        "name": "testcrate",
+        "vers": "0.1.0",
+    });
+    let token = signer.sign(publish, INDEX_URL);
+    let publish = Request::put("/api/v1/crates/new")
+        .header("Authorization", &token)
+        .body(Body::empty())
+        .unwrap();
+    assert_eq!(call(&repository, publish).await, StatusCode::UNAUTHORIZED);
+}
@@ -0,0 +1,93 @@
+mod common;
+
+use aws_sdk_dynamodb::types::AttributeValue;
+use raktar::models::crate_summary::CrateSummary;
+use raktar::repository::{CrateRepository, DynRepository, DynamoDBRepository, MemoryRepository};
+use semver::Version;
+use serde_dynamo::to_item;
+use std::sync::Arc;
+
+use common::conformance::run_conformance_suite;
+use common::setup::{
+    build_postgres_repository, build_repository, build_sqlite_repository, create_db_client,
+};
+
+#[tokio::test]
+async fn test_memory_repository_conformance() {
+    run_conformance_suite(|| async { Arc::new(MemoryRepository::new()) as DynRepository }).await;
+}
+
+#[tokio::test]
+async fn test_sqlite_repository_conformance() {
+    run_conformance_suite(|| async { Arc::new(build_sqlite_repository().await) as DynRepository })
+        .await;
+}
+
+#[tokio::test]
+async fn test_postgres_repository_conformance() {
+    // only runs against a server given in TEST_POSTGRES_URL
+    if build_postgres_repository().await.is_none() {
+        return;
+    }
+
+    run_conformance_suite(|| async {
+        let repository = build_postgres_repository()
+            .await
+            .expect("PostgreSQL to be configured");
+        Arc::new(repository) as DynRepository
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_dynamodb_repository_conformance() {
+    run_conformance_suite(|| async { Arc::new(build_repository().await) as DynRepository }).await;
+}
+
+#[tokio::test]
+async fn test_dynamodb_repository_pagination() {
+    // small pages make every multi-item read cross page boundaries
+    run_conformance_suite(|| async {
+        Arc::new(build_repository().await.with_page_size(2)) as DynRepository
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_dynamodb_crate_names_are_backfilled() {
+    let (db_client, table_name) = create_db_client().await;
+    // a crate published before canonical names were recorded
+    let legacy = CrateSummary {
+        name: "Foo-Bar".to_string(),
+        owners: vec![1],
+        max_version: Version::new(0, 1, 0),
+        description: "".to_string(),
+    };
+    db_client
+        .put_item()
+        .table_name(&table_name)
+        .set_item(Some(to_item(legacy).unwrap()))
+        .item("pk", AttributeValue::S("CRATES".to_string()))
+        .item("sk", AttributeValue::S("Foo-Bar".to_string()))
+        .send()
+        .await
+        .unwrap();
+    let repository = DynamoDBRepository::new(db_client, table_name);
+    assert_eq!(
+        repository.resolve_crate_name("foo_bar").await.unwrap(),
+        None
+    );
+
+    repository.migrate().await.unwrap();
+    assert_eq!(
+        repository.resolve_crate_name("foo_bar").await.unwrap(),
+        Some("Foo-Bar".to_string())
+    );
+    // cargo asks for lowercased index paths
+    assert_eq!(
+        repository.resolve_crate_name("foo-bar").await.unwrap(),
+        Some("Foo-Bar".to_string())
+    );
+    // migrations only run once
+    repository.migrate().await.unwrap();
+}
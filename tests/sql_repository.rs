@@ -0,0 +1,199 @@
+mod common;
+
+use raktar::auth::AuthenticatedUser;
+use raktar::cargo_api::publish::publish_crate;
+use raktar::config::PublishLimits;
+use raktar::error::{AppError, AppResult};
+use raktar::models::token::TokenScopes;
+use raktar::models::user::CognitoUserData;
+use raktar::repository::{DynRepository, SqlRepository};
+use raktar::storage::DynCrateStorage;
+use semver::Version;
+use std::sync::Arc;
+
+use common::fixtures::{CRATE_BYTES_V1, CRATE_BYTES_V2};
+use common::memory_storage::MemoryStorage;
+
+async fn build() -> (DynCrateStorage, DynRepository) {
+    let storage = Arc::new(MemoryStorage::default()) as DynCrateStorage;
+    let repository = SqlRepository::connect("sqlite::memory:")
+        .await
+        .expect("to be able to connect to SQLite");
+    (storage, Arc::new(repository) as DynRepository)
+}
+
+#[tokio::test]
+async fn test_publishing_updates_head_version() {
+    let (storage, repository) = build().await;
+    let user = AuthenticatedUser { id: 1 };
+
+    for data in [CRATE_BYTES_V1, CRATE_BYTES_V2] {
+        publish_crate(
+            user.clone(),
+            &TokenScopes::default(),
+            None,
+            storage.clone(),
+            repository.clone(),
+            &PublishLimits::default(),
+            &data[..],
+        )
+        .await
+        .expect("publish to succeed");
+    }
+
+    let summary = repository
+        .get_crate_summary("testcrate_1")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(summary.max_version, Version::new(0, 1, 2));
+    assert_eq!(summary.owners, vec![1]);
+
+    let versions = repository.list_crate_versions("testcrate_1").await.unwrap();
+    assert_eq!(versions, vec![Version::new(0, 1, 1), Version::new(0, 1, 2)]);
+
+    let metadata = repository
+        .get_crate_metadata("testcrate_1", &Version::new(0, 1, 1))
+        .await
+        .unwrap();
+    assert!(metadata.is_some());
+}
+
+#[tokio::test]
+async fn test_duplicate_versions_are_rejected() {
+    let (storage, repository) = build().await;
+    let user = AuthenticatedUser { id: 1 };
+
+    publish_crate(
+        user.clone(),
+        &TokenScopes::default(),
+        None,
+        storage.clone(),
+        repository.clone(),
+        &PublishLimits::default(),
+        &CRATE_BYTES_V1[..],
+    )
+    .await
+    .expect("publish to succeed");
+
+    let result = publish_crate(
+        user,
+        &TokenScopes::default(),
+        None,
+        storage,
+        repository,
+        &PublishLimits::default(),
+        &CRATE_BYTES_V1[..],
+    )
+    .await;
+
+    assert!(matches!(
+        result,
+        AppResult::Err(AppError::DuplicateCrateVersion { .. })
+    ));
+}
+
+#[tokio::test]
+async fn test_failed_publish_leaves_nothing_behind() {
+    let (storage, repository) = build().await;
+
+    publish_crate(
+        AuthenticatedUser { id: 1 },
+        &TokenScopes::default(),
+        None,
+        storage.clone(),
+        repository.clone(),
+        &PublishLimits::default(),
+        &CRATE_BYTES_V1[..],
+    )
+    .await
+    .expect("publish to succeed");
+
+    let result = publish_crate(
+        AuthenticatedUser { id: 2 },
+        &TokenScopes::default(),
+        None,
+        storage,
+        repository.clone(),
+        &PublishLimits::default(),
+        &CRATE_BYTES_V2[..],
+    )
+    .await;
+
+    assert!(matches!(result, AppResult::Err(AppError::Unauthorized(_))));
+    let versions = repository.list_crate_versions("testcrate_1").await.unwrap();
+    assert_eq!(versions, vec![Version::new(0, 1, 1)]);
+}
+
+#[tokio::test]
+async fn test_yanked_state_is_reflected_in_the_index() {
+    let (storage, repository) = build().await;
+
+    publish_crate(
+        AuthenticatedUser { id: 1 },
+        &TokenScopes::default(),
+        None,
+        storage,
+        repository.clone(),
+        &PublishLimits::default(),
+        &CRATE_BYTES_V1[..],
+    )
+    .await
+    .expect("publish to succeed");
+
+    repository
+        .set_yanked("testcrate_1", &Version::new(0, 1, 1), true)
+        .await
+        .unwrap();
+    let info = repository.get_package_info("testcrate_1").await.unwrap();
+    assert!(info.contains("\"yanked\":true"));
+
+    let result = repository
+        .set_yanked("testcrate_1", &Version::new(0, 2, 0), true)
+        .await;
+    assert!(matches!(
+        result,
+        AppResult::Err(AppError::NonExistentCrateVersion { .. })
+    ));
+}
+
+#[tokio::test]
+async fn test_users_and_tokens() {
+    let (_, repository) = build().await;
+
+    let user_data = CognitoUserData {
+        login: "test@raktar.io".to_string(),
+        given_name: "Bruce".to_string(),
+        family_name: "Wayne".to_string(),
+    };
+    let user = repository
+        .update_or_create_user(user_data.clone())
+        .await
+        .unwrap();
+    let user2 = repository.update_or_create_user(user_data).await.unwrap();
+    assert_eq!(user.id, 1);
+    assert_eq!(user, user2);
+
+    let token = repository
+        .store_auth_token(
+            b"secret",
+            "ci".to_string(),
+            user.id,
+            TokenScopes::default(),
+            None,
+        )
+        .await
+        .unwrap();
+    let found = repository.get_auth_token(b"secret").await.unwrap().unwrap();
+    assert_eq!(found.token_id, token.token_id);
+
+    repository
+        .delete_auth_token(user.id, token.token_id)
+        .await
+        .unwrap();
+    assert!(repository
+        .get_auth_token(b"secret")
+        .await
+        .unwrap()
+        .is_none());
+}
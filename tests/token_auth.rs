@@ -0,0 +1,160 @@
+mod common;
+
+use std::sync::Arc;
+
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use hyper::service::Service;
+use raktar::auth::AuthenticatedUser;
+use raktar::models::token::{now, CratePattern, EndpointScope, TokenScopes};
+use raktar::repository::{DynRepository, MemoryRepository};
+use raktar::router::build_router;
+use raktar::storage::DynCrateStorage;
+use time::{Duration, OffsetDateTime};
+
+use common::conformance::publish;
+use common::memory_storage::MemoryStorage;
+use common::setup::build_config;
+
+async fn store_token(
+    repository: &DynRepository,
+    key: &[u8],
+    scopes: TokenScopes,
+    expires_at: Option<OffsetDateTime>,
+) {
+    repository
+        .store_auth_token(key, "ci".to_string(), 1, scopes, expires_at)
+        .await
+        .unwrap();
+}
+
+async fn call(repository: &DynRepository, request: Request<Body>) -> StatusCode {
+    let storage = Arc::new(MemoryStorage::default()) as DynCrateStorage;
+    let mut router = build_router(repository.clone(), storage, build_config(vec![]));
+
+    router.call(request).await.unwrap().status()
+}
+
+fn list_owners(key: &str) -> Request<Body> {
+    Request::get("/api/v1/crates/testcrate/owners")
+        .header("Authorization", key)
+        .body(Body::empty())
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_expired_tokens_are_rejected() {
+    let repository = Arc::new(MemoryRepository::new()) as DynRepository;
+    store_token(
+        &repository,
+        b"expired",
+        TokenScopes::default(),
+        Some(now() - Duration::minutes(1)),
+    )
+    .await;
+    store_token(
+        &repository,
+        b"valid",
+        TokenScopes::default(),
+        Some(now() + Duration::days(1)),
+    )
+    .await;
+
+    let status = call(&repository, list_owners("expired")).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let status = call(&repository, list_owners("valid")).await;
+    assert_ne!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_token_use_is_recorded() {
+    let repository = Arc::new(MemoryRepository::new()) as DynRepository;
+    store_token(&repository, b"secret", TokenScopes::default(), None).await;
+
+    call(&repository, list_owners("secret")).await;
+
+    let token = repository.get_auth_token(b"secret").await.unwrap().unwrap();
+    let last_used_at = token.last_used_at.expect("the use to be recorded");
+    assert!(now() - last_used_at < Duration::minutes(1));
+}
+
+#[tokio::test]
+async fn test_scopes_are_checked_by_the_endpoints() {
+    let repository = Arc::new(MemoryRepository::new()) as DynRepository;
+    let publish_only = TokenScopes {
+        endpoints: Some(vec![EndpointScope::PublishUpdate]),
+        crates: None,
+    };
+    store_token(&repository, b"secret", publish_only, None).await;
+    publish(
+        &repository,
+        "testcrate",
+        "0.1.0",
+        &AuthenticatedUser { id: 1 },
+    )
+    .await;
+
+    let yank = Request::delete("/api/v1/crates/testcrate/0.1.0/yank")
+        .header("Authorization", "secret")
+        .body(Body::empty())
+        .unwrap();
+    assert_eq!(call(&repository, yank).await, StatusCode::FORBIDDEN);
+
+    // reading the registry is allowed with any token
+    let status = call(&repository, list_owners("secret")).await;
+    assert_ne!(status, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_invalid_versions_are_rejected() {
+    let repository = Arc::new(MemoryRepository::new()) as DynRepository;
+    store_token(&repository, b"secret", TokenScopes::default(), None).await;
+
+    let yank = Request::delete("/api/v1/crates/testcrate/not-a-version/yank")
+        .header("Authorization", "secret")
+        .body(Body::empty())
+        .unwrap();
+    assert_eq!(call(&repository, yank).await, StatusCode::BAD_REQUEST);
+    let unyank = Request::put("/api/v1/crates/testcrate/not-a-version/unyank")
+        .header("Authorization", "secret")
+        .body(Body::empty())
+        .unwrap();
+    assert_eq!(call(&repository, unyank).await, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_endpoints_use_the_published_crate_name() {
+    let repository = Arc::new(MemoryRepository::new()) as DynRepository;
+    let yank_only = TokenScopes {
+        endpoints: Some(vec![EndpointScope::Yank]),
+        crates: Some(vec![CratePattern::try_from("foo-bar".to_string()).unwrap()]),
+    };
+    store_token(&repository, b"secret", yank_only, None).await;
+    publish(
+        &repository,
+        "Foo_Bar",
+        "0.1.0",
+        &AuthenticatedUser { id: 1 },
+    )
+    .await;
+
+    let yank = Request::delete("/api/v1/crates/foo-bar/0.1.0/yank")
+        .header("Authorization", "secret")
+        .body(Body::empty())
+        .unwrap();
+    assert_eq!(call(&repository, yank).await, StatusCode::OK);
+    let versions = repository.list_package_infos("Foo_Bar").await.unwrap();
+    assert!(versions[0].yanked);
+
+    let owners = Request::get("/api/v1/crates/foo_bar/owners")
+        .header("Authorization", "secret")
+        .body(Body::empty())
+        .unwrap();
+    assert_eq!(call(&repository, owners).await, StatusCode::OK);
+
+    let unknown = Request::delete("/api/v1/crates/foo-baz/0.1.0/yank")
+        .header("Authorization", "secret")
+        .body(Body::empty())
+        .unwrap();
+    assert_eq!(call(&repository, unknown).await, StatusCode::NOT_FOUND);
+}